@@ -1,39 +1,127 @@
-use criterion::{Criterion, criterion_group, criterion_main};
-
-use dlx::choose::{Choose, mrv_chooser, no_tiebreak, prefer_any};
-use dlx::x::{INodes, ONodes, Problem};
-use dlx::{OptOrder, Solve, Solver, Uint};
-
-fn bench_dance(c: &mut Criterion) {
-    let items = INodes::new(7, 0);
-    let os: Vec<Vec<Uint>> = vec![
-        vec![2, 4],
-        vec![0, 3, 6],
-        vec![1, 2, 5],
-        vec![0, 3, 5],
-        vec![1, 6],
-        vec![3, 4, 6],
-    ];
-    let opts = ONodes::new(7, 7, &os, OptOrder::Seq);
-    let problem = Problem::new(items, opts);
-    let mut solver = Solver::new(problem);
-    let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
-    c.bench_function("dance", |b| {
-        b.iter(|| {
-            solve(&mut solver, &mut chooser);
-        })
-    });
-}
-
-fn solve<S: Solve, C: Choose<S>>(
-    solver: &mut Solver<S>, chooser: &mut C,
-) -> usize {
-    let mut i = 0;
-    while solver.next_solution(chooser) {
-        i += 1;
-    }
-    i
-}
-
-criterion_group!(benches, bench_dance);
-criterion_main!(benches);
+use criterion::{Criterion, criterion_group, criterion_main};
+
+use dlx::Solve;
+use dlx::bits::{self, BitSolver};
+use dlx::choose::{Choose, mrv_chooser, no_tiebreak, prefer_any};
+use dlx::x::{INodes, ONodes};
+use dlx_omino::{SpecInfo, pentominoes, rectangle};
+
+include!("../examples/common/sudoku.rs");
+
+fn bench_dance(c: &mut Criterion) {
+    let items = INodes::new(7, 0, ItemOrder::Seq);
+    let os: Vec<Vec<Uint>> = vec![
+        vec![2, 4],
+        vec![0, 3, 6],
+        vec![1, 2, 5],
+        vec![0, 3, 5],
+        vec![1, 6],
+        vec![3, 4, 6],
+    ];
+    let opts = ONodes::new(7, 7, &os, OptOrder::Seq);
+    let mut problem = Problem::new(items, opts);
+    let mut solver = Solver::new(&mut problem);
+    let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+    c.bench_function("dance", |b| {
+        b.iter(|| {
+            solve(&mut solver, &mut chooser);
+        })
+    });
+}
+
+// The sudoku problems only have items for the cells, rows, columns and
+// boxes that the clues leave open, 324 less 4 per clue, so with 17 or more
+// clues they fit the bitset backend.
+fn bench_sudoku(c: &mut Criterion) {
+    let puzzles = [
+        (
+            "sudoku-30",
+            "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+        ),
+        (
+            "sudoku-20",
+            "12.3..4..5..4..1......2..6.7...........7...31....547..4..5..3...8.........9.4....",
+        ),
+    ];
+    for (name, sdm) in puzzles {
+        let clues = Clues::from_sdm(sdm);
+        let (mut problem, os, names) =
+            clues.make_problem(OptOrder::Seq, ItemOrder::Seq);
+        let mut solver = Solver::new(&mut problem);
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        c.bench_function(&format!("{}/links", name), |b| {
+            b.iter(|| solve(&mut solver, &mut chooser))
+        });
+        let np = Uint(names.len());
+        let problem: bits::Problem<4> =
+            bits::Problem::new(np, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        let mut solver = BitSolver::new(&problem);
+        c.bench_function(&format!("{}/bits", name), |b| {
+            b.iter(|| {
+                solver.reset();
+                let mut i = 0;
+                while solver.next_solution() {
+                    i += 1;
+                }
+                i
+            })
+        });
+    }
+}
+
+struct Pentominoes;
+impl SpecInfo for Pentominoes {
+    type OData = Uint;
+    const PIECE_COUNT: usize = 12;
+    const CELL_COUNT: usize = 60;
+}
+
+// The options for packing the pentominoes into a rows x cols box, with the
+// piece `fixed` in a single orientation to skip symmetric solutions.
+fn pentomino_options(rows: usize, cols: usize, fixed: usize) -> Vec<Vec<Uint>> {
+    let bx = rectangle(rows, cols);
+    let mut os = Vec::new();
+    for (i, p) in pentominoes().iter().enumerate() {
+        let ts = p.transform(255);
+        let ts = if i == fixed { &ts[..1] } else { &ts[..] };
+        for t in ts {
+            os.extend(t.all_options(Uint(i), &bx, &Pentominoes));
+        }
+    }
+    os
+}
+
+fn bench_pentomino(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pentomino");
+    group.sample_size(10);
+    let os = pentomino_options(3, 20, 7);
+    let mut problem = make_problem(72, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+    let mut solver = Solver::new(&mut problem);
+    let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+    group.bench_function("3x20", |b| {
+        b.iter(|| solve(&mut solver, &mut chooser))
+    });
+    let os = pentomino_options(6, 10, 7);
+    let mut problem = make_problem(72, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+    let mut solver = Solver::new(&mut problem);
+    group.bench_function("6x10-first", |b| {
+        b.iter(|| {
+            solver.next_solution(&mut chooser);
+            solver.reset();
+        })
+    });
+    group.finish();
+}
+
+fn solve<S: Solve, C: Choose<S>>(
+    solver: &mut Solver<S>, chooser: &mut C,
+) -> usize {
+    let mut i = 0;
+    while solver.next_solution(chooser) {
+        i += 1;
+    }
+    i
+}
+
+criterion_group!(benches, bench_dance, bench_sudoku, bench_pentomino);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+extern crate dlx;
+
+use std::fs::File;
+use std::io::BufWriter;
+
+use dlx::choose::*;
+use dlx::m::Problem;
+use dlx::tree::SearchTree;
+use dlx::{ItemOrder, OptOrder, Solver, Spec};
+
+// Langford pairs: place two copies of each of 1..=n in 2n slots so that
+// the copies of k have k numbers between them.
+fn langford(n: usize) -> String {
+    let mut spec = (1..=n).map(|k| k.to_string()).collect::<Vec<_>>();
+    spec.extend((1..=2 * n).map(|j| format!("s{}", j)));
+    let mut lines = vec![spec.join(" ")];
+    for k in 1..=n {
+        for j in 1..2 * n - k {
+            lines.push(format!("{} s{} s{}", k, j, j + k + 1));
+        }
+    }
+    lines.join("\n")
+}
+
+fn write_tree<C: Choose<Problem>>(
+    spec: &Spec, chooser: &mut C, limit: usize, path: &str,
+) {
+    let mut problem =
+        Problem::from_spec(spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+    let mut solver =
+        Solver::with_observer(&mut problem, SearchTree::new(limit));
+    while solver.next_solution(chooser) {}
+    let tree = solver.into_observer();
+    let mut w = BufWriter::new(File::create(path).expect("Couldn't create"));
+    tree.write_dot(&mut problem, &mut w).unwrap();
+    println!("{}: {} nodes", path, tree.nodes().len());
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let n = if args.len() > 1 {
+        args[1].parse().unwrap()
+    } else {
+        4
+    };
+    let spec = Spec::new(&langford(n), false).unwrap();
+    let limit = 500;
+    let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+    write_tree(&spec, &mut chooser, limit, "tree-none.dot");
+    let mut chooser = mrv_chooser(prefer_any(), rnd_tiebreak(12345678));
+    write_tree(&spec, &mut chooser, limit, "tree-rnd.dot");
+    let mut chooser = mrv_chooser(prefer_any(), knuth_tiebreak());
+    write_tree(&spec, &mut chooser, limit, "tree-knuth.dot");
+}
@@ -0,0 +1,198 @@
+use anyhow::{Result, anyhow};
+
+use crate::choose::Choose;
+use crate::nodes::Link;
+use crate::step::{Step, Stepper};
+use crate::{Int, Solve, Solver, Spec};
+use crate::{c, m, mc, x};
+
+pub trait Assume: Solve + Step {}
+
+impl<S: Link> Assume for x::Problem<S> {}
+
+impl Assume for c::Problem {}
+
+impl Assume for m::Problem {}
+
+impl Assume for mc::Problem {}
+
+pub struct Assumptions<'a, P> {
+    stepper: Stepper<'a, P>,
+}
+
+impl<'a, P: Assume> Assumptions<'a, P> {
+    pub fn new(problem: &'a mut P) -> Assumptions<'a, P> {
+        Assumptions { stepper: Stepper::new(problem) }
+    }
+
+    pub fn forced(&self) -> Vec<Int> {
+        self.stepper.selected()
+    }
+
+    // Returns false, leaving the problem unchanged, if the option
+    // conflicts with the options that have already been forced.
+    pub fn force(&mut self, o: Int) -> Result<bool> {
+        self.stepper.apply(o)
+    }
+
+    pub fn release(&mut self) -> Option<Int> {
+        self.stepper.undo()
+    }
+
+    pub fn release_all(&mut self) {
+        while self.release().is_some() {}
+    }
+
+    // Finds a solution that includes the forced options; the options are
+    // listed in the order they were found, followed by the forced options.
+    pub fn solve<C: Choose<P>>(&mut self, chooser: &mut C) -> Option<Vec<Int>> {
+        let mut solver = Solver::new(self.stepper.dance());
+        if !solver.next_solution(chooser) {
+            return None;
+        }
+        let mut solution = solver.fmt_solution().to_vec();
+        solver.reset();
+        solution.extend(self.stepper.selected());
+        Some(solution)
+    }
+
+    pub fn solve_with<C: Choose<P>>(
+        &mut self, os: &[Int], chooser: &mut C,
+    ) -> Result<Option<Vec<Int>>> {
+        let depth = self.stepper.selected().len();
+        let mut consistent = true;
+        for &o in os {
+            match self.force(o) {
+                Ok(true) => {}
+                Ok(false) => {
+                    consistent = false;
+                    break;
+                }
+                Err(e) => {
+                    self.release_to(depth);
+                    return Err(e);
+                }
+            }
+        }
+        let solution = if consistent {
+            self.solve(chooser)
+        } else {
+            None
+        };
+        self.release_to(depth);
+        Ok(solution)
+    }
+
+    // Shrinks an unsolvable set of options, one deletion at a time, until
+    // removing any remaining option would make the problem solvable.
+    // Returns None if the options do not make the problem unsolvable.
+    pub fn min_core<C: Choose<P>>(
+        &mut self, os: &[Int], chooser: &mut C,
+    ) -> Result<Option<Vec<Int>>> {
+        if self.solve_with(os, chooser)?.is_some() {
+            return Ok(None);
+        }
+        let mut core = os.to_vec();
+        let mut k = 0;
+        while k < core.len() {
+            let o = core.remove(k);
+            if self.solve_with(&core, chooser)?.is_some() {
+                core.insert(k, o);
+                k += 1;
+            }
+        }
+        Ok(Some(core))
+    }
+
+    fn release_to(&mut self, depth: usize) {
+        while self.stepper.selected().len() > depth {
+            self.release();
+        }
+    }
+}
+
+pub fn opt_names(spec: &Spec, os: &[Int]) -> Result<Vec<String>> {
+    os.iter()
+        .map(|&o| {
+            usize::try_from(o)
+                .ok()
+                .and_then(|k| spec.opts.get(k))
+                .map(|opt| opt.join(" "))
+                .ok_or_else(|| anyhow!("Option {} is not in the spec", o))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::choose::*;
+    use crate::testing::{XCC_SPEC, assert_restored, xc_problem};
+    use crate::{ItemOrder, OptOrder};
+
+    #[test]
+    fn test_solve_with() {
+        let mut problem = xc_problem();
+        let init = xc_problem();
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        let mut assume = Assumptions::new(&mut problem);
+        let mut solution =
+            assume.solve_with(&[3], &mut chooser).unwrap().unwrap();
+        solution.sort();
+        assert_eq!(solution, vec![0, 3, 4]);
+        assert_eq!(assume.solve_with(&[1], &mut chooser).unwrap(), None);
+        assert!(assume.force(4).unwrap());
+        assert!(!assume.force(1).unwrap(), "conflicting option forced");
+        assert_eq!(assume.forced(), vec![4]);
+        assume.release_all();
+        assert!(assume.solve_with(&[6], &mut chooser).is_err());
+        assert_restored(&mut problem, &init);
+    }
+
+    #[test]
+    fn test_min_core() {
+        let mut problem = xc_problem();
+        let init = xc_problem();
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        let mut assume = Assumptions::new(&mut problem);
+        let core = assume.min_core(&[0, 4, 1], &mut chooser).unwrap();
+        assert_eq!(core, Some(vec![1]));
+        let core = assume.min_core(&[0, 3], &mut chooser).unwrap();
+        assert_eq!(core, None);
+        assert_restored(&mut problem, &init);
+    }
+
+    #[test]
+    fn test_colors() {
+        let spec = Spec::new(XCC_SPEC, false).unwrap();
+        let mut problem =
+            c::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        let mut assume = Assumptions::new(&mut problem);
+        let core = assume.min_core(&[4, 2], &mut chooser).unwrap().unwrap();
+        assert_eq!(opt_names(&spec, &core).unwrap(), vec!["p x:B"]);
+        assert!(opt_names(&spec, &[-1]).is_err());
+        assert!(opt_names(&spec, &[5]).is_err());
+    }
+
+    #[test]
+    fn test_multiplicities() {
+        let spec = Spec::new("2|a b\n0:2|a\na b\nb", false).unwrap();
+        let build =
+            || m::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq);
+        let mut problem = build().unwrap();
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        let mut assume = Assumptions::new(&mut problem);
+        let mut solution =
+            assume.solve_with(&[2], &mut chooser).unwrap().unwrap();
+        solution.sort();
+        assert_eq!(solution, vec![0, 0, 2]);
+        assert!(assume.force(0).unwrap());
+        assert!(assume.force(0).unwrap());
+        assert!(!assume.force(0).unwrap(), "option used too often");
+        assert_eq!(assume.solve(&mut chooser), Some(vec![2, 0, 0]));
+        assume.release_all();
+        assert_restored(&mut problem, &build().unwrap());
+    }
+}
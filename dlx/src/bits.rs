@@ -0,0 +1,527 @@
+use anyhow::{Result, anyhow, bail};
+
+use crate::choose::{mrv_chooser, no_tiebreak, prefer_any};
+use crate::{Int, ItemOrder, OptOrder, Solver, Spec, Uint, x};
+
+// A set of items, one bit each, in W words.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Bits<const W: usize>([u64; W]);
+
+impl<const W: usize> Bits<W> {
+    const EMPTY: Bits<W> = Bits([0; W]);
+
+    #[inline]
+    fn set(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    #[inline]
+    fn remove(&mut self, other: &Bits<W>) {
+        for (a, b) in self.0.iter_mut().zip(&other.0) {
+            *a &= !b;
+        }
+    }
+}
+
+// An exact cover problem with at most 64 * W items, in which each option is
+// a bitmask of its items and the search state is the set of items not yet
+// covered, along with a bitset of the options that still fit. Items are
+// numbered from 0, primary items first.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Problem<const W: usize> {
+    n: usize,
+    primary: Bits<W>,
+    masks: Vec<Bits<W>>,
+    // The options of each item, in the order they are tried
+    item_opts: Vec<Vec<Uint>>,
+    opt_items: Vec<Vec<usize>>,
+    // The options that share an item with each option, as bitsets of
+    // words() words
+    conflicts: Vec<u64>,
+}
+
+impl<const W: usize> Problem<W> {
+    pub const MAX_ITEMS: usize = 64 * W;
+
+    pub fn new(
+        np: Uint, ns: Uint, os: &[Vec<Uint>], order: OptOrder,
+        item_order: ItemOrder,
+    ) -> Problem<W> {
+        let np = np as usize;
+        let n = np + ns as usize;
+        assert!(np > 0, "No primary items");
+        assert!(n <= Self::MAX_ITEMS, "Too many items for {} words", W);
+        // Primary items are renumbered so that branching considers them in
+        // the order of their bits.
+        let mut items = Vec::with_capacity(np);
+        let mut item_order = item_order;
+        for i in 0..np {
+            let k = match &mut item_order {
+                ItemOrder::Seq => items.len(),
+                ItemOrder::Rnd(rng) => {
+                    rng.uniform(items.len() as u32 + 1) as usize
+                }
+            };
+            items.insert(k, i);
+        }
+        let mut pos = (0..n).collect::<Vec<_>>();
+        for (k, &i) in items.iter().enumerate() {
+            pos[i] = k;
+        }
+        let mut primary = Bits::EMPTY;
+        for i in 0..np {
+            primary.set(i);
+        }
+        let mut order = order;
+        let mut masks = Vec::with_capacity(os.len());
+        let mut item_opts = vec![Vec::new(); n];
+        for (m, opt) in os.iter().enumerate() {
+            let mut mask = Bits::EMPTY;
+            for &i in opt {
+                let i = pos[i as usize];
+                mask.set(i);
+                let opts = &mut item_opts[i];
+                // As in the linked version, each option goes last or at a
+                // random place among the item's options
+                let k = match &mut order {
+                    OptOrder::Seq => opts.len(),
+                    OptOrder::Rnd(rng) => {
+                        rng.uniform(opts.len() as u32 + 1) as usize
+                    }
+                };
+                opts.insert(k, m as Uint);
+            }
+            masks.push(mask);
+        }
+        let words = os.len().div_ceil(64);
+        let opt_items = os
+            .iter()
+            .map(|opt| opt.iter().map(|&i| pos[i as usize]).collect())
+            .collect::<Vec<Vec<_>>>();
+        let mut conflicts = vec![0; os.len() * words];
+        for (m, opt) in opt_items.iter().enumerate() {
+            for &i in opt {
+                for &q in &item_opts[i] {
+                    conflicts[m * words + q as usize / 64] |= 1 << (q % 64);
+                }
+            }
+        }
+        Problem { n, primary, masks, item_opts, opt_items, conflicts }
+    }
+
+    pub fn from_spec(
+        spec: &Spec, order: OptOrder, item_order: ItemOrder,
+    ) -> Result<Problem<W>> {
+        let (np, ns, os) = spec_opts(spec, Self::MAX_ITEMS)?;
+        Ok(Problem::new(np, ns, &os, order, item_order))
+    }
+
+    pub fn items(&self) -> usize {
+        self.n
+    }
+
+    fn all(&self) -> Bits<W> {
+        let mut live = Bits::EMPTY;
+        for i in 0..self.n {
+            live.set(i);
+        }
+        live
+    }
+
+    // The number of words in a bitset of options.
+    fn words(&self) -> usize {
+        self.masks.len().div_ceil(64)
+    }
+
+    fn all_lens(&self) -> Vec<u32> {
+        self.item_opts
+            .iter()
+            .map(|opts| opts.len() as u32)
+            .collect()
+    }
+
+    fn all_opts(&self) -> Vec<u64> {
+        let mut fits = vec![!0; self.words()];
+        if !self.masks.len().is_multiple_of(64) {
+            fits[self.words() - 1] = (1 << (self.masks.len() % 64)) - 1;
+        }
+        fits
+    }
+}
+
+// The numbers of primary and secondary items of a spec, and its options as
+// lists of item indexes. Colors are not supported.
+fn spec_opts(spec: &Spec, max: usize) -> Result<(Uint, Uint, Vec<Vec<Uint>>)> {
+    use std::collections::{HashMap, HashSet};
+    let (_, names) = x::INodes::from_spec(spec, ItemOrder::Seq)?;
+    if names.len() > max {
+        bail!("Too many items");
+    }
+    let idx = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name, i as Uint))
+        .collect::<HashMap<_, _>>();
+    let mut os = Vec::new();
+    for opt in &spec.opts {
+        let mut is = Vec::new();
+        let mut used = HashSet::new();
+        for itm in opt {
+            let i = idx.get(itm).ok_or_else(|| anyhow!("Invalid item"))?;
+            if !used.insert(itm) {
+                bail!("Duplicate items in option");
+            }
+            is.push(*i);
+        }
+        os.push(is);
+    }
+    let np = spec.primary.len() as Uint;
+    Ok((np, spec.secondary.len() as Uint, os))
+}
+
+// A level of the search: the item branched on, the position of the option
+// tried in its list, and the items that were uncovered before it.
+#[derive(Clone, Copy, Debug)]
+struct Frame<const W: usize> {
+    i: usize,
+    k: usize,
+    live: Bits<W>,
+}
+
+#[inline]
+fn has(fits: &[u64], m: Uint) -> bool {
+    fits[m as usize / 64] & (1 << (m % 64)) != 0
+}
+
+// Finds the solutions of a bitset problem one at a time, choosing the
+// primary item with the fewest fitting options as the linked solvers do
+// with the MRV heuristic.
+pub struct BitSolver<'a, const W: usize> {
+    problem: &'a Problem<W>,
+    live: Bits<W>,
+    fits: Vec<u64>,
+    // The number of options that fit for each item
+    lens: Vec<u32>,
+    stack: Vec<Frame<W>>,
+    // The options that fit and the lengths before each level
+    saved: Vec<u64>,
+    saved_lens: Vec<u32>,
+    // Whether to branch on a new item, rather than advance the last one
+    enter: bool,
+    done: bool,
+    o: Vec<Int>,
+    nodes: usize,
+}
+
+impl<'a, const W: usize> BitSolver<'a, W> {
+    pub fn new(problem: &'a Problem<W>) -> BitSolver<'a, W> {
+        BitSolver {
+            problem,
+            live: problem.all(),
+            fits: problem.all_opts(),
+            lens: problem.all_lens(),
+            stack: Vec::new(),
+            saved: Vec::new(),
+            saved_lens: Vec::new(),
+            enter: true,
+            done: false,
+            o: Vec::new(),
+            nodes: 0,
+        }
+    }
+
+    pub fn next_solution(&mut self) -> bool {
+        if self.done {
+            return false;
+        }
+        loop {
+            if self.enter {
+                self.nodes += 1;
+                match self.choose() {
+                    None => {
+                        self.enter = false;
+                        return true;
+                    }
+                    Some(i) => {
+                        let live = self.live;
+                        self.stack.push(Frame { i, k: 0, live });
+                        self.saved.extend_from_slice(&self.fits);
+                        self.saved_lens.extend_from_slice(&self.lens);
+                        self.try_option(0);
+                        continue;
+                    }
+                }
+            }
+            let Some(frame) = self.stack.last() else {
+                self.done = true;
+                return false;
+            };
+            self.try_option(frame.k + 1);
+        }
+    }
+
+    // Selects the first option of the last level at position k or later
+    // that fits, or pops the level if there is none. Selecting an option
+    // removes every option that shares an item with it.
+    fn try_option(&mut self, k: usize) {
+        let problem = self.problem;
+        let words = problem.words();
+        let base = self.saved.len() - words;
+        let lens_base = self.saved_lens.len() - problem.n;
+        self.fits.copy_from_slice(&self.saved[base..]);
+        self.lens.copy_from_slice(&self.saved_lens[lens_base..]);
+        let frame = self.stack.last_mut().unwrap();
+        let opts = &problem.item_opts[frame.i];
+        for (k, &m) in opts.iter().enumerate().skip(k) {
+            if has(&self.fits, m) {
+                frame.k = k;
+                self.live = frame.live;
+                self.live.remove(&problem.masks[m as usize]);
+                let conflicts = &problem.conflicts[m as usize * words..];
+                for (w, (f, c)) in
+                    self.fits.iter_mut().zip(conflicts).enumerate()
+                {
+                    let mut gone = *f & c;
+                    *f &= !c;
+                    while gone != 0 {
+                        let q = w * 64 + gone.trailing_zeros() as usize;
+                        gone &= gone - 1;
+                        for &j in &problem.opt_items[q] {
+                            self.lens[j] -= 1;
+                        }
+                    }
+                }
+                self.enter = true;
+                return;
+            }
+        }
+        self.live = frame.live;
+        self.stack.pop();
+        self.saved.truncate(base);
+        self.saved_lens.truncate(lens_base);
+        self.enter = false;
+    }
+
+    // The uncovered primary item with the fewest fitting options, or None
+    // if every primary item is covered.
+    fn choose(&self) -> Option<usize> {
+        let problem = self.problem;
+        let mut best = None;
+        let mut min = usize::MAX;
+        for (w, (&live, &primary)) in
+            self.live.0.iter().zip(&problem.primary.0).enumerate()
+        {
+            let mut open = live & primary;
+            while open != 0 {
+                let i = w * 64 + open.trailing_zeros() as usize;
+                open &= open - 1;
+                let len = self.lens[i] as usize;
+                if len < min {
+                    min = len;
+                    best = Some(i);
+                    if len == 0 {
+                        return best;
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    pub fn fmt_solution(&mut self) -> &[Int] {
+        let problem = self.problem;
+        self.o.clear();
+        for frame in &self.stack {
+            self.o.push(problem.item_opts[frame.i][frame.k] as Int);
+        }
+        &self.o
+    }
+
+    pub fn reset(&mut self) {
+        self.live = self.problem.all();
+        self.fits = self.problem.all_opts();
+        self.lens = self.problem.all_lens();
+        self.stack.clear();
+        self.saved.clear();
+        self.saved_lens.clear();
+        self.enter = true;
+        self.done = false;
+        self.nodes = 0;
+    }
+
+    pub fn get_nodes(&self) -> usize {
+        self.nodes
+    }
+}
+
+// An exact cover problem in whichever representation suits its size: a
+// bitset problem for up to 128 or 256 items and MAX_BIT_OPTS options, and
+// linked lists beyond that. Either way the search uses the MRV heuristic
+// without tie-breaks, and only solutions are reported: BitSolver takes no
+// chooser and keeps no updates or profile, so a Solver on an x::Problem is
+// needed for those.
+pub enum Auto {
+    Bits2(Problem<2>),
+    Bits4(Problem<4>),
+    Links(x::Problem),
+}
+
+impl Auto {
+    // The conflicts between options of a bitset problem take m * m / 8
+    // bytes for m options, 8 MB at this limit.
+    pub const MAX_BIT_OPTS: usize = 8192;
+
+    pub fn new(
+        np: Uint, ns: Uint, os: &[Vec<Uint>], order: OptOrder,
+        item_order: ItemOrder,
+    ) -> Auto {
+        let n = (np + ns) as usize;
+        if os.len() > Auto::MAX_BIT_OPTS {
+            Auto::Links(x::make_problem(np, ns, os, order, item_order))
+        } else if n <= Problem::<2>::MAX_ITEMS {
+            Auto::Bits2(Problem::new(np, ns, os, order, item_order))
+        } else if n <= Problem::<4>::MAX_ITEMS {
+            Auto::Bits4(Problem::new(np, ns, os, order, item_order))
+        } else {
+            Auto::Links(x::make_problem(np, ns, os, order, item_order))
+        }
+    }
+
+    pub fn from_spec(
+        spec: &Spec, order: OptOrder, item_order: ItemOrder,
+    ) -> Result<Auto> {
+        let n = spec.primary.len() + spec.secondary.len();
+        if n > Problem::<4>::MAX_ITEMS || spec.opts.len() > Auto::MAX_BIT_OPTS {
+            let problem = x::Problem::from_spec(spec, order, item_order)?;
+            return Ok(Auto::Links(problem));
+        }
+        let (np, ns, os) = spec_opts(spec, Problem::<4>::MAX_ITEMS)?;
+        Ok(Auto::new(np, ns, &os, order, item_order))
+    }
+
+    // Calls f with each solution, as option numbers, until it returns
+    // false. Returns the number of solutions seen.
+    pub fn for_each_solution<F: FnMut(&[Int]) -> bool>(
+        &mut self, mut f: F,
+    ) -> usize {
+        let mut count = 0;
+        match self {
+            Auto::Bits2(problem) => {
+                let mut solver = BitSolver::new(problem);
+                while solver.next_solution() {
+                    count += 1;
+                    if !f(solver.fmt_solution()) {
+                        break;
+                    }
+                }
+            }
+            Auto::Bits4(problem) => {
+                let mut solver = BitSolver::new(problem);
+                while solver.next_solution() {
+                    count += 1;
+                    if !f(solver.fmt_solution()) {
+                        break;
+                    }
+                }
+            }
+            Auto::Links(problem) => {
+                let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+                let mut solver = Solver::new(problem);
+                while solver.next_solution(&mut chooser) {
+                    count += 1;
+                    if !f(solver.fmt_solution()) {
+                        break;
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    pub fn count_solutions(&mut self) -> usize {
+        self.for_each_solution(|_| true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rng;
+    use crate::testing::{langford_spec, sorted};
+
+    fn linked(spec: &Spec) -> Vec<Vec<Int>> {
+        let problem =
+            x::Problem::from_spec(spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        let mut solutions = Vec::new();
+        Auto::Links(problem).for_each_solution(|s| {
+            solutions.push(s.to_vec());
+            true
+        });
+        sorted(solutions)
+    }
+
+    #[test]
+    fn test_bits() {
+        let os: Vec<Vec<Uint>> = vec![
+            vec![2, 4],
+            vec![0, 3, 6],
+            vec![1, 2, 5],
+            vec![0, 3, 5],
+            vec![1, 6],
+            vec![3, 4, 6],
+        ];
+        let problem: Problem<1> =
+            Problem::new(7, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        let mut solver = BitSolver::new(&problem);
+        assert!(solver.next_solution());
+        let mut solution = solver.fmt_solution().to_vec();
+        solution.sort();
+        assert_eq!(solution, vec![0, 3, 4]);
+        assert!(!solver.next_solution());
+        assert!(!solver.next_solution());
+        solver.reset();
+        assert!(solver.next_solution());
+    }
+
+    #[test]
+    fn test_matches_links() {
+        let spec = langford_spec(8);
+        let expected = linked(&spec);
+        assert_eq!(expected.len(), 300);
+        for order in [OptOrder::Seq, OptOrder::Rnd(Rng::new(123))] {
+            let item_order = ItemOrder::Rnd(Rng::new(456));
+            let mut problem =
+                Auto::from_spec(&spec, order, item_order).unwrap();
+            assert!(matches!(problem, Auto::Bits2(_)));
+            let mut solutions = Vec::new();
+            problem.for_each_solution(|s| {
+                solutions.push(s.to_vec());
+                true
+            });
+            assert_eq!(sorted(solutions), expected);
+        }
+    }
+
+    #[test]
+    fn test_auto() {
+        for (n, bits) in [(100, 2), (200, 4), (300, 0)] {
+            let os = (0..n).map(|i| vec![i as Uint]).collect::<Vec<_>>();
+            let mut problem =
+                Auto::new(n, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+            let kind = match problem {
+                Auto::Bits2(_) => 2,
+                Auto::Bits4(_) => 4,
+                Auto::Links(_) => 0,
+            };
+            assert_eq!(kind, bits);
+            assert_eq!(problem.count_solutions(), 1);
+        }
+        // Too many options for the conflicts to fit
+        let os = vec![vec![0]; Auto::MAX_BIT_OPTS + 1];
+        let mut problem = Auto::new(1, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        assert!(matches!(problem, Auto::Links(_)));
+        assert_eq!(problem.count_solutions(), Auto::MAX_BIT_OPTS + 1);
+        let spec = Spec::new("a b\na b:X\n", false).unwrap();
+        assert!(Auto::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).is_err());
+    }
+}
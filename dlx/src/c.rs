@@ -1,450 +1,708 @@
-use anyhow::{Result, anyhow, bail};
-
-use crate::x;
-use crate::{Dance, Int, OptData, OptOrder, Opts, Solve, Spec, Uint};
-
-pub fn commit<D: DanceC<O: OptsC>>(p: Uint, j: Uint, dance: &mut D) {
-    if *dance.color(p) == 0 {
-        dance.cover(j);
-    }
-    if *dance.color(p) > 0 {
-        dance.purify(p);
-    }
-}
-
-pub fn uncommit<D: DanceC<O: OptsC>>(p: Uint, j: Uint, dance: &mut D) {
-    if *dance.color(p) == 0 {
-        dance.uncover(j)
-    }
-    if *dance.color(p) > 0 {
-        dance.unpurify(p);
-    }
-}
-
-pub fn hide<D: DanceC<O: OptsC>>(p: Uint, dance: &mut D) {
-    let mut q = p + 1;
-    while q != p {
-        let x = *dance.top(q);
-        let u = *dance.ulink(q);
-        let d = *dance.dlink(q);
-        if x <= 0 {
-            q = u;
-        } else {
-            if *dance.color(q) >= 0 {
-                *dance.dlink(u) = d;
-                *dance.ulink(d) = u;
-                *dance.len(x as Uint) -= 1;
-                *dance.updates() += 1;
-            }
-            q += 1;
-        }
-    }
-}
-
-pub fn unhide<D: DanceC<O: OptsC>>(p: Uint, dance: &mut D) {
-    let mut q = p - 1;
-    while q != p {
-        let x = *dance.top(q);
-        let u = *dance.ulink(q);
-        let d = *dance.dlink(q);
-        if x <= 0 {
-            q = d;
-        } else {
-            if *dance.color(q) >= 0 {
-                *dance.dlink(u) = q;
-                *dance.ulink(d) = q;
-                *dance.len(x as Uint) += 1;
-            }
-            q -= 1;
-        }
-    }
-}
-
-pub fn purify<D: DanceC<O: OptsC>>(p: Uint, dance: &mut D) {
-    let c = *dance.color(p);
-    let i = *dance.top(p) as Uint;
-    // TODO is this needed?
-    // *dance.color(i) = c;
-    let mut q = *dance.dlink(i);
-    while q != i {
-        if *dance.color(q) == c {
-            *dance.color(q) = -1;
-        } else {
-            dance.hide(q)
-        }
-        q = *dance.dlink(q);
-    }
-}
-
-pub fn unpurify<D: DanceC<O: OptsC>>(p: Uint, dance: &mut D) {
-    let c = *dance.color(p);
-    let i = *dance.top(p) as Uint;
-    let mut q = *dance.ulink(i);
-    while q != i {
-        if *dance.color(q) < 0 {
-            *dance.color(q) = c;
-        } else {
-            dance.unhide(q);
-        }
-        q = *dance.ulink(q);
-    }
-}
-
-#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
-pub struct ONode {
-    hdr_info: Int,
-    up: Uint,
-    down: Uint,
-    color: Int,
-}
-
-#[derive(Clone, Default, Debug, Eq, PartialEq)]
-pub struct ONodes {
-    nodes: Vec<ONode>,
-}
-
-impl ONodes {
-    pub fn new(
-        n: Uint, np: Uint, os: &[Vec<(Uint, Int)>], order: OptOrder,
-    ) -> ONodes {
-        // TODO: ensure primary have color 0
-        let mut nodes =
-            ONodes { nodes: vec![Default::default(); (n + 2) as usize] };
-        nodes.init_links(n, np, order, os);
-        nodes
-    }
-
-    pub fn from_spec(spec: &Spec, names: &[String]) -> Result<ONodes> {
-        use std::collections::{HashMap, HashSet};
-        let mut idx = HashMap::new();
-        for (i, name) in names.iter().enumerate() {
-            idx.insert(name.as_str(), i);
-        }
-        let mut os = Vec::new();
-        for opt in &spec.opts {
-            let mut is = Vec::new();
-            let mut used = HashSet::new();
-            for itm in opt {
-                let data = itm.split(':').collect::<Vec<_>>();
-                let (name, color) = if data.len() > 2 {
-                    bail!("Too many ':' separators");
-                } else if data.len() == 2 {
-                    if data[1].len() != 1 {
-                        bail!("Color must be a single character");
-                    }
-                    (data[0], data[1].chars().nth(0).unwrap())
-                } else {
-                    (data[0], ' ')
-                };
-                let i =
-                    *idx.get(name).ok_or_else(|| anyhow!("Invalid item"))?;
-                if !used.insert(itm) {
-                    bail!("Duplicate items in option");
-                }
-                if i < spec.primary.len() && color != ' ' {
-                    bail!("Color on primary item");
-                }
-                let color = if color == ' ' { 0 } else { color as Int };
-                is.push((i as Uint, color));
-            }
-            os.push(is);
-        }
-        let n = (spec.primary.len() + spec.secondary.len()) as Uint;
-        let opts =
-            ONodes::new(n, spec.primary.len() as Uint, &os, OptOrder::Seq);
-        Ok(opts)
-    }
-
-    #[inline]
-    fn get_node(&mut self, i: Uint) -> &mut ONode {
-        if cfg!(feature = "unsafe-fast-index") {
-            unsafe { self.nodes.get_unchecked_mut(i as usize) }
-        } else {
-            &mut self.nodes[i as usize]
-        }
-    }
-}
-
-pub fn make_problem(
-    np: Uint, ns: Uint, os: &[Vec<(Uint, Int)>], order: OptOrder,
-) -> Problem {
-    Problem::new(x::INodes::new(np, ns), ONodes::new(np + ns, np, os, order))
-}
-
-#[derive(Debug, Eq, PartialEq)]
-pub struct Problem {
-    items: x::INodes,
-    opts: ONodes,
-    updates: isize,
-}
-
-impl Problem {
-    pub fn new(items: x::INodes, opts: ONodes) -> Problem {
-        Problem { items, opts, updates: 0 }
-    }
-
-    pub fn from_spec(spec: &Spec) -> Result<Problem> {
-        let (items, names) = x::INodes::from_spec(spec)?;
-        let opts = ONodes::from_spec(spec, &names)?;
-        Ok(Problem::new(items, opts))
-    }
-}
-
-impl OptData for (Uint, Int) {
-    fn new_item(i: Uint) -> Self {
-        (i, 0)
-    }
-    fn get_item(&self) -> Uint {
-        self.0
-    }
-}
-
-impl Opts for ONodes {
-    type Data = (Uint, Int);
-
-    #[inline]
-    fn len(&mut self, i: Uint) -> &mut Int {
-        &mut self.get_node(i).hdr_info
-    }
-
-    #[inline]
-    fn top(&mut self, i: Uint) -> &mut Int {
-        &mut self.get_node(i).hdr_info
-    }
-
-    #[inline]
-    fn ulink(&mut self, i: Uint) -> &mut Uint {
-        &mut self.get_node(i).up
-    }
-
-    #[inline]
-    fn dlink(&mut self, i: Uint) -> &mut Uint {
-        &mut self.get_node(i).down
-    }
-
-    fn set_data(&mut self, pk: Uint, s: (Uint, Int)) -> Uint {
-        self.nodes.push(Default::default());
-        *self.color(pk) = s.1;
-        s.0
-    }
-
-    fn get_data_item(s: Self::Data) -> Uint {
-        s.0
-    }
-}
-
-pub trait OptsC: Opts {
-    fn color(&mut self, i: Uint) -> &mut Int;
-}
-
-impl OptsC for ONodes {
-    #[inline]
-    fn color(&mut self, i: Uint) -> &mut Int {
-        &mut self.get_node(i).color
-    }
-}
-
-impl Dance for Problem {
-    type I = x::INodes;
-    type O = ONodes;
-
-    #[inline]
-    fn items(&mut self) -> &mut Self::I {
-        &mut self.items
-    }
-
-    #[inline]
-    fn opts(&mut self) -> &mut Self::O {
-        &mut self.opts
-    }
-
-    #[inline]
-    fn updates(&mut self) -> &mut isize {
-        &mut self.updates
-    }
-
-    #[inline]
-    fn cover(&mut self, i: Uint) {
-        x::cover(i, self);
-    }
-
-    #[inline]
-    fn commit(&mut self, p: Uint, j: Uint) {
-        commit(p, j, self);
-    }
-
-    #[inline]
-    fn uncover(&mut self, i: Uint) {
-        x::uncover(i, self);
-    }
-
-    #[inline]
-    fn uncommit(&mut self, p: Uint, j: Uint) {
-        uncommit(p, j, self);
-    }
-
-    #[inline]
-    fn hide(&mut self, p: Uint) {
-        hide(p, self);
-    }
-
-    #[inline]
-    fn unhide(&mut self, p: Uint) {
-        unhide(p, self);
-    }
-
-    #[inline]
-    fn branch_degree(&mut self, i: Uint) -> Int {
-        x::branch_degree(i, self)
-    }
-}
-
-pub trait DanceC: Dance<O: OptsC> {
-    fn purify(&mut self, p: Uint);
-    fn unpurify(&mut self, p: Uint);
-
-    #[inline]
-    fn color(&mut self, i: Uint) -> &mut Int {
-        self.opts().color(i)
-    }
-}
-
-impl DanceC for Problem {
-    #[inline]
-    fn purify(&mut self, p: Uint) {
-        purify(p, self);
-    }
-
-    #[inline]
-    fn unpurify(&mut self, p: Uint) {
-        unpurify(p, self);
-    }
-}
-
-impl Solve for Problem {
-    fn enter_level(&mut self, _: Uint, _: Uint, _: Uint) {}
-
-    #[inline]
-    fn prepare_to_branch(&mut self, i: Uint, l: Uint, xl: Uint) {
-        x::prepare_to_branch(self, i, l, xl);
-    }
-
-    #[inline]
-    fn try_item(&mut self, i: Uint, _: Uint, xl: Uint) -> bool {
-        x::try_item(self, i, xl)
-    }
-
-    #[inline]
-    fn try_again(&mut self, i: Uint, l: Uint, xl: &mut Uint) -> bool {
-        x::try_again(self, i, l, xl)
-    }
-
-    #[inline]
-    fn restore_item(&mut self, i: Uint, _: Uint, _: Uint) {
-        x::restore_item(self, i);
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_opt_init() {
-        let os: Vec<Vec<(Uint, Int)>> = vec![
-            vec![(0, 0), (1, 0), (3, 0), (4, 65)],
-            vec![(0, 0), (2, 0), (3, 65), (4, 0)],
-            vec![(0, 0), (3, 66)],
-            vec![(1, 0), (3, 65)],
-            vec![(2, 0), (4, 66)],
-        ];
-        let opts = ONodes::new(5, 3, &os, OptOrder::Seq);
-        let onodes = onodes_data();
-        assert_eq!(opts.nodes, onodes, "incorrect options");
-    }
-
-    #[test]
-    fn test_from_spec() {
-        let spec_str = "
-p q r | x y
-p q x y:A
-p r x:A y
-p x:B
-q x:A
-r y:B
-";
-        let spec = Spec::new(spec_str, false).unwrap();
-        let problem = Problem::from_spec(&spec).unwrap();
-        assert_eq!(problem.opts.nodes, onodes_data());
-    }
-
-    #[test]
-    fn test_xcc() {
-        use crate::Solver;
-        use crate::choose::*;
-        let items = x::INodes::new(3, 2);
-        let os: Vec<Vec<(Uint, Int)>> = vec![
-            vec![(0, 0), (1, 0), (3, 0), (4, 65)],
-            vec![(0, 0), (2, 0), (3, 65), (4, 0)],
-            vec![(0, 0), (3, 66)],
-            vec![(1, 0), (3, 65)],
-            vec![(2, 0), (4, 66)],
-        ];
-        let opts = ONodes::new(5, 3, &os, OptOrder::Seq);
-        let items_init = items.clone();
-        let opts_init = opts.clone();
-        let mut problem = Problem::new(items, opts);
-        let mut solver = Solver::new(&mut problem);
-        let mut solutions: Vec<Vec<Int>> = Vec::new();
-        let mut expected = vec![vec![1, 3]];
-        let mut i = 0;
-        let mut chooser = mrv_chooser(prefer_any(), rnd_tiebreak(12345678));
-        while solver.next_solution(&mut chooser) {
-            assert!(i <= expected.len(), "too many solutions");
-            solver.fmt_solution();
-            solver.o.sort();
-            solutions.push(solver.o.clone());
-            i += 1;
-        }
-        solutions.sort();
-        expected.sort();
-        assert_eq!(solutions, expected, "wrong solutions");
-        assert_eq!(solver.problem.items, items_init, "items not backtracked");
-        assert_eq!(solver.problem.opts, opts_init, "options not backtracked");
-        assert!(
-            solver.l == 0 && solver.restart == false,
-            "initial state not restored"
-        );
-    }
-
-    fn onodes_data() -> Vec<ONode> {
-        vec![
-            ONode { hdr_info: 0, up: 0, down: 0, color: 0 },
-            ONode { hdr_info: 3, up: 17, down: 7, color: 0 },
-            ONode { hdr_info: 2, up: 20, down: 8, color: 0 },
-            ONode { hdr_info: 2, up: 23, down: 13, color: 0 },
-            ONode { hdr_info: 4, up: 21, down: 9, color: 0 },
-            ONode { hdr_info: 3, up: 24, down: 10, color: 0 },
-            ONode { hdr_info: 0, up: 0, down: 10, color: 0 },
-            ONode { hdr_info: 1, up: 1, down: 12, color: 0 },
-            ONode { hdr_info: 2, up: 2, down: 20, color: 0 },
-            ONode { hdr_info: 4, up: 4, down: 14, color: 0 },
-            ONode { hdr_info: 5, up: 5, down: 15, color: 65 },
-            ONode { hdr_info: -1, up: 7, down: 15, color: 0 },
-            ONode { hdr_info: 1, up: 7, down: 17, color: 0 },
-            ONode { hdr_info: 3, up: 3, down: 23, color: 0 },
-            ONode { hdr_info: 4, up: 9, down: 18, color: 65 },
-            ONode { hdr_info: 5, up: 10, down: 24, color: 0 },
-            ONode { hdr_info: -2, up: 12, down: 18, color: 0 },
-            ONode { hdr_info: 1, up: 12, down: 1, color: 0 },
-            ONode { hdr_info: 4, up: 14, down: 21, color: 66 },
-            ONode { hdr_info: -3, up: 17, down: 21, color: 0 },
-            ONode { hdr_info: 2, up: 8, down: 2, color: 0 },
-            ONode { hdr_info: 4, up: 18, down: 4, color: 65 },
-            ONode { hdr_info: -4, up: 20, down: 24, color: 0 },
-            ONode { hdr_info: 3, up: 13, down: 3, color: 0 },
-            ONode { hdr_info: 5, up: 15, down: 5, color: 66 },
-            ONode { hdr_info: -5, up: 23, down: 0, color: 0 },
-        ]
-    }
-}
+use anyhow::{Result, anyhow, bail};
+
+use crate::lookahead::{self, Lookahead};
+use crate::nodes::{Links, Nodes, check_links};
+use crate::store::Store;
+use crate::{Dance, Int, ItemOrder, Mems, OptData, OptOrder, Opts, Solve};
+use crate::{Spec, Uint, csr_colored, csr_size, to_csr};
+use crate::{m, x};
+
+pub fn commit<D: DanceC<O: OptsC>>(p: Uint, j: Uint, dance: &mut D) {
+    if dance.color(p) == 0 {
+        dance.cover(j);
+    }
+    if dance.color(p) > 0 {
+        dance.purify(p);
+    }
+}
+
+pub fn uncommit<D: DanceC<O: OptsC>>(p: Uint, j: Uint, dance: &mut D) {
+    if dance.color(p) == 0 {
+        dance.uncover(j)
+    }
+    if dance.color(p) > 0 {
+        dance.unpurify(p);
+    }
+}
+
+pub fn hide<D: DanceC<O: OptsC>>(p: Uint, dance: &mut D) {
+    let mut q = p + 1;
+    while q != p {
+        let (x, u, d) = dance.node(q);
+        if x <= 0 {
+            q = u;
+        } else {
+            if dance.color(q) >= 0 {
+                *dance.dlink(u) = d;
+                *dance.ulink(d) = u;
+                *dance.len(x as Uint) -= 1;
+                *dance.updates() += 1;
+            }
+            q += 1;
+        }
+    }
+}
+
+pub fn unhide<D: DanceC<O: OptsC>>(p: Uint, dance: &mut D) {
+    let mut q = p - 1;
+    while q != p {
+        let (x, u, d) = dance.node(q);
+        if x <= 0 {
+            q = d;
+        } else {
+            if dance.color(q) >= 0 {
+                *dance.dlink(u) = q;
+                *dance.ulink(d) = q;
+                *dance.len(x as Uint) += 1;
+            }
+            q -= 1;
+        }
+    }
+}
+
+pub fn purify<D: DanceC<O: OptsC>>(p: Uint, dance: &mut D) {
+    let c = dance.color(p);
+    let i = *dance.top(p) as Uint;
+    // TODO is this needed?
+    // dance.set_color(i, c);
+    let mut q = *dance.dlink(i);
+    while q != i {
+        if dance.color(q) == c {
+            dance.set_color(q, -1);
+        } else {
+            dance.hide(q)
+        }
+        q = *dance.dlink(q);
+    }
+}
+
+pub fn unpurify<D: DanceC<O: OptsC>>(p: Uint, dance: &mut D) {
+    let c = dance.color(p);
+    let i = *dance.top(p) as Uint;
+    let mut q = *dance.ulink(i);
+    while q != i {
+        if dance.color(q) < 0 {
+            dance.set_color(q, c);
+        } else {
+            dance.unhide(q);
+        }
+        q = *dance.ulink(q);
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct ONodes {
+    links: Links,
+    // The colors of the nodes from color_base on, kept apart from the links
+    // since they are only read for secondary items. Options with colors are
+    // laid out after those without, whose nodes all have color 0 and store
+    // none.
+    colors: Nodes<Int>,
+    color_base: Uint,
+    count: Int,
+    free: Vec<Uint>,
+    mems: Mems,
+}
+
+// As with x::ONodes, mems are left out, and so is where the stored colors
+// start.
+impl PartialEq for ONodes {
+    fn eq(&self, other: &ONodes) -> bool {
+        let fields = (&self.links, self.count, &self.free);
+        fields == (&other.links, other.count, &other.free)
+            && (0..self.links.len())
+                .all(|i| self.color_of(i) == other.color_of(i))
+    }
+}
+
+impl Eq for ONodes {}
+
+impl ONodes {
+    pub fn new(
+        n: Uint, np: Uint, os: &[Vec<(Uint, Int)>], order: OptOrder,
+    ) -> ONodes {
+        let entries = os.iter().map(Vec::len).sum();
+        ONodes::from_options(n, np, os, entries, order)
+    }
+
+    // Builds the nodes from options produced one at a time, flattened
+    // with room for entries item uses in all, as in x::ONodes.
+    pub fn from_options<T: AsRef<[(Uint, Int)]>>(
+        n: Uint, np: Uint, os: impl IntoIterator<Item = T>, entries: usize,
+        order: OptOrder,
+    ) -> ONodes {
+        let (starts, items) = to_csr(os, entries);
+        ONodes::from_csr(n, np, &starts, &items, order)
+    }
+
+    // Builds the nodes from options in compressed sparse row form, where
+    // option k has the items items[starts[k]..starts[k + 1]], allocating
+    // the links and colors once at their exact size.
+    pub fn from_csr(
+        n: Uint, np: Uint, starts: &[usize], items: &[(Uint, Int)],
+        order: OptOrder,
+    ) -> ONodes {
+        // TODO: ensure primary have color 0
+        let size = csr_size(n, np, starts, items);
+        let base = csr_colored(n, np, starts, items) + 1;
+        let mut colors = Vec::with_capacity(size - base);
+        for w in starts.windows(2) {
+            let opt = &items[w[0]..w[1]];
+            if opt.iter().any(|&(i, _)| i < np)
+                && opt.iter().any(OptData::has_color)
+            {
+                colors.extend(opt.iter().map(|&(_, c)| c));
+                colors.push(0);
+            }
+        }
+        let mut nodes = ONodes {
+            links: Links::new(size),
+            colors: Nodes::from_vec(colors),
+            color_base: base as Uint,
+            ..Default::default()
+        };
+        nodes.link_csr(n, np, order, starts, items);
+        nodes
+    }
+
+    // Unlinked nodes of the given colors, or of none if colors is empty,
+    // for the links to be filled in.
+    pub(crate) fn blank(size: usize, mut colors: Vec<Int>) -> ONodes {
+        let base = colors.iter().position(|&c| c != 0).unwrap_or(size);
+        colors.drain(..base.min(colors.len()));
+        colors.resize(size - base, 0);
+        ONodes {
+            links: Links::new(size),
+            colors: Nodes::from_vec(colors),
+            color_base: base as Uint,
+            ..Default::default()
+        }
+    }
+
+    // The color of node i, without counting a mem.
+    fn color_of(&self, i: usize) -> Int {
+        match i.checked_sub(self.color_base as usize) {
+            Some(k) => self.colors.as_slice()[k],
+            None => 0,
+        }
+    }
+
+    pub fn from_spec(
+        spec: &Spec, names: &[String], order: OptOrder,
+    ) -> Result<ONodes> {
+        use std::collections::{HashMap, HashSet};
+        let mut idx = HashMap::new();
+        for (i, name) in names.iter().enumerate() {
+            idx.insert(name.as_str(), i);
+        }
+        let mut starts = vec![0];
+        let mut items =
+            Vec::with_capacity(spec.opts.iter().map(Vec::len).sum());
+        for opt in &spec.opts {
+            let mut used = HashSet::new();
+            for itm in opt {
+                let data = itm.split(':').collect::<Vec<_>>();
+                let (name, color) = if data.len() > 2 {
+                    bail!("Too many ':' separators");
+                } else if data.len() == 2 {
+                    if data[1].len() != 1 {
+                        bail!("Color must be a single character");
+                    }
+                    (data[0], data[1].chars().nth(0).unwrap())
+                } else {
+                    (data[0], ' ')
+                };
+                let i =
+                    *idx.get(name).ok_or_else(|| anyhow!("Invalid item"))?;
+                if !used.insert(itm) {
+                    bail!("Duplicate items in option");
+                }
+                if i < spec.primary.len() && color != ' ' {
+                    bail!("Color on primary item");
+                }
+                let color = if color == ' ' { 0 } else { color as Int };
+                items.push((i as Uint, color));
+            }
+            starts.push(items.len());
+        }
+        let n = (spec.primary.len() + spec.secondary.len()) as Uint;
+        let np = spec.primary.len() as Uint;
+        Ok(ONodes::from_csr(n, np, &starts, &items, order))
+    }
+
+    #[inline]
+    fn links(&mut self) -> &mut Links {
+        self.mems.add();
+        &mut self.links
+    }
+}
+
+pub fn make_problem(
+    np: Uint, ns: Uint, os: &[Vec<(Uint, Int)>], order: OptOrder,
+    item_order: ItemOrder,
+) -> Problem {
+    let items = x::INodes::new(np, ns, item_order);
+    Problem::new(items, ONodes::new(np + ns, np, os, order))
+}
+
+#[derive(Debug)]
+pub struct Problem {
+    items: x::INodes,
+    opts: ONodes,
+    updates: isize,
+    lookahead: Lookahead,
+}
+
+// Problems compare equal whatever lookahead they were set to use.
+impl PartialEq for Problem {
+    fn eq(&self, other: &Problem) -> bool {
+        let fields = (&self.items, &self.opts, self.updates);
+        fields == (&other.items, &other.opts, other.updates)
+    }
+}
+
+impl Eq for Problem {}
+
+impl Problem {
+    pub fn new(items: x::INodes, opts: ONodes) -> Problem {
+        let problem = Problem::from_parts(items, opts, Vec::new());
+        problem.unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn from_spec(
+        spec: &Spec, order: OptOrder, item_order: ItemOrder,
+    ) -> Result<Problem> {
+        let (items, names) = x::INodes::from_spec(spec, item_order)?;
+        let opts = ONodes::from_spec(spec, &names, order)?;
+        Ok(Problem::new(items, opts))
+    }
+}
+
+impl OptData for (Uint, Int) {
+    fn new_item(i: Uint) -> Self {
+        (i, 0)
+    }
+    fn get_item(&self) -> Uint {
+        self.0
+    }
+    fn has_color(&self) -> bool {
+        self.1 != 0
+    }
+}
+
+impl Opts for ONodes {
+    type Data = (Uint, Int);
+    type Ref<'a> = &'a mut Uint;
+    type SignedRef<'a> = &'a mut Int;
+
+    #[inline]
+    fn len(&mut self, i: Uint) -> &mut Int {
+        self.links().top(i)
+    }
+
+    #[inline]
+    fn top(&mut self, i: Uint) -> &mut Int {
+        self.links().top(i)
+    }
+
+    #[inline]
+    fn ulink(&mut self, i: Uint) -> &mut Uint {
+        self.links().up(i)
+    }
+
+    #[inline]
+    fn dlink(&mut self, i: Uint) -> &mut Uint {
+        self.links().down(i)
+    }
+
+    #[inline]
+    fn node(&mut self, i: Uint) -> (Int, Uint, Uint) {
+        self.links().node(i)
+    }
+
+    fn set_data(&mut self, pk: Uint, s: (Uint, Int)) -> Uint {
+        if pk as usize == self.links.len() {
+            self.insert_nodes(pk, 1);
+        }
+        self.set_color(pk, s.1);
+        s.0
+    }
+
+    fn get_data_item(s: Self::Data) -> Uint {
+        s.0
+    }
+
+    #[inline]
+    fn size(&self) -> Uint {
+        self.links.len() as Uint
+    }
+
+    fn insert_nodes(&mut self, i: Uint, k: Uint) {
+        self.links.insert(i as usize, k as usize);
+        if i <= self.color_base {
+            self.color_base += k;
+        } else {
+            let at = (i - self.color_base) as usize;
+            self.colors.insert_many(at, k as usize, 0);
+        }
+    }
+
+    #[inline]
+    fn opt_count(&mut self) -> &mut Int {
+        &mut self.count
+    }
+
+    #[inline]
+    fn free(&mut self) -> &mut Vec<Uint> {
+        &mut self.free
+    }
+
+    #[inline]
+    fn mems(&self) -> u64 {
+        self.mems.get()
+    }
+}
+
+pub trait OptsC: Opts {
+    fn color(&mut self, i: Uint) -> Int;
+    fn set_color(&mut self, i: Uint, c: Int);
+}
+
+impl OptsC for ONodes {
+    #[inline]
+    fn color(&mut self, i: Uint) -> Int {
+        self.mems.add();
+        if i < self.color_base {
+            0
+        } else {
+            *self.colors.get_mut(i - self.color_base)
+        }
+    }
+
+    fn set_color(&mut self, i: Uint, c: Int) {
+        self.mems.add();
+        if i < self.color_base {
+            if c == 0 {
+                return;
+            }
+            // A color given to an earlier node, by an option added in a
+            // gap, makes the colors start there
+            let k = self.color_base - i;
+            self.colors.insert_many(0, k as usize, 0);
+            self.color_base = i;
+        }
+        *self.colors.get_mut(i - self.color_base) = c;
+    }
+}
+
+impl Store for Problem {
+    const KIND: u8 = 2;
+
+    fn node_color(&mut self, p: Uint) -> Int {
+        self.opts.color(p)
+    }
+
+    fn blank_items(np: Uint, ns: Uint, _: &[(Int, Int)]) -> x::INodes {
+        x::INodes::new(np, ns, ItemOrder::Seq)
+    }
+
+    fn blank_opts(size: usize, colors: Vec<Int>) -> ONodes {
+        ONodes::blank(size, colors)
+    }
+
+    fn from_parts(
+        items: x::INodes, opts: ONodes, _: Vec<m::Uses>,
+    ) -> Result<Problem> {
+        let mut problem = Problem {
+            items,
+            opts,
+            updates: 0,
+            lookahead: Default::default(),
+        };
+        check_links(&mut problem)?;
+        Ok(problem)
+    }
+}
+
+impl Dance for Problem {
+    type I = x::INodes;
+    type O = ONodes;
+
+    #[inline]
+    fn items(&mut self) -> &mut Self::I {
+        &mut self.items
+    }
+
+    #[inline]
+    fn opts(&mut self) -> &mut Self::O {
+        &mut self.opts
+    }
+
+    #[inline]
+    fn updates(&mut self) -> &mut isize {
+        &mut self.updates
+    }
+
+    #[inline]
+    fn cover(&mut self, i: Uint) {
+        x::cover(i, self);
+    }
+
+    #[inline]
+    fn commit(&mut self, p: Uint, j: Uint) {
+        commit(p, j, self);
+    }
+
+    #[inline]
+    fn uncover(&mut self, i: Uint) {
+        x::uncover(i, self);
+    }
+
+    #[inline]
+    fn uncommit(&mut self, p: Uint, j: Uint) {
+        uncommit(p, j, self);
+    }
+
+    #[inline]
+    fn hide(&mut self, p: Uint) {
+        hide(p, self);
+    }
+
+    #[inline]
+    fn unhide(&mut self, p: Uint) {
+        unhide(p, self);
+    }
+
+    #[inline]
+    fn branch_degree(&mut self, i: Uint) -> Int {
+        x::branch_degree(i, self)
+    }
+}
+
+pub trait DanceC: Dance<O: OptsC> {
+    fn purify(&mut self, p: Uint);
+    fn unpurify(&mut self, p: Uint);
+
+    #[inline]
+    fn color(&mut self, i: Uint) -> Int {
+        self.opts().color(i)
+    }
+
+    #[inline]
+    fn set_color(&mut self, i: Uint, c: Int) {
+        self.opts().set_color(i, c);
+    }
+}
+
+impl DanceC for Problem {
+    #[inline]
+    fn purify(&mut self, p: Uint) {
+        purify(p, self);
+    }
+
+    #[inline]
+    fn unpurify(&mut self, p: Uint) {
+        unpurify(p, self);
+    }
+}
+
+impl Solve for Problem {
+    fn set_lookahead(&mut self, depth: usize, max_level: Uint) -> Result<()> {
+        self.lookahead = Lookahead::new(depth, max_level);
+        Ok(())
+    }
+
+    fn enter_level(&mut self, _: Uint, _: Uint, _: Uint) {}
+
+    #[inline]
+    fn prepare_to_branch(&mut self, i: Uint, l: Uint, xl: Uint) {
+        if self.lookahead.applies(l) {
+            let pruned = lookahead::prune(self, i, self.lookahead.depth());
+            self.lookahead.push(l, pruned);
+        }
+        x::prepare_to_branch(self, i, l, xl);
+    }
+
+    #[inline]
+    fn try_item(&mut self, i: Uint, _: Uint, xl: Uint) -> bool {
+        x::try_item(self, i, xl)
+    }
+
+    #[inline]
+    fn try_again(&mut self, i: Uint, l: Uint, xl: &mut Uint) -> bool {
+        x::try_again(self, i, l, xl)
+    }
+
+    #[inline]
+    fn undo_item(&mut self, _: Uint, _: Uint, xl: Uint) {
+        x::undo_item(self, xl);
+    }
+
+    #[inline]
+    fn restore_item(&mut self, i: Uint, l: Uint, _: Uint) {
+        x::restore_item(self, i);
+        if let Some(pruned) = self.lookahead.pop(l) {
+            lookahead::unprune(self, i, &pruned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opt_init() {
+        let os: Vec<Vec<(Uint, Int)>> = vec![
+            vec![(0, 0), (1, 0), (3, 0), (4, 65)],
+            vec![(0, 0), (2, 0), (3, 65), (4, 0)],
+            vec![(0, 0), (3, 66)],
+            vec![(1, 0), (3, 65)],
+            vec![(2, 0), (4, 66)],
+        ];
+        let opts = ONodes::new(5, 3, &os, OptOrder::Seq);
+        let onodes = onodes_data();
+        assert_eq!(records(&opts), onodes, "incorrect options");
+    }
+
+    #[test]
+    fn test_from_csr() {
+        let os: Vec<Vec<(Uint, Int)>> = vec![
+            vec![(0, 0), (1, 0), (3, 0), (4, 65)],
+            vec![(0, 0), (2, 0), (3, 65), (4, 0)],
+            vec![(0, 0), (3, 66)],
+            vec![(1, 0), (3, 65)],
+            vec![(2, 0), (4, 66)],
+        ];
+        let starts = [0, 4, 8, 10, 12, 14];
+        let items = os.concat();
+        let opts = ONodes::from_csr(5, 3, &starts, &items, OptOrder::Seq);
+        assert_eq!(records(&opts), onodes_data());
+        assert_eq!(opts.colors.len() + 7, opts.links.len());
+        if !cfg!(feature = "masked-index") {
+            assert_eq!(opts.colors.capacity(), opts.colors.len());
+        }
+
+        // Options with colors are laid out after those without, which store
+        // no colors, and are still listed in order
+        let os: Vec<Vec<(Uint, Int)>> =
+            vec![vec![(0, 0), (2, 65)], vec![(1, 0)], vec![(0, 0), (1, 0)]];
+        let mut opts = ONodes::new(3, 2, &os, OptOrder::Seq);
+        assert_eq!(opts.links.len(), 13);
+        assert_eq!(opts.colors.len(), 3);
+        assert_eq!((*opts.dlink(1), *opts.ulink(1)), (10, 7));
+        assert_eq!(opts.color(11), 65);
+
+        // A node before the stored colors can still be given one
+        opts.set_color(5, 66);
+        assert_eq!((opts.color(4), opts.color(5)), (0, 66));
+        assert_eq!(opts.color(11), 65);
+    }
+
+    #[test]
+    fn test_from_spec() {
+        let spec_str = "
+p q r | x y
+p q x y:A
+p r x:A y
+p x:B
+q x:A
+r y:B
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let problem =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        assert_eq!(records(&problem.opts), onodes_data());
+    }
+
+    #[test]
+    fn test_xcc() {
+        use crate::Solver;
+        use crate::choose::*;
+        let items = x::INodes::new(3, 2, ItemOrder::Seq);
+        let os: Vec<Vec<(Uint, Int)>> = vec![
+            vec![(0, 0), (1, 0), (3, 0), (4, 65)],
+            vec![(0, 0), (2, 0), (3, 65), (4, 0)],
+            vec![(0, 0), (3, 66)],
+            vec![(1, 0), (3, 65)],
+            vec![(2, 0), (4, 66)],
+        ];
+        let opts = ONodes::new(5, 3, &os, OptOrder::Seq);
+        let items_init = items.clone();
+        let opts_init = opts.clone();
+        let mut problem = Problem::new(items, opts);
+        let mut solver = Solver::new(&mut problem);
+        let mut solutions: Vec<Vec<Int>> = Vec::new();
+        let mut expected = vec![vec![1, 3]];
+        let mut i = 0;
+        let mut chooser = mrv_chooser(prefer_any(), rnd_tiebreak(12345678));
+        while solver.next_solution(&mut chooser) {
+            assert!(i <= expected.len(), "too many solutions");
+            solver.fmt_solution();
+            solver.o.sort();
+            solutions.push(solver.o.clone());
+            i += 1;
+        }
+        solutions.sort();
+        expected.sort();
+        assert_eq!(solutions, expected, "wrong solutions");
+        assert_eq!(solver.problem.items, items_init, "items not backtracked");
+        assert_eq!(solver.problem.opts, opts_init, "options not backtracked");
+        assert!(
+            solver.l == 0 && solver.restart == false,
+            "initial state not restored"
+        );
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct ONode {
+        hdr_info: Int,
+        up: Uint,
+        down: Uint,
+        color: Int,
+    }
+
+    fn records(opts: &ONodes) -> Vec<ONode> {
+        let colors = (0..opts.links.len()).map(|i| opts.color_of(i));
+        let fields = opts.links.fields().into_iter().zip(colors);
+        fields
+            .map(|((hdr_info, up, down), color)| ONode {
+                hdr_info,
+                up,
+                down,
+                color,
+            })
+            .collect()
+    }
+
+    fn onodes_data() -> Vec<ONode> {
+        vec![
+            ONode { hdr_info: 0, up: 0, down: 0, color: 0 },
+            ONode { hdr_info: 3, up: 17, down: 7, color: 0 },
+            ONode { hdr_info: 2, up: 20, down: 8, color: 0 },
+            ONode { hdr_info: 2, up: 23, down: 13, color: 0 },
+            ONode { hdr_info: 4, up: 21, down: 9, color: 0 },
+            ONode { hdr_info: 3, up: 24, down: 10, color: 0 },
+            ONode { hdr_info: 0, up: 0, down: 10, color: 0 },
+            ONode { hdr_info: 1, up: 1, down: 12, color: 0 },
+            ONode { hdr_info: 2, up: 2, down: 20, color: 0 },
+            ONode { hdr_info: 4, up: 4, down: 14, color: 0 },
+            ONode { hdr_info: 5, up: 5, down: 15, color: 65 },
+            ONode { hdr_info: -1, up: 7, down: 15, color: 0 },
+            ONode { hdr_info: 1, up: 7, down: 17, color: 0 },
+            ONode { hdr_info: 3, up: 3, down: 23, color: 0 },
+            ONode { hdr_info: 4, up: 9, down: 18, color: 65 },
+            ONode { hdr_info: 5, up: 10, down: 24, color: 0 },
+            ONode { hdr_info: -2, up: 12, down: 18, color: 0 },
+            ONode { hdr_info: 1, up: 12, down: 1, color: 0 },
+            ONode { hdr_info: 4, up: 14, down: 21, color: 66 },
+            ONode { hdr_info: -3, up: 17, down: 21, color: 0 },
+            ONode { hdr_info: 2, up: 8, down: 2, color: 0 },
+            ONode { hdr_info: 4, up: 18, down: 4, color: 65 },
+            ONode { hdr_info: -4, up: 20, down: 24, color: 0 },
+            ONode { hdr_info: 3, up: 13, down: 3, color: 0 },
+            ONode { hdr_info: 5, up: 15, down: 5, color: 66 },
+            ONode { hdr_info: -5, up: 23, down: 0, color: 0 },
+        ]
+    }
+}
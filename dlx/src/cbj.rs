@@ -0,0 +1,319 @@
+use crate::p::Reduce;
+use crate::{Int, Items, Opts, Uint, opt_starts};
+
+// A set of levels, as a bitset.
+#[derive(Clone, Default)]
+struct Levels(Vec<u64>);
+
+impl Levels {
+    fn insert(&mut self, k: usize) {
+        if self.0.len() <= k / 64 {
+            self.0.resize(k / 64 + 1, 0);
+        }
+        self.0[k / 64] |= 1 << (k % 64);
+    }
+
+    fn remove(&mut self, k: usize) {
+        if let Some(w) = self.0.get_mut(k / 64) {
+            *w &= !(1 << (k % 64));
+        }
+    }
+
+    fn contains(&self, k: usize) -> bool {
+        self.0.get(k / 64).is_some_and(|w| w & (1 << (k % 64)) != 0)
+    }
+
+    fn union(&mut self, other: &Levels) {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        for (w, v) in self.0.iter_mut().zip(&other.0) {
+            *w |= v;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.0.len() * 64).filter(|&k| self.contains(k))
+    }
+
+    // The deepest level in the set.
+    fn last(&self) -> Option<usize> {
+        let k = self.0.iter().rposition(|&w| w != 0)?;
+        Some(k * 64 + 63 - self.0[k].leading_zeros() as usize)
+    }
+}
+
+// Conflict-directed backjumping for Solver. When every option of an item
+// has failed, the search jumps back to the deepest level that took part in
+// eliminating them, skipping levels whose choices made no difference. The
+// reasons are found from the options each level selected.
+pub struct Backjump {
+    // The option number of each node, or -1 for headers and spacers
+    opt_of: Vec<Int>,
+    // The items of each option, with their colors
+    items_of: Vec<Vec<(Uint, Int)>>,
+    // The options of each item
+    col: Vec<Vec<Int>>,
+    // The first level to use each item, and the color it used
+    claim: Vec<Option<(usize, Int)>>,
+    level_of: Vec<Option<usize>>,
+    // For each level, the earlier levels responsible for its failures so
+    // far, and whether no solution was found below it
+    conf: Vec<Levels>,
+    clean: Vec<bool>,
+    // Sets of options that are in no solution, and the nogoods that each
+    // option is in
+    nogoods: Vec<Vec<Int>>,
+    watch: Vec<Vec<usize>>,
+    max_nogood: usize,
+    jumps: usize,
+}
+
+impl Backjump {
+    // Learns nogoods of up to max_nogood options, and skips options that
+    // would complete one of them.
+    pub fn new<D: Reduce>(dance: &mut D, max_nogood: usize) -> Backjump {
+        let n = dance.items().count();
+        let size = dance.opts().size();
+        let starts = opt_starts(dance);
+        let mut opt_of = vec![-1; size as usize];
+        let mut items_of = vec![Vec::new(); starts.len()];
+        let mut col = vec![Vec::new(); (n + 1) as usize];
+        for (o, &start) in starts.iter().enumerate() {
+            if start == 0 {
+                continue;
+            }
+            let mut p = start;
+            while *dance.top(p) > 0 {
+                let i = *dance.top(p) as Uint;
+                opt_of[p as usize] = o as Int;
+                items_of[o].push((i, dance.get_color(p)));
+                col[i as usize].push(o as Int);
+                p += 1;
+            }
+        }
+        Backjump {
+            opt_of,
+            col,
+            claim: vec![None; (n + 1) as usize],
+            level_of: vec![None; items_of.len()],
+            watch: vec![Vec::new(); items_of.len()],
+            items_of,
+            conf: Vec::new(),
+            clean: Vec::new(),
+            nogoods: Vec::new(),
+            max_nogood,
+            jumps: 0,
+        }
+    }
+
+    pub fn get_jumps(&self) -> usize {
+        self.jumps
+    }
+
+    pub fn get_nogoods(&self) -> &[Vec<Int>] {
+        &self.nogoods
+    }
+
+    // Starts level l on item i, blaming the levels that eliminated its
+    // missing options.
+    pub(crate) fn choose(&mut self, l: usize, i: Uint) {
+        if self.conf.len() <= l {
+            self.conf.resize(l + 1, Levels::default());
+            self.clean.resize(l + 1, true);
+        }
+        let mut conf = Levels::default();
+        for &o in &self.col[i as usize] {
+            if let Some(k) = self.reason(o) {
+                conf.insert(k);
+            }
+        }
+        self.conf[l] = conf;
+        self.clean[l] = true;
+    }
+
+    // Probing removed options on level l for reasons that aren't recorded,
+    // so every earlier level is blamed.
+    pub(crate) fn blame_all(&mut self, l: usize) {
+        (0..l).for_each(|k| self.conf[l].insert(k));
+    }
+
+    // Whether the option of node p completes a nogood, in which case the
+    // levels of its other options are blamed for the failure on level l.
+    pub(crate) fn blocked(&mut self, l: usize, p: Uint) -> bool {
+        let o = self.opt_of[p as usize];
+        'nogoods: for &g in &self.watch[o as usize] {
+            let mut levels = Levels::default();
+            for &q in &self.nogoods[g] {
+                if q == o {
+                    continue;
+                }
+                match self.level_of[q as usize] {
+                    Some(k) => levels.insert(k),
+                    None => continue 'nogoods,
+                }
+            }
+            self.conf[l].union(&levels);
+            return true;
+        }
+        false
+    }
+
+    // The option of node p was selected on level l.
+    pub(crate) fn select(&mut self, l: usize, p: Uint) {
+        let o = self.opt_of[p as usize];
+        for &(j, c) in &self.items_of[o as usize] {
+            if self.claim[j as usize].is_none() {
+                self.claim[j as usize] = Some((l, c));
+            }
+        }
+        self.level_of[o as usize] = Some(l);
+    }
+
+    pub(crate) fn deselect(&mut self, l: usize, p: Uint) {
+        let o = self.opt_of[p as usize];
+        for &(j, _) in &self.items_of[o as usize] {
+            if self.claim[j as usize].is_some_and(|(k, _)| k == l) {
+                self.claim[j as usize] = None;
+            }
+        }
+        self.level_of[o as usize] = None;
+    }
+
+    // A solution was found with l options; later solutions may depend on
+    // any of them.
+    pub(crate) fn solution(&mut self, l: usize) {
+        if let Some(h) = l.checked_sub(1) {
+            (0..h).for_each(|k| self.conf[h].insert(k));
+            self.clean[h] = false;
+        }
+    }
+
+    // Level l has no options left. Records a nogood of the options x
+    // selected on the levels responsible, and returns the deepest of those
+    // levels, which is given the reasons, or None if there is none.
+    pub(crate) fn fail(&mut self, l: usize, x: &[Uint]) -> Option<usize> {
+        let mut conf = std::mem::take(&mut self.conf[l]);
+        let clean = self.clean[l];
+        let size = conf.len();
+        if clean && size > 0 && size <= self.max_nogood {
+            let nogood = conf
+                .iter()
+                .map(|k| self.opt_of[x[k] as usize])
+                .collect::<Vec<_>>();
+            for &o in &nogood {
+                self.watch[o as usize].push(self.nogoods.len());
+            }
+            self.nogoods.push(nogood);
+        }
+        let Some(h) = conf.last() else {
+            self.jumps += l;
+            return None;
+        };
+        conf.remove(h);
+        self.conf[h].union(&conf);
+        self.clean[h] &= clean;
+        self.jumps += l - h - 1;
+        Some(h)
+    }
+
+    // The earliest level whose option conflicts with option o, if any.
+    fn reason(&self, o: Int) -> Option<usize> {
+        let mut first = None;
+        for &(j, c) in &self.items_of[o as usize] {
+            if let Some((k, d)) = self.claim[j as usize] {
+                // A primary item has color 0, so it always conflicts
+                if c == 0 || d == 0 || c != d {
+                    first = Some(first.map_or(k, |f: usize| f.min(k)));
+                }
+            }
+        }
+        first
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::choose::*;
+    use crate::testing::{
+        DEAD_ENDS_SPEC, assert_restored, langford_spec, solutions, sorted,
+    };
+    use crate::{Int, ItemOrder, OptOrder, Solve, Solver, Spec, c, x};
+
+    fn cbj_solutions<P: Solve>(solver: &mut Solver<P>) -> Vec<Vec<Int>> {
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        let mut solutions = Vec::new();
+        while solver.next_solution(&mut chooser) {
+            solutions.push(solver.fmt_solution().to_vec());
+        }
+        sorted(solutions)
+    }
+
+    #[test]
+    fn test_backjump() {
+        let spec = langford_spec(7);
+        let build = || {
+            x::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap()
+        };
+        let mut problem = build();
+        let expected = solutions(&mut problem);
+        let mut solver = Solver::new(&mut problem);
+        solver.set_backjumping(0);
+        assert_eq!(cbj_solutions(&mut solver), expected);
+        assert_eq!(expected.len(), 52);
+        assert!(solver.backjump().unwrap().get_jumps() > 0);
+        assert_eq!(cbj_solutions(&mut solver), expected);
+        assert_restored(&mut problem, &build());
+    }
+
+    #[test]
+    fn test_nogoods() {
+        let spec = langford_spec(7);
+        let build = || {
+            x::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap()
+        };
+        let mut problem = build();
+        let expected = solutions(&mut problem);
+        let mut solver = Solver::new(&mut problem);
+        solver.set_backjumping(3);
+        assert_eq!(cbj_solutions(&mut solver), expected);
+        let nogoods = solver.backjump().unwrap().get_nogoods();
+        assert!(!nogoods.is_empty());
+        assert!(nogoods.iter().all(|g| g.len() <= 3));
+        solver.next_solution(&mut mrv_chooser(prefer_any(), no_tiebreak()));
+        solver.reset();
+        assert_restored(&mut problem, &build());
+    }
+
+    #[test]
+    fn test_colors() {
+        let spec = Spec::new(DEAD_ENDS_SPEC, false).unwrap();
+        let build = || {
+            c::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap()
+        };
+        let mut problem = build();
+        let expected = solutions(&mut problem);
+        assert_eq!(expected.len(), 5);
+        let mut solver = Solver::new(&mut problem);
+        solver.set_backjumping(2);
+        assert_eq!(cbj_solutions(&mut solver), expected);
+        assert_restored(&mut problem, &build());
+    }
+
+    #[test]
+    fn test_lookahead() {
+        let spec = langford_spec(7);
+        let mut problem =
+            x::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        let expected = solutions(&mut problem);
+        problem.set_lookahead(2, 3).unwrap();
+        let mut solver = Solver::new(&mut problem);
+        solver.set_backjumping(3);
+        assert_eq!(cbj_solutions(&mut solver), expected);
+    }
+}
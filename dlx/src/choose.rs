@@ -1,145 +1,301 @@
-use std::marker::PhantomData;
-
-use crate::m::ItemsM;
-use crate::{Dance, Int, Items, Opts, Rng, Uint};
-
-pub trait Choose<D: Dance> {
-    fn choose(&mut self, dance: &mut D) -> Uint;
-}
-
-pub trait Preference {
-    fn prefer(&self, i: Uint) -> bool;
-}
-
-pub trait Tiebreak {
-    type D: Dance;
-
-    fn reset(&mut self);
-    fn replace(&mut self, i0: Uint, i1: Uint, dance: &mut Self::D) -> bool;
-}
-
-pub fn mrv_chooser<D: Dance, P: Preference, T: Tiebreak<D = D>>(
-    pref: P, tbreak: T,
-) -> impl Choose<D> {
-    MRVChooser { pref, tbreak }
-}
-
-pub fn prefer_any() -> impl Preference {
-    PreferAny
-}
-
-pub fn prefer_first_n(n: Uint) -> impl Preference {
-    PreferFirstN(n)
-}
-
-pub fn no_tiebreak<D: Dance>() -> impl Tiebreak<D = D> {
-    NoTiebreak(PhantomData)
-}
-
-pub fn rnd_tiebreak<D: Dance>(seed: u32) -> impl Tiebreak<D = D> {
-    RndTiebreak {
-        rng: Rng::new(seed),
-        c: 1,
-        _phantom: PhantomData::<D>,
-    }
-}
-
-pub fn knuth_tiebreak<D: Dance<I: ItemsM>>() -> impl Tiebreak<D = D> {
-    KnuthTiebreak(PhantomData::<D>)
-}
-
-struct MRVChooser<P, T> {
-    pref: P,
-    tbreak: T,
-}
-
-impl<P: Preference, T: Tiebreak> MRVChooser<P, T> {
-    fn choose(&mut self, dance: &mut T::D) -> Uint {
-        let mut min = Int::MAX;
-        let mut p = *dance.items().rlink(0);
-        let mut i = p;
-        while p != 0 {
-            let mut curr = dance.branch_degree(p);
-            if !self.pref.prefer(p) {
-                curr += *dance.opts().len(p);
-            }
-            if curr < min {
-                self.tbreak.reset();
-                min = curr;
-                i = p;
-            } else if curr == min && self.tbreak.replace(i, p, dance) {
-                min = curr;
-                i = p
-            }
-            p = *dance.items().rlink(p);
-        }
-        i
-    }
-}
-
-impl<D: Dance, P: Preference, T: Tiebreak<D = D>> Choose<D>
-    for MRVChooser<P, T>
-{
-    fn choose(&mut self, links: &mut T::D) -> Uint {
-        self.choose(links)
-    }
-}
-
-struct PreferAny;
-
-impl Preference for PreferAny {
-    fn prefer(&self, _: Uint) -> bool {
-        true
-    }
-}
-
-struct PreferFirstN(Uint);
-
-impl Preference for PreferFirstN {
-    fn prefer(&self, i: Uint) -> bool {
-        i < self.0
-    }
-}
-
-struct NoTiebreak<D>(PhantomData<D>);
-
-impl<D: Dance> Tiebreak for NoTiebreak<D> {
-    type D = D;
-
-    fn reset(&mut self) {}
-    fn replace(&mut self, _: Uint, _: Uint, _: &mut D) -> bool {
-        false
-    }
-}
-
-struct RndTiebreak<D> {
-    rng: Rng,
-    c: u32,
-    _phantom: PhantomData<D>,
-}
-
-impl<D: Dance> Tiebreak for RndTiebreak<D> {
-    type D = D;
-    fn reset(&mut self) {
-        self.c = 1;
-    }
-
-    fn replace(&mut self, _: Uint, _: Uint, _: &mut Self::D) -> bool {
-        self.c += 1;
-        self.rng.uniform(self.c) == 0
-    }
-}
-
-struct KnuthTiebreak<D>(PhantomData<D>);
-
-impl<D: Dance<I: ItemsM>> Tiebreak for KnuthTiebreak<D> {
-    type D = D;
-
-    fn reset(&mut self) {}
-
-    fn replace(&mut self, i0: Uint, i1: Uint, dance: &mut Self::D) -> bool {
-        dance.items().slack(i1) < dance.items().slack(i0)
-            || (dance.items().slack(i1) == dance.items().slack(i0)
-                && *dance.opts().len(i1) > *dance.opts().len(i0))
-    }
-}
+use std::marker::PhantomData;
+
+use crate::m::ItemsM;
+use crate::{Dance, Int, Items, Opts, Rng, Spec, Uint};
+
+pub trait Choose<D: Dance> {
+    fn choose(&mut self, dance: &mut D) -> Uint;
+}
+
+pub trait Preference {
+    fn prefer(&self, i: Uint) -> bool;
+}
+
+pub trait Tiebreak {
+    type D: Dance;
+
+    fn reset(&mut self);
+    fn replace(&mut self, i0: Uint, i1: Uint, dance: &mut Self::D) -> bool;
+}
+
+pub fn mrv_chooser<D: Dance, P: Preference, T: Tiebreak<D = D>>(
+    pref: P, tbreak: T,
+) -> impl Choose<D> {
+    MRVChooser { pref, tbreak }
+}
+
+// Chooses the item with the fewest options relative to its weight; an item
+// gains weight each time it is found with no options left. Every later
+// increment is divided by decay, so with decay < 1 recent dead ends count
+// for more.
+pub fn wdeg_chooser<D: Dance>(decay: f64) -> impl Choose<D> {
+    assert!(decay > 0.0 && decay <= 1.0);
+    WDegChooser { weights: Vec::new(), inc: 1.0, decay }
+}
+
+pub fn prefer_any() -> impl Preference {
+    PreferAny
+}
+
+pub fn prefer_first_n(n: Uint) -> impl Preference {
+    PreferFirstN(n)
+}
+
+// Prefers the primary items that Spec::new sorted first: those with '#' in
+// their names if sharp_pref was set, and the others if not.
+pub fn prefer_spec(spec: &Spec) -> impl Preference {
+    let mut preferred = vec![false];
+    let sharp = spec.primary.iter().map(|name| name.contains('#'));
+    preferred.extend(sharp.map(|s| s == spec.sharp_pref));
+    PreferSet(preferred)
+}
+
+pub fn no_tiebreak<D: Dance>() -> impl Tiebreak<D = D> {
+    NoTiebreak(PhantomData)
+}
+
+pub fn rnd_tiebreak<D: Dance>(seed: u32) -> impl Tiebreak<D = D> {
+    RndTiebreak {
+        rng: Rng::new(seed),
+        c: 1,
+        _phantom: PhantomData::<D>,
+    }
+}
+
+pub fn knuth_tiebreak<D: Dance<I: ItemsM>>() -> impl Tiebreak<D = D> {
+    KnuthTiebreak(PhantomData::<D>)
+}
+
+struct MRVChooser<P, T> {
+    pref: P,
+    tbreak: T,
+}
+
+impl<P: Preference, T: Tiebreak> MRVChooser<P, T> {
+    fn choose(&mut self, dance: &mut T::D) -> Uint {
+        let mut min = Int::MAX;
+        let mut p = *dance.items().rlink(0);
+        let mut i = p;
+        while p != 0 {
+            let mut curr = dance.branch_degree(p);
+            if !self.pref.prefer(p) {
+                curr += *dance.opts().len(p);
+            }
+            if curr < min {
+                self.tbreak.reset();
+                min = curr;
+                i = p;
+            } else if curr == min && self.tbreak.replace(i, p, dance) {
+                min = curr;
+                i = p
+            }
+            p = *dance.items().rlink(p);
+        }
+        i
+    }
+}
+
+impl<D: Dance, P: Preference, T: Tiebreak<D = D>> Choose<D>
+    for MRVChooser<P, T>
+{
+    fn choose(&mut self, links: &mut T::D) -> Uint {
+        self.choose(links)
+    }
+}
+
+struct WDegChooser {
+    weights: Vec<f64>,
+    inc: f64,
+    decay: f64,
+}
+
+impl WDegChooser {
+    fn bump(&mut self, i: Uint) {
+        self.weights[i as usize] += self.inc;
+        self.inc /= self.decay;
+        if self.inc > 1e100 {
+            for w in self.weights.iter_mut() {
+                *w *= 1e-100;
+            }
+            self.inc *= 1e-100;
+        }
+    }
+}
+
+impl<D: Dance> Choose<D> for WDegChooser {
+    fn choose(&mut self, dance: &mut D) -> Uint {
+        let np = dance.items().primary() as usize;
+        if self.weights.len() <= np {
+            self.weights.resize(np + 1, 1.0);
+        }
+        let mut min = f64::INFINITY;
+        let mut p = *dance.items().rlink(0);
+        let mut i = p;
+        while p != 0 {
+            let degree = dance.branch_degree(p);
+            if degree == 0 {
+                self.bump(p);
+                return p;
+            }
+            let curr = degree as f64 / self.weights[p as usize];
+            if curr < min {
+                min = curr;
+                i = p;
+            }
+            p = *dance.items().rlink(p);
+        }
+        i
+    }
+}
+
+struct PreferAny;
+
+impl Preference for PreferAny {
+    fn prefer(&self, _: Uint) -> bool {
+        true
+    }
+}
+
+struct PreferFirstN(Uint);
+
+impl Preference for PreferFirstN {
+    fn prefer(&self, i: Uint) -> bool {
+        i < self.0
+    }
+}
+
+struct PreferSet(Vec<bool>);
+
+impl Preference for PreferSet {
+    fn prefer(&self, i: Uint) -> bool {
+        self.0.get(i as usize).copied().unwrap_or(false)
+    }
+}
+
+struct NoTiebreak<D>(PhantomData<D>);
+
+impl<D: Dance> Tiebreak for NoTiebreak<D> {
+    type D = D;
+
+    fn reset(&mut self) {}
+    fn replace(&mut self, _: Uint, _: Uint, _: &mut D) -> bool {
+        false
+    }
+}
+
+struct RndTiebreak<D> {
+    rng: Rng,
+    c: u32,
+    _phantom: PhantomData<D>,
+}
+
+impl<D: Dance> Tiebreak for RndTiebreak<D> {
+    type D = D;
+    fn reset(&mut self) {
+        self.c = 1;
+    }
+
+    fn replace(&mut self, _: Uint, _: Uint, _: &mut Self::D) -> bool {
+        self.c += 1;
+        self.rng.uniform(self.c) == 0
+    }
+}
+
+struct KnuthTiebreak<D>(PhantomData<D>);
+
+impl<D: Dance<I: ItemsM>> Tiebreak for KnuthTiebreak<D> {
+    type D = D;
+
+    fn reset(&mut self) {}
+
+    fn replace(&mut self, i0: Uint, i1: Uint, dance: &mut Self::D) -> bool {
+        dance.items().slack(i1) < dance.items().slack(i0)
+            || (dance.items().slack(i1) == dance.items().slack(i0) && {
+                let len = *dance.opts().len(i1);
+                len > *dance.opts().len(i0)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ItemOrder, OptOrder, Solver, m, x};
+
+    #[test]
+    fn test_wdeg_learns() {
+        let os: Vec<Vec<Uint>> = vec![vec![0, 1], vec![0, 2], vec![1, 2]];
+        let mut problem =
+            x::make_problem(3, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        let mut chooser =
+            WDegChooser { weights: Vec::new(), inc: 1.0, decay: 0.5 };
+        let mut solver = Solver::new(&mut problem);
+        assert!(!solver.next_solution(&mut chooser));
+        // Every branch leaves one item without options
+        let w = &chooser.weights;
+        assert_eq!(w.len(), 4);
+        assert!(w.iter().filter(|&&w| w > 1.0).count() > 0);
+        assert!(chooser.inc > 1.0, "no decay");
+    }
+
+    fn solutions<C: Choose<m::Problem>>(
+        spec: &Spec, chooser: &mut C,
+    ) -> Vec<Vec<Int>> {
+        let mut problem =
+            m::Problem::from_spec(spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        let mut solver = Solver::new(&mut problem);
+        let mut solutions = Vec::new();
+        while solver.next_solution(chooser) {
+            let mut solution = solver.fmt_solution().to_vec();
+            solution.sort();
+            solutions.push(solution);
+        }
+        solutions.sort();
+        solutions
+    }
+
+    #[test]
+    fn test_wdeg_solutions() {
+        let spec_str = "
+2:3|a 1|b c | x
+a b
+a c x
+a x
+b c
+c
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let mut mrv = mrv_chooser(prefer_any(), no_tiebreak());
+        let expected = solutions(&spec, &mut mrv);
+        assert!(!expected.is_empty());
+        assert_eq!(solutions(&spec, &mut wdeg_chooser(0.9)), expected);
+    }
+
+    #[test]
+    fn test_prefer_spec() {
+        let spec_str = "
+a b# c#
+a b#
+b#
+c#
+b# c#
+";
+        // Item a has the fewest options, but is only preferred when the
+        // sharp items are not; it is sorted first in that case
+        for (sharp_pref, expected) in [(true, 2), (false, 1)] {
+            let spec = Spec::new(spec_str, sharp_pref).unwrap();
+            let mut problem =
+                x::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                    .unwrap();
+            let mut chooser = mrv_chooser(prefer_spec(&spec), no_tiebreak());
+            assert_eq!(chooser.choose(&mut problem), expected);
+        }
+        let spec_str = format!("1:2|{}", spec_str.trim());
+        let spec = Spec::new(&spec_str, true).unwrap();
+        let mut problem =
+            m::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        let mut chooser = mrv_chooser(prefer_spec(&spec), no_tiebreak());
+        assert_eq!(chooser.choose(&mut problem), 2);
+    }
+}
@@ -0,0 +1,403 @@
+use anyhow::Result;
+
+use crate::choose::Choose;
+use crate::{Int, Solve, Solver, Spec};
+
+// A part of a problem that shares no items with the other parts, along
+// with the numbers its options have in the original problem.
+pub struct Component {
+    pub spec: Spec,
+    pub opts: Vec<usize>,
+}
+
+// The name of an item as it appears in options, without any multiplicity
+// prefix.
+pub(crate) fn item_name(item: &str) -> &str {
+    item.rsplit('|').next().unwrap()
+}
+
+// An item with a multiplicity prefix of 0:v need not be covered.
+fn is_optional(item: &str) -> bool {
+    let data = item.split('|').collect::<Vec<_>>();
+    data.len() == 2 && data[0].split(':').next() == Some("0")
+}
+
+pub(crate) fn find(parent: &mut [usize], i: usize) -> usize {
+    let mut i = i;
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+// Splits the items of a spec into the connected components of the graph in
+// which two items are joined if they appear in the same option. Components
+// without primary items are left out, since they are always solved by
+// selecting nothing. The components are ordered by their number of options.
+pub fn components(spec: &Spec) -> Vec<Component> {
+    use std::collections::HashMap;
+    let np = spec.primary.len();
+    let names = spec.primary.iter().chain(spec.secondary.iter());
+    let idx = names
+        .enumerate()
+        .map(|(i, item)| (item_name(item), i))
+        .collect::<HashMap<_, _>>();
+    let opt_items = spec
+        .opts
+        .iter()
+        .map(|opt| {
+            opt.iter()
+                .filter_map(|itm| {
+                    idx.get(item_name(itm).split(':').next().unwrap())
+                })
+                .copied()
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut parent = (0..idx.len()).collect::<Vec<_>>();
+    for is in &opt_items {
+        for w in is.windows(2) {
+            let a = find(&mut parent, w[0]);
+            let b = find(&mut parent, w[1]);
+            parent[a] = b;
+        }
+    }
+    let mut comp_of = HashMap::new();
+    let mut comps: Vec<Component> = Vec::new();
+    for i in 0..np {
+        let r = find(&mut parent, i);
+        let k = *comp_of.entry(r).or_insert_with(|| {
+            comps.push(Component {
+                spec: Spec {
+                    primary: Vec::new(),
+                    secondary: Vec::new(),
+                    opts: Vec::new(),
+                    sharp_pref: spec.sharp_pref,
+                },
+                opts: Vec::new(),
+            });
+            comps.len() - 1
+        });
+        comps[k].spec.primary.push(spec.primary[i].clone());
+    }
+    for (j, item) in spec.secondary.iter().enumerate() {
+        let r = find(&mut parent, np + j);
+        if let Some(&k) = comp_of.get(&r) {
+            comps[k].spec.secondary.push(item.clone());
+        }
+    }
+    for (o, is) in opt_items.iter().enumerate() {
+        // Options without primary items are never selected
+        if !is.iter().any(|&i| i < np) {
+            continue;
+        }
+        let k = comp_of[&find(&mut parent, is[0])];
+        comps[k].spec.opts.push(spec.opts[o].clone());
+        comps[k].opts.push(o);
+    }
+    comps.sort_by_key(|c| c.opts.len());
+    comps
+}
+
+struct Part<P> {
+    problem: P,
+    opts: Vec<usize>,
+}
+
+// A problem solved as a product of its independent components.
+pub struct Decomposition<P> {
+    parts: Vec<Part<P>>,
+    // Set if a component has primary items that no option can cover
+    unsolvable: bool,
+}
+
+impl<P: Solve> Decomposition<P> {
+    pub fn new<F>(spec: &Spec, mut build: F) -> Result<Decomposition<P>>
+    where
+        F: FnMut(&Spec) -> Result<P>,
+    {
+        let mut parts = Vec::new();
+        let mut unsolvable = false;
+        for comp in components(spec) {
+            if comp.opts.is_empty() {
+                let primary = &comp.spec.primary;
+                unsolvable |= !primary.iter().all(|item| is_optional(item));
+                continue;
+            }
+            let problem = build(&comp.spec)?;
+            parts.push(Part { problem, opts: comp.opts });
+        }
+        Ok(Decomposition { parts, unsolvable })
+    }
+
+    // The number of components that need to be solved.
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    // Counts the solutions of each component in turn, stopping at the
+    // first one that has none.
+    pub fn count<C, F>(&mut self, mut make_chooser: F) -> u128
+    where
+        C: Choose<P>,
+        F: FnMut() -> C,
+    {
+        if self.unsolvable {
+            return 0;
+        }
+        let mut total: u128 = 1;
+        for part in &mut self.parts {
+            let mut chooser = make_chooser();
+            let mut solver = Solver::new(&mut part.problem);
+            let mut count: u128 = 0;
+            while solver.next_solution(&mut chooser) {
+                count += 1;
+            }
+            if count == 0 {
+                return 0;
+            }
+            total *= count;
+        }
+        total
+    }
+
+    // Enumerates the solutions of the whole problem, one combination of
+    // component solutions at a time.
+    pub fn solutions<C, F>(
+        &mut self, mut make_chooser: F,
+    ) -> Solutions<'_, P, C>
+    where
+        C: Choose<P>,
+        F: FnMut() -> C,
+    {
+        let done = self.unsolvable;
+        let mut solvers = Vec::new();
+        let mut opts = Vec::new();
+        for part in &mut self.parts {
+            solvers.push((Solver::new(&mut part.problem), make_chooser()));
+            opts.push(&part.opts[..]);
+        }
+        let current = vec![Vec::new(); solvers.len()];
+        Solutions { solvers, opts, current, started: false, done }
+    }
+}
+
+pub struct Solutions<'a, P: Solve, C> {
+    solvers: Vec<(Solver<'a, P>, C)>,
+    opts: Vec<&'a [usize]>,
+    // The current solution of each component, in original option numbers
+    current: Vec<Vec<Int>>,
+    started: bool,
+    done: bool,
+}
+
+impl<P: Solve, C: Choose<P>> Solutions<'_, P, C> {
+    // Moves component k on to its next solution, returning false if it has
+    // no more. An exhausted component starts again from the beginning.
+    fn advance(&mut self, k: usize) -> bool {
+        let (solver, chooser) = &mut self.solvers[k];
+        if !solver.next_solution(chooser) {
+            return false;
+        }
+        let opts = self.opts[k];
+        let solution = solver.fmt_solution().iter();
+        self.current[k] = solution.map(|&o| opts[o as usize] as Int).collect();
+        true
+    }
+}
+
+impl<P: Solve, C: Choose<P>> Iterator for Solutions<'_, P, C> {
+    type Item = Vec<Int>;
+
+    fn next(&mut self) -> Option<Vec<Int>> {
+        if self.done {
+            return None;
+        }
+        if !self.started {
+            self.started = true;
+            for k in 0..self.solvers.len() {
+                if !self.advance(k) {
+                    self.done = true;
+                    return None;
+                }
+            }
+        } else {
+            // Odometer order: the last component changes fastest
+            let mut k = self.solvers.len();
+            loop {
+                if k == 0 {
+                    self.done = true;
+                    return None;
+                }
+                k -= 1;
+                if self.advance(k) {
+                    break;
+                }
+                // The search is over, so this starts it again
+                self.advance(k);
+            }
+        }
+        Some(self.current.concat())
+    }
+}
+
+// Leaves the components as they were before the enumeration.
+impl<P: Solve, C> Drop for Solutions<'_, P, C> {
+    fn drop(&mut self) {
+        for (solver, _) in &mut self.solvers {
+            solver.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::choose::*;
+    use crate::testing::{assert_restored, solutions, sorted};
+    use crate::{ItemOrder, OptOrder, c, m, mc, x};
+
+    const XC_SPEC: &str = "
+a b c d e | x
+a b
+a
+b
+c d x
+c
+d x
+e
+";
+
+    #[test]
+    fn test_components() {
+        let spec = Spec::new(XC_SPEC, false).unwrap();
+        let comps = components(&spec);
+        let opts = comps.iter().map(|c| c.opts.clone()).collect::<Vec<_>>();
+        assert_eq!(opts, vec![vec![6], vec![0, 1, 2], vec![3, 4, 5]]);
+        assert_eq!(comps[2].spec.primary, vec!["c", "d"]);
+        assert_eq!(comps[2].spec.secondary, vec!["x"]);
+    }
+
+    #[test]
+    fn test_product() {
+        let spec = Spec::new(XC_SPEC, false).unwrap();
+        let build =
+            |s: &Spec| x::Problem::from_spec(s, OptOrder::Seq, ItemOrder::Seq);
+        let mut problem = build(&spec).unwrap();
+        let expected = solutions(&mut problem);
+        let mut parts = Decomposition::new(&spec, build).unwrap();
+        assert_eq!(parts.len(), 3);
+        let chooser = || mrv_chooser(prefer_any(), no_tiebreak());
+        assert_eq!(parts.count(chooser), 4);
+        assert_eq!(sorted(parts.solutions(chooser)), expected);
+        assert_eq!(parts.solutions(chooser).take(3).count(), 3);
+        assert_eq!(sorted(parts.solutions(chooser)), expected);
+        for part in &mut parts.parts {
+            let init = build(&part_spec(&spec, &part.opts)).unwrap();
+            assert_restored(&mut part.problem, &init);
+        }
+    }
+
+    // Rebuilds the spec of a component from its option numbers.
+    fn part_spec(spec: &Spec, opts: &[usize]) -> Spec {
+        components(spec)
+            .into_iter()
+            .find(|c| c.opts == opts)
+            .unwrap()
+            .spec
+    }
+
+    #[test]
+    fn test_unsolvable() {
+        let spec_str = "
+a b c
+a
+b
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let build =
+            |s: &Spec| x::Problem::from_spec(s, OptOrder::Seq, ItemOrder::Seq);
+        let mut parts = Decomposition::new(&spec, build).unwrap();
+        let chooser = || mrv_chooser(prefer_any(), no_tiebreak());
+        assert_eq!(parts.count(chooser), 0);
+        assert_eq!(parts.solutions(chooser).next(), None);
+
+        let spec_str = "
+2|a b c
+a
+b c
+b
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let build =
+            |s: &Spec| m::Problem::from_spec(s, OptOrder::Seq, ItemOrder::Seq);
+        let mut parts = Decomposition::new(&spec, build).unwrap();
+        let chooser = || mrv_chooser(prefer_any(), no_tiebreak());
+        assert_eq!(parts.count(chooser), 0);
+        assert_eq!(parts.solutions(chooser).next(), None);
+    }
+
+    #[test]
+    fn test_colors() {
+        let spec_str = "
+0:1|p 2|q r s | x y
+p q x:A
+q x:A
+q x:B
+r y
+s y:C
+r s
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let build =
+            |s: &Spec| mc::Problem::from_spec(s, OptOrder::Seq, ItemOrder::Seq);
+        let mut problem = build(&spec).unwrap();
+        let expected = solutions(&mut problem);
+        let mut parts = Decomposition::new(&spec, build).unwrap();
+        assert_eq!(parts.len(), 2);
+        let chooser = || mrv_chooser(prefer_any(), no_tiebreak());
+        assert_eq!(parts.count(chooser), expected.len() as u128);
+        assert_eq!(sorted(parts.solutions(chooser)), expected);
+
+        let spec_str = "
+p q | x
+p x:A
+q x:B
+p
+q
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let build =
+            |s: &Spec| c::Problem::from_spec(s, OptOrder::Seq, ItemOrder::Seq);
+        let mut parts = Decomposition::new(&spec, build).unwrap();
+        assert_eq!(parts.len(), 1);
+        let chooser = || mrv_chooser(prefer_any(), no_tiebreak());
+        assert_eq!(parts.count(chooser), 3);
+    }
+
+    #[test]
+    fn test_option_uses() {
+        let spec_str = "
+a b | x
+1:2|a x
+b x
+b
+a
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let build =
+            |s: &Spec| m::Problem::from_spec(s, OptOrder::Seq, ItemOrder::Seq);
+        let mut problem = build(&spec).unwrap();
+        let expected = solutions(&mut problem);
+        assert_eq!(expected.len(), 1);
+        let mut parts = Decomposition::new(&spec, build).unwrap();
+        let chooser = || mrv_chooser(prefer_any(), no_tiebreak());
+        assert_eq!(parts.count(chooser), 1);
+        assert_eq!(sorted(parts.solutions(chooser)), expected);
+    }
+}
@@ -0,0 +1,237 @@
+use anyhow::{Result, bail};
+
+use crate::nodes::Link;
+use crate::{Dance, Int, Items, OptData, OptOrder, Opts, Uint, opt_starts};
+use crate::{c, m, mc, x};
+
+type OData<D> = <<D as Dance>::O as Opts>::Data;
+
+// Changes to a problem must be made between searches, when no options are
+// selected; item and option numbers are the same as in the input data.
+pub trait Edit: Dance {
+    // Appends an option, reusing the nodes of a deleted option of the same
+    // size if there is one. Returns the new option's number.
+    fn add_option(&mut self, opt: &[OData<Self>]) -> Result<Int> {
+        use std::collections::HashSet;
+        let n = self.items().count();
+        let np = self.items().primary();
+        let mut used = HashSet::new();
+        for node in opt {
+            let i = node.get_item();
+            if i >= n {
+                bail!("Invalid item");
+            }
+            if !used.insert(i) {
+                bail!("Duplicate items in option");
+            }
+        }
+        let m = *self.opts().opt_count() + 1;
+        if !opt.iter().any(|node| node.get_item() < np) {
+            *self.opts().opt_count() = m;
+            return Ok(m - 1);
+        }
+        let k = opt.len() as Uint;
+        let size = self.opts().size();
+        let mut p = size - 1;
+        for f in 0..self.opts().free().len() {
+            let s = self.opts().free()[f];
+            if *self.dlink(s) - s == k {
+                self.opts().free().swap_remove(f);
+                p = s;
+                break;
+            }
+        }
+        if p == size - 1 && size + k + 1 > self.opts().max_size() {
+            bail!("Too many nodes for the link type");
+        }
+        *self.opts().opt_count() = m;
+        self.opts().link_option(p, np, m, opt, &mut OptOrder::Seq);
+        Ok(m - 1)
+    }
+
+    // Deletes an option, which changes nothing if the option was skipped
+    // for having no primary items or was deleted already. The items it
+    // covered still have to be covered, but the least number of times an m
+    // or mc option had to be used goes with it.
+    fn delete_option(&mut self, o: Int) -> Result<()> {
+        unlink_option(self, o)
+    }
+
+    // Appends a secondary item, returning its number.
+    fn add_secondary(&mut self) -> Uint {
+        self.add_secondaries(1)
+    }
+
+    // Appends k secondary items, returning the number of the first. Each
+    // call moves every option node, so items are best added all at once.
+    fn add_secondaries(&mut self, k: Uint) -> Uint {
+        let n = self.items().count();
+        self.items().add_secondaries(k);
+        self.opts().add_items(n, k);
+        n
+    }
+}
+
+// Unlinks the nodes of option o, if it still has any.
+fn unlink_option<D: Dance + ?Sized>(dance: &mut D, o: Int) -> Result<()> {
+    if o < 0 || o >= *dance.opts().opt_count() {
+        bail!("Option {} is not in the problem", o);
+    }
+    let Some(&start) = opt_starts(dance).get(o as usize) else {
+        return Ok(());
+    };
+    if start == 0 {
+        return Ok(());
+    }
+    let s = start - 1;
+    let last = *dance.dlink(s);
+    let w = dance.uses_left(start);
+    for q in start..=last {
+        let u = *dance.ulink(q);
+        let d = *dance.dlink(q);
+        *dance.dlink(u) = d;
+        *dance.ulink(d) = u;
+        let i = *dance.top(q) as Uint;
+        *dance.len(i) -= w;
+    }
+    // A zero spacer marks the nodes as free
+    *dance.top(last + 1) = 0;
+    dance.opts().free().push(s);
+    Ok(())
+}
+
+impl<S: Link> Edit for x::Problem<S> {}
+
+impl Edit for c::Problem {}
+
+impl Edit for m::Problem {
+    fn delete_option(&mut self, o: Int) -> Result<()> {
+        unlink_option(self, o)?;
+        m::drop_min_uses(self, o as usize);
+        Ok(())
+    }
+}
+
+impl Edit for mc::Problem {
+    fn delete_option(&mut self, o: Int) -> Result<()> {
+        unlink_option(self, o)?;
+        m::drop_min_uses(self, o as usize);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ItemOrder;
+    use crate::testing::solutions;
+
+    #[test]
+    fn test_edit_x() {
+        let os: Vec<Vec<Uint>> = vec![
+            vec![2, 4],
+            vec![0, 3, 6],
+            vec![1, 2, 5],
+            vec![0, 3, 5],
+            vec![1, 6],
+            vec![3, 4, 6],
+        ];
+        let mut problem =
+            x::make_problem(7, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        assert_eq!(solutions(&mut problem), vec![vec![0, 3, 4]]);
+        problem.delete_option(3).unwrap();
+        problem.delete_option(3).unwrap();
+        assert!(problem.delete_option(6).is_err());
+        assert!(solutions(&mut problem).is_empty());
+        let size = problem.opts().size();
+        assert_eq!(problem.add_option(&[0, 3, 5]).unwrap(), 6);
+        assert_eq!(problem.opts().size(), size, "nodes not reused");
+        assert_eq!(solutions(&mut problem), vec![vec![0, 4, 6]]);
+        assert_eq!(problem.add_option(&[0, 1, 2, 3]).unwrap(), 7);
+        assert_eq!(problem.opts().size(), size + 5);
+        assert!(problem.add_option(&[0, 0]).is_err());
+
+        let mut rebuilt = os.clone();
+        rebuilt[3] = vec![];
+        rebuilt.push(vec![0, 3, 5]);
+        rebuilt.push(vec![0, 1, 2, 3]);
+        let mut expected =
+            x::make_problem(7, 0, &rebuilt, OptOrder::Seq, ItemOrder::Seq);
+        assert_eq!(solutions(&mut problem), solutions(&mut expected));
+    }
+
+    #[test]
+    fn test_edit_c() {
+        let os: Vec<Vec<(Uint, Int)>> = vec![
+            vec![(0, 0), (1, 0), (3, 0), (4, 65)],
+            vec![(0, 0), (2, 0), (3, 65), (4, 0)],
+            vec![(0, 0), (3, 66)],
+            vec![(1, 0), (3, 65)],
+            vec![(2, 0), (4, 66)],
+        ];
+        let mut problem =
+            c::make_problem(3, 2, &os, OptOrder::Seq, ItemOrder::Seq);
+        assert_eq!(solutions(&mut problem), vec![vec![1, 3]]);
+        assert_eq!(problem.add_secondary(), 5);
+        problem.delete_option(3).unwrap();
+        assert_eq!(problem.add_option(&[(1, 0), (5, 67)]).unwrap(), 5);
+        assert_eq!(problem.add_option(&[(2, 0), (5, 68)]).unwrap(), 6);
+        assert_eq!(
+            solutions(&mut problem),
+            vec![vec![0, 6], vec![1, 5], vec![2, 4, 5]]
+        );
+        assert!(problem.add_option(&[(2, 0), (6, 0)]).is_err());
+
+        assert_eq!(problem.add_secondaries(2), 6);
+        assert!(crate::nodes::check_links(&mut problem).is_ok());
+        assert_eq!(problem.add_option(&[(2, 0), (6, 0), (7, 0)]).unwrap(), 7);
+        assert_eq!(
+            solutions(&mut problem),
+            vec![
+                vec![0, 6],
+                vec![0, 7],
+                vec![1, 5],
+                vec![2, 4, 5],
+                vec![2, 5, 7]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_edit_m() {
+        let spec_str = "
+1:2|a b c
+0:2|a
+a b
+c
+b c
+";
+        let spec = crate::Spec::new(spec_str, false).unwrap();
+        let mut problem =
+            m::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        assert_eq!(
+            solutions(&mut problem),
+            vec![vec![0, 0, 3], vec![0, 1, 2], vec![0, 3], vec![1, 2]]
+        );
+        problem.delete_option(0).unwrap();
+        assert!(problem.delete_option(-1).is_err());
+        assert_eq!(solutions(&mut problem), vec![vec![1, 2]]);
+
+        // Option 0 has to be used at least once, which stops counting once
+        // it is deleted, and option 3 has no primary items
+        let spec_str = "2|a | x\n1:2|a\na x\na\nx";
+        let spec = crate::Spec::new(spec_str, false).unwrap();
+        let mut problem =
+            m::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        assert_eq!(
+            solutions(&mut problem),
+            vec![vec![0, 0], vec![0, 1], vec![0, 2]]
+        );
+        problem.delete_option(0).unwrap();
+        problem.delete_option(3).unwrap();
+        assert!(problem.delete_option(4).is_err());
+        assert_eq!(solutions(&mut problem), vec![vec![1, 2]]);
+    }
+}
@@ -1,386 +1,832 @@
-use anyhow::{Result, anyhow, bail};
-use num_traits::AsPrimitive;
-
-use crate::choose::Choose;
-
-pub mod x;
-pub mod c;
-pub mod m;
-pub mod mc;
-pub mod p;
-pub mod choose;
-
-#[cfg(not(feature = "32-bit"))]
-pub type Uint = u64;
-#[cfg(not(feature = "32-bit"))]
-pub type Int = i64;
-
-#[cfg(feature = "32-bit")]
-pub type Uint = u32;
-#[cfg(feature = "32-bit")]
-pub type Int = i32;
-
-#[allow(non_snake_case)]
-pub fn Uint<T: AsPrimitive<Uint>>(data: T) -> Uint {
-    data.as_()
-}
-
-#[allow(non_snake_case)]
-pub fn Int<T: AsPrimitive<Int>>(data: T) -> Int {
-    data.as_()
-}
-
-pub trait Dance {
-    type I: Items;
-    type O: Opts;
-
-    fn items(&mut self) -> &mut Self::I;
-    fn opts(&mut self) -> &mut Self::O;
-
-    #[inline]
-    fn llink(&mut self, i: Uint) -> &mut Uint {
-        self.items().llink(i)
-    }
-
-    #[inline]
-    fn rlink(&mut self, i: Uint) -> &mut Uint {
-        self.items().rlink(i)
-    }
-
-    #[inline]
-    fn dlink(&mut self, i: Uint) -> &mut Uint {
-        self.opts().dlink(i)
-    }
-
-    #[inline]
-    fn ulink(&mut self, i: Uint) -> &mut Uint {
-        self.opts().ulink(i)
-    }
-
-    #[inline]
-    fn top(&mut self, i: Uint) -> &mut Int {
-        self.opts().top(i)
-    }
-
-    #[inline]
-    fn len(&mut self, i: Uint) -> &mut Int {
-        self.opts().len(i)
-    }
-
-    fn updates(&mut self) -> &mut isize;
-
-    fn cover(&mut self, i: Uint);
-    fn commit(&mut self, p: Uint, j: Uint);
-    fn uncover(&mut self, i: Uint);
-    fn uncommit(&mut self, p: Uint, j: Uint);
-    fn hide(&mut self, p: Uint);
-    fn unhide(&mut self, p: Uint);
-    fn branch_degree(&mut self, i: Uint) -> Int;
-}
-
-#[allow(clippy::len_without_is_empty)]
-pub trait Items {
-    fn llink(&mut self, i: Uint) -> &mut Uint;
-    fn rlink(&mut self, i: Uint) -> &mut Uint;
-
-    fn primary(&self) -> Uint;
-    fn count(&self) -> Uint;
-
-    fn init_links(&mut self) {
-        let n1 = self.primary();
-        assert!(n1 > 0, "No primary items");
-        let n = self.count();
-        for i in (1 as Uint)..=n {
-            *self.llink(i) = i - 1;
-            *self.rlink(i - 1) = i;
-        }
-        *self.llink(n + 1) = n;
-        *self.rlink(n) = n + 1;
-        *self.llink(n1 + 1) = n + 1;
-        *self.rlink(n + 1) = n1 + 1;
-        *self.llink(0) = n1;
-        *self.rlink(n1) = 0;
-    }
-}
-
-pub trait OptData: Clone + Copy + Default {
-    fn new_item(i: Uint) -> Self;
-    fn get_item(&self) -> Uint;
-}
-
-pub trait Opts {
-    type Data: OptData;
-
-    fn len(&mut self, i: Uint) -> &mut Int;
-    fn top(&mut self, i: Uint) -> &mut Int;
-    fn ulink(&mut self, i: Uint) -> &mut Uint;
-    fn dlink(&mut self, i: Uint) -> &mut Uint;
-
-    fn set_data(&mut self, pk: Uint, s: Self::Data) -> Uint;
-    fn get_data_item(s: Self::Data) -> Uint;
-
-    fn init_links(
-        &mut self, n: Uint, np: Uint, order: OptOrder, os: &[Vec<Self::Data>],
-    ) {
-        let mut order = order;
-        for i in (1 as Uint)..=n {
-            *self.ulink(i) = i;
-            *self.dlink(i) = i;
-        }
-        let mut m: Int = 0;
-        let mut p: Uint = n + 1;
-
-        for opt in os {
-            let mut k = 0;
-            let mut has_primary = false;
-            for node in opt {
-                if Self::get_data_item(*node) < np {
-                    has_primary = true;
-                }
-            }
-            // Options without primary items are skipped but the option
-            // number is still incremented so that the options match their
-            // position in the input data.
-            m += 1;
-            if !has_primary {
-                continue;
-            }
-            for node in opt {
-                k += 1;
-                // Internal item indexes are 1-based.
-                let i = self.set_data(p + k, *node) + 1;
-                *self.len(i) += 1;
-                let q = match order {
-                    OptOrder::Seq => *self.ulink(i),
-                    OptOrder::Rnd(ref mut rng) => {
-                        let mut i = i;
-                        let p = rng.uniform(*self.len(i) as u32);
-                        for _ in 0..p {
-                            i = *self.dlink(i);
-                        }
-                        i
-                    }
-                };
-                let qd = *self.dlink(q);
-                *self.ulink(p + k) = q;
-                *self.dlink(p + k) = qd;
-                *self.dlink(q) = p + k;
-                *self.ulink(qd) = p + k;
-                *self.top(p + k) = i as Int;
-            }
-            *self.dlink(p) = p + k;
-            // add spacer
-            p = p + k + 1;
-            self.set_data(p, Default::default());
-            *self.top(p) = -m;
-            *self.ulink(p) = p - k;
-        }
-        assert!(m > 0, "No options");
-    }
-}
-
-pub trait Solve: Dance {
-    fn enter_level(&mut self, i: Uint, l: Uint, xl: Uint);
-    fn prepare_to_branch(&mut self, i: Uint, l: Uint, xl: Uint);
-    fn try_item(&mut self, i: Uint, l: Uint, xl: Uint) -> bool;
-    fn try_again(&mut self, i: Uint, l: Uint, xl: &mut Uint) -> bool;
-    fn restore_item(&mut self, i: Uint, l: Uint, xl: Uint);
-}
-
-pub struct Solver<'a, P> {
-    problem: &'a mut P,
-    x: Vec<Uint>,
-    o: Vec<Int>,
-    profile: Vec<usize>,
-    l: Uint,
-    i: Uint,
-    restart: bool,
-}
-
-impl<'a, P: Solve> Solver<'a, P> {
-    pub fn new(problem: &'a mut P) -> Solver<'a, P> {
-        Solver {
-            problem,
-            x: Vec::new(),
-            o: Vec::new(),
-            profile: Vec::new(),
-            l: 0,
-            i: 0,
-            restart: false,
-        }
-    }
-
-    pub fn next_solution<C: Choose<P>>(&mut self, chooser: &mut C) -> bool {
-        let mut l = self.l;
-        let mut i = self.i;
-        if *self.problem.updates() < 0 {
-            *self.problem.updates() = 0;
-        }
-
-        loop {
-            if self.restart {
-                self.restart = false;
-            } else if *self.problem.items().rlink(0) == 0 {
-                self.l = l;
-                self.i = i;
-                self.restart = true;
-                return true;
-            } else {
-                if self.x.len() == l as usize {
-                    self.x.push(0);
-                    self.profile.push(0);
-                    self.problem.enter_level(i, l, self.x[l as usize]);
-                }
-                self.profile[l as usize] += 1;
-                i = chooser.choose(self.problem);
-                // TODO: return option from choose
-                if self.problem.branch_degree(i) != 0 {
-                    self.x[l as usize] = *self.problem.opts().dlink(i);
-                    self.problem.prepare_to_branch(i, l, self.x[l as usize]);
-                    if self.problem.try_item(i, l, self.x[l as usize]) {
-                        l += 1;
-                        continue;
-                    } else {
-                        self.problem.restore_item(i, l, self.x[l as usize]);
-                    }
-                }
-            }
-            loop {
-                if l == 0 {
-                    self.l = l;
-                    *self.problem.updates() = -*self.problem.updates();
-                    return false;
-                }
-                l -= 1;
-                i = *self.problem.opts().top(self.x[l as usize]) as Uint;
-                if self.problem.try_again(i, l, &mut self.x[l as usize]) {
-                    l += 1;
-                    break;
-                }
-            }
-        }
-    }
-
-    pub fn fmt_solution(&mut self) -> &[Int] {
-        let n = self.problem.items().count();
-        self.o.clear();
-        for xj in &self.x[..self.l as usize] {
-            let mut r = *xj;
-            if r <= n {
-                // TODO: report these elemente
-                continue;
-            }
-            while *self.problem.opts().top(r) >= 0 {
-                r += 1;
-            }
-            // Internal option indexes are 1-based
-            self.o.push(-*self.problem.opts().top(r) - 1);
-        }
-        &self.o
-    }
-
-    pub fn get_updates(&mut self) -> isize {
-        self.problem.updates().abs()
-    }
-
-    pub fn get_profile(&self) -> &[usize] {
-        &self.profile
-    }
-}
-
-pub struct Spec {
-    pub primary: Vec<String>,
-    pub secondary: Vec<String>,
-    pub opts: Vec<Vec<String>>,
-}
-
-impl Spec {
-    pub fn new(spec: &str, sharp_pref: bool) -> Result<Spec> {
-        use std::cmp::Ordering;
-        let mut lines = spec
-            .lines()
-            .map(str::trim)
-            .filter(|s| !s.is_empty() && !s.starts_with('|'));
-        let items =
-            lines.next().ok_or_else(|| anyhow!("No items specified"))?;
-        let opts: Vec<String> = lines.map(String::from).collect();
-        if opts.is_empty() {
-            bail!("No options specified");
-        }
-        let item_list = items
-            .split_whitespace()
-            .map(String::from)
-            .collect::<Vec<_>>();
-        let items = item_list.split(|e| e == "|").collect::<Vec<_>>();
-        if items.len() > 2 {
-            bail!("Too many '|' separators");
-        }
-        let secondary = if items.len() > 1 {
-            if items[1].is_empty() {
-                bail!("No seecondary items specified");
-            }
-            // TODO: no '#' in secondary?
-            items[1].to_vec()
-        } else {
-            Vec::new()
-        };
-        let mut primary = items[0].to_vec();
-        if primary.is_empty() {
-            bail!("No primary items");
-        }
-        primary.sort_by(|a, b| {
-            let a_sharp = a.contains("#");
-            let b_sharp = b.contains("#");
-            if a_sharp == b_sharp {
-                Ordering::Equal
-            } else if a_sharp == sharp_pref {
-                Ordering::Less
-            } else {
-                Ordering::Greater
-            }
-        });
-        let opts = opts
-            .iter()
-            .map(|o| o.split_whitespace().map(String::from).collect())
-            .collect();
-        Ok(Spec { primary, secondary, opts })
-    }
-}
-
-pub struct Rng {
-    state: u32,
-}
-
-#[allow(clippy::should_implement_trait)]
-impl Rng {
-    pub fn new(state: u32) -> Rng {
-        assert_ne!(state, 0);
-        Rng { state }
-    }
-
-    pub fn next(&mut self) -> u32 {
-        let mut x = self.state;
-        x ^= x << 13;
-        x ^= x >> 17;
-        x ^= x << 5;
-        self.state = x;
-        x
-    }
-
-    pub fn uniform(&mut self, max: u32) -> u32 {
-        let t = 0x80000000 - (0x80000000 % max);
-        let mut r;
-        loop {
-            r = self.next();
-            if t > r {
-                break;
-            }
-        }
-        r % max
-    }
-}
-
-pub enum OptOrder {
-    Seq,
-    Rnd(Rng),
-}
+use std::ops::DerefMut;
+
+use anyhow::{Result, anyhow, bail};
+use num_traits::AsPrimitive;
+
+use crate::cbj::Backjump;
+use crate::choose::Choose;
+use crate::observe::{NoObserver, Observer};
+use crate::p::Reduce;
+use crate::step::ExactCover;
+
+pub mod x;
+pub mod c;
+pub mod m;
+pub mod mc;
+pub mod p;
+pub mod choose;
+pub mod assume;
+pub mod edit;
+pub mod step;
+pub mod observe;
+pub mod tree;
+pub mod stats;
+pub mod restart;
+pub mod sample;
+pub mod decompose;
+pub mod cbj;
+pub mod lookahead;
+pub mod sym;
+pub mod partial;
+pub mod bits;
+pub mod nodes;
+pub mod store;
+#[cfg(test)]
+mod testing;
+
+// Node indexes as the algorithms see them; the 32-bit feature narrows them
+// for the whole crate. The links of an x::Problem can be stored narrower
+// still, per problem, as chosen by x::Auto.
+#[cfg(not(feature = "32-bit"))]
+pub type Uint = u64;
+#[cfg(not(feature = "32-bit"))]
+pub type Int = i64;
+
+#[cfg(feature = "32-bit")]
+pub type Uint = u32;
+#[cfg(feature = "32-bit")]
+pub type Int = i32;
+
+#[allow(non_snake_case)]
+pub fn Uint<T: AsPrimitive<Uint>>(data: T) -> Uint {
+    data.as_()
+}
+
+#[allow(non_snake_case)]
+pub fn Int<T: AsPrimitive<Int>>(data: T) -> Int {
+    data.as_()
+}
+
+pub trait Dance {
+    type I: Items;
+    type O: Opts;
+
+    fn items(&mut self) -> &mut Self::I;
+    fn opts(&mut self) -> &mut Self::O;
+
+    #[inline]
+    fn llink(&mut self, i: Uint) -> &mut Uint {
+        self.items().llink(i)
+    }
+
+    #[inline]
+    fn rlink(&mut self, i: Uint) -> &mut Uint {
+        self.items().rlink(i)
+    }
+
+    #[inline]
+    fn dlink(&mut self, i: Uint) -> <Self::O as Opts>::Ref<'_> {
+        self.opts().dlink(i)
+    }
+
+    #[inline]
+    fn ulink(&mut self, i: Uint) -> <Self::O as Opts>::Ref<'_> {
+        self.opts().ulink(i)
+    }
+
+    #[inline]
+    fn top(&mut self, i: Uint) -> <Self::O as Opts>::SignedRef<'_> {
+        self.opts().top(i)
+    }
+
+    #[inline]
+    fn len(&mut self, i: Uint) -> <Self::O as Opts>::SignedRef<'_> {
+        self.opts().len(i)
+    }
+
+    #[inline]
+    fn node(&mut self, i: Uint) -> (Int, Uint, Uint) {
+        self.opts().node(i)
+    }
+
+    fn updates(&mut self) -> &mut isize;
+
+    #[inline]
+    fn mems(&mut self) -> u64 {
+        self.items().mems() + self.opts().mems()
+    }
+
+    fn cover(&mut self, i: Uint);
+    fn commit(&mut self, p: Uint, j: Uint);
+    fn uncover(&mut self, i: Uint);
+    fn uncommit(&mut self, p: Uint, j: Uint);
+    fn hide(&mut self, p: Uint);
+    fn unhide(&mut self, p: Uint);
+    fn branch_degree(&mut self, i: Uint) -> Int;
+
+    // How many times the option of node p counts in the length of each of
+    // its item lists, which is once for every use it has left. Only m and
+    // mc options can have more than one.
+    #[inline]
+    fn uses_left(&mut self, _p: Uint) -> Int {
+        1
+    }
+}
+
+#[allow(clippy::len_without_is_empty)]
+pub trait Items {
+    fn llink(&mut self, i: Uint) -> &mut Uint;
+    fn rlink(&mut self, i: Uint) -> &mut Uint;
+
+    fn primary(&self) -> Uint;
+    fn count(&self) -> Uint;
+    fn mems(&self) -> u64;
+
+    // Inserts k unlinked nodes before node i, and adds k to the count.
+    fn insert_nodes(&mut self, i: Uint, k: Uint);
+
+    fn init_links(&mut self, order: ItemOrder) {
+        let n1 = self.primary();
+        assert!(n1 > 0, "No primary items");
+        let n = self.count();
+        let mut order = order;
+        for (h, is) in [(0, 1..=n1), (n + 1, (n1 + 1)..=n)] {
+            *self.llink(h) = h;
+            *self.rlink(h) = h;
+            for (k, i) in is.enumerate() {
+                // Each item goes after the last one, or after a random one
+                // of the k items already in the list or the head
+                let q = match &mut order {
+                    ItemOrder::Seq => *self.llink(h),
+                    ItemOrder::Rnd(rng) => {
+                        let mut q = h;
+                        for _ in 0..rng.uniform(k as u32 + 1) {
+                            q = *self.rlink(q);
+                        }
+                        q
+                    }
+                };
+                let r = *self.rlink(q);
+                *self.llink(i) = q;
+                *self.rlink(i) = r;
+                *self.rlink(q) = i;
+                *self.llink(r) = i;
+            }
+        }
+    }
+
+    // Appends k secondary items, returning the (1-based) index of the
+    // first. The items after them are renumbered once for all k.
+    fn add_secondaries(&mut self, k: Uint) -> Uint {
+        let n = self.count();
+        self.insert_nodes(n + 1, k);
+        // The head of the secondary list moves from n + 1 to n + k + 1
+        let h = n + k + 1;
+        for i in (0..=n).chain([h]) {
+            if *self.llink(i) > n {
+                *self.llink(i) += k;
+            }
+            if *self.rlink(i) > n {
+                *self.rlink(i) += k;
+            }
+        }
+        for i in n + 1..h {
+            let l = *self.llink(h);
+            *self.llink(i) = l;
+            *self.rlink(i) = h;
+            *self.rlink(l) = i;
+            *self.llink(h) = i;
+        }
+        n + 1
+    }
+}
+
+// Counts node accesses when the "mems" feature is enabled.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Mems(u64);
+
+impl Mems {
+    #[inline]
+    pub fn add(&mut self) {
+        if cfg!(feature = "mems") {
+            self.0 += 1;
+        }
+    }
+
+    #[inline]
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+}
+
+pub trait OptData: Clone + Copy + Default {
+    fn new_item(i: Uint) -> Self;
+    fn get_item(&self) -> Uint;
+
+    fn has_color(&self) -> bool {
+        false
+    }
+}
+
+pub trait Opts {
+    type Data: OptData;
+    // Handles on the links of a node, which are plain references unless the
+    // links are stored narrower than Uint.
+    type Ref<'a>: DerefMut<Target = Uint>
+    where
+        Self: 'a;
+    type SignedRef<'a>: DerefMut<Target = Int>
+    where
+        Self: 'a;
+
+    fn len(&mut self, i: Uint) -> Self::SignedRef<'_>;
+    fn top(&mut self, i: Uint) -> Self::SignedRef<'_>;
+    fn ulink(&mut self, i: Uint) -> Self::Ref<'_>;
+    fn dlink(&mut self, i: Uint) -> Self::Ref<'_>;
+
+    // The top, up and down links of node i, for the loops that need all
+    // three to read them at once.
+    #[inline]
+    fn node(&mut self, i: Uint) -> (Int, Uint, Uint) {
+        let top = *self.top(i);
+        let up = *self.ulink(i);
+        (top, up, *self.dlink(i))
+    }
+
+    fn set_data(&mut self, pk: Uint, s: Self::Data) -> Uint;
+    fn get_data_item(s: Self::Data) -> Uint;
+
+    fn size(&self) -> Uint;
+    // The most nodes the links can number.
+    fn max_size(&self) -> Uint {
+        Int::MAX as Uint
+    }
+    // Inserts k default nodes before node i.
+    fn insert_nodes(&mut self, i: Uint, k: Uint);
+    // The number of options, including those that were skipped.
+    fn opt_count(&mut self) -> &mut Int;
+    // The spacers in front of deleted options.
+    fn free(&mut self) -> &mut Vec<Uint>;
+    fn mems(&self) -> u64;
+
+    // Links options in compressed sparse row form, where option k has the
+    // nodes data[starts[k]..starts[k + 1]], into nodes that were sized by
+    // csr_size and already hold any data besides the items, as laid out by
+    // csr_colored.
+    fn link_csr(
+        &mut self, n: Uint, np: Uint, order: OptOrder, starts: &[usize],
+        data: &[Self::Data],
+    ) {
+        let mut order = order;
+        for i in (1 as Uint)..=n {
+            *self.ulink(i) = i;
+            *self.dlink(i) = i;
+        }
+        // The options are linked in order, so that they are in order in
+        // each list, into the part of the nodes where they are laid out
+        let mut p = [n + 1, csr_colored(n, np, starts, data) as Uint];
+        for (m, w) in starts.windows(2).enumerate() {
+            // Options without primary items are skipped but the option
+            // number is still incremented so that the options match their
+            // position in the input data.
+            let opt = &data[w[0]..w[1]];
+            if opt.iter().any(|node| node.get_item() < np) {
+                let k = opt.iter().any(OptData::has_color) as usize;
+                p[k] = self.link_nodes(p[k], m as Int + 1, opt, &mut order);
+            }
+        }
+        let m = starts.len().saturating_sub(1) as Int;
+        assert!(m > 0, "No options");
+        *self.opt_count() = m;
+    }
+
+    // Links option m into the item lists, with its nodes following the
+    // spacer at p. Returns the position of the spacer after the option, or p
+    // if the option has no primary items.
+    fn link_option(
+        &mut self, p: Uint, np: Uint, m: Int, opt: &[Self::Data],
+        order: &mut OptOrder,
+    ) -> Uint {
+        if !opt.iter().any(|node| Self::get_data_item(*node) < np) {
+            return p;
+        }
+        let k = opt.len() as Uint;
+        for (q, node) in (p + 1..).zip(opt) {
+            self.set_data(q, *node);
+        }
+        self.set_data(p + k + 1, Default::default());
+        self.link_nodes(p, m, opt, order)
+    }
+
+    // Links the nodes of option m, which follow the spacer at p and hold its
+    // data already, into the lists of its items. Returns the position of the
+    // spacer after the option.
+    fn link_nodes(
+        &mut self, p: Uint, m: Int, opt: &[Self::Data], order: &mut OptOrder,
+    ) -> Uint {
+        let mut k = 0;
+        for node in opt {
+            k += 1;
+            // Internal item indexes are 1-based.
+            let i = Self::get_data_item(*node) + 1;
+            *self.len(i) += 1;
+            let q = match order {
+                OptOrder::Seq => *self.ulink(i),
+                OptOrder::Rnd(rng) => {
+                    let mut i = i;
+                    let p = rng.uniform(*self.len(i) as u32);
+                    for _ in 0..p {
+                        i = *self.dlink(i);
+                    }
+                    i
+                }
+            };
+            let qd = *self.dlink(q);
+            *self.ulink(p + k) = q;
+            *self.dlink(p + k) = qd;
+            *self.dlink(q) = p + k;
+            *self.ulink(qd) = p + k;
+            *self.top(p + k) = i as Int;
+        }
+        *self.dlink(p) = p + k;
+        // add spacer
+        let p = p + k + 1;
+        *self.top(p) = -m;
+        *self.ulink(p) = p - k;
+        p
+    }
+
+    // Makes room for k new item headers after the n existing ones, moving
+    // the option nodes and renumbering their links once for all k.
+    fn add_items(&mut self, n: Uint, k: Uint) {
+        self.insert_nodes(n + 1, k);
+        for q in (0..=n).chain(n + k + 1..self.size()) {
+            if *self.ulink(q) > n {
+                *self.ulink(q) += k;
+            }
+            if *self.dlink(q) > n {
+                *self.dlink(q) += k;
+            }
+        }
+        for s in self.free().iter_mut() {
+            *s += k;
+        }
+        for i in n + 1..=n + k {
+            *self.ulink(i) = i;
+            *self.dlink(i) = i;
+        }
+    }
+}
+
+// Finds the first node of each option, or 0 for options that have been
+// skipped or deleted.
+pub(crate) fn opt_starts<D: Dance + ?Sized>(dance: &mut D) -> Vec<Uint> {
+    let mut starts = Vec::new();
+    let mut s = dance.items().count() + 1;
+    loop {
+        // Each spacer links down to the last node of the following option
+        let last = *dance.dlink(s);
+        if last <= s {
+            break;
+        }
+        let m = -*dance.top(last + 1) as usize;
+        if m > 0 {
+            starts.resize(starts.len().max(m), 0);
+            starts[m - 1] = s + 1;
+        }
+        s = last + 1;
+    }
+    starts
+}
+
+// The number of option nodes for options in compressed sparse row form:
+// the item headers and the first spacer, then the nodes of each option
+// with a primary item and the spacer after them.
+pub(crate) fn csr_size<D: OptData>(
+    n: Uint, np: Uint, starts: &[usize], data: &[D],
+) -> usize {
+    let opts = starts.windows(2).map(|w| &data[w[0]..w[1]]);
+    let kept = opts.filter(|opt| opt.iter().any(|node| node.get_item() < np));
+    (n + 2) as usize + kept.map(|opt| opt.len() + 1).sum::<usize>()
+}
+
+// The spacer in front of the first option with colors, for options in
+// compressed sparse row form. Options with colors are laid out after all
+// those without, so that only their nodes need to store colors.
+pub(crate) fn csr_colored<D: OptData>(
+    n: Uint, np: Uint, starts: &[usize], data: &[D],
+) -> usize {
+    let opts = starts.windows(2).map(|w| &data[w[0]..w[1]]);
+    let kept = opts.filter(|opt| opt.iter().any(|node| node.get_item() < np));
+    let plain = kept.filter(|opt| !opt.iter().any(OptData::has_color));
+    (n + 1) as usize + plain.map(|opt| opt.len() + 1).sum::<usize>()
+}
+
+// Flattens options into compressed sparse row form, with room made up
+// front for entries item uses in all.
+pub(crate) fn to_csr<D: OptData, T: AsRef<[D]>>(
+    os: impl IntoIterator<Item = T>, entries: usize,
+) -> (Vec<usize>, Vec<D>) {
+    let os = os.into_iter();
+    let mut starts = Vec::with_capacity(os.size_hint().0 + 1);
+    starts.push(0);
+    let mut data = Vec::with_capacity(entries);
+    for opt in os {
+        data.extend_from_slice(opt.as_ref());
+        starts.push(data.len());
+    }
+    (starts, data)
+}
+
+pub trait Solve: Dance {
+    fn enter_level(&mut self, i: Uint, l: Uint, xl: Uint);
+    fn prepare_to_branch(&mut self, i: Uint, l: Uint, xl: Uint);
+    fn try_item(&mut self, i: Uint, l: Uint, xl: Uint) -> bool;
+    fn try_again(&mut self, i: Uint, l: Uint, xl: &mut Uint) -> bool;
+    fn undo_item(&mut self, i: Uint, l: Uint, xl: Uint);
+    fn restore_item(&mut self, i: Uint, l: Uint, xl: Uint);
+
+    // Probes the options of each item chosen on levels below max_level,
+    // discarding those that fail within depth levels. A depth of 0 turns
+    // probing off, which is all that problem types without probing accept.
+    fn set_lookahead(&mut self, depth: usize, _max_level: Uint) -> Result<()> {
+        if depth != 0 {
+            bail!("Lookahead is not supported by this problem type");
+        }
+        Ok(())
+    }
+}
+
+pub struct Solver<'a, P, O = NoObserver> {
+    problem: &'a mut P,
+    observer: O,
+    x: Vec<Uint>,
+    o: Vec<Int>,
+    profile: Vec<usize>,
+    l: Uint,
+    i: Uint,
+    restart: bool,
+    // Mems at the start of the run and at the last solution
+    mems: (u64, u64),
+    solution_mems: u64,
+    nodes: usize,
+    node_limit: usize,
+    limit_reached: bool,
+    backjump: Option<Backjump>,
+}
+
+impl<'a, P: Solve> Solver<'a, P> {
+    pub fn new(problem: &'a mut P) -> Solver<'a, P> {
+        Solver::with_observer(problem, NoObserver)
+    }
+}
+
+impl<'a, P: Solve, O: Observer> Solver<'a, P, O> {
+    pub fn with_observer(problem: &'a mut P, observer: O) -> Solver<'a, P, O> {
+        let mems = problem.mems();
+        Solver {
+            problem,
+            observer,
+            x: Vec::new(),
+            o: Vec::new(),
+            profile: Vec::new(),
+            l: 0,
+            i: 0,
+            restart: false,
+            mems: (mems, mems),
+            solution_mems: 0,
+            nodes: 0,
+            node_limit: usize::MAX,
+            limit_reached: false,
+            backjump: None,
+        }
+    }
+
+    pub fn next_solution<C: Choose<P>>(&mut self, chooser: &mut C) -> bool {
+        let mut l = self.l;
+        let mut i = self.i;
+        if *self.problem.updates() < 0 {
+            *self.problem.updates() = 0;
+        }
+        self.limit_reached = false;
+
+        loop {
+            if self.restart {
+                self.restart = false;
+                if let Some(bj) = &mut self.backjump {
+                    bj.solution(l as usize);
+                }
+            } else if *self.problem.items().rlink(0) == 0 {
+                self.l = l;
+                self.i = i;
+                self.restart = true;
+                let mems = self.problem.mems();
+                self.solution_mems = mems - self.mems.1;
+                self.mems.1 = mems;
+                self.observer.solution(l, *self.problem.updates());
+                return true;
+            } else {
+                if self.nodes >= self.node_limit {
+                    // Stop before entering the node, so the search can
+                    // resume from it or be abandoned with reset
+                    self.l = l;
+                    self.i = i;
+                    self.limit_reached = true;
+                    return false;
+                }
+                self.nodes += 1;
+                if self.x.len() == l as usize {
+                    self.x.push(0);
+                    self.profile.push(0);
+                    self.problem.enter_level(i, l, self.x[l as usize]);
+                }
+                self.profile[l as usize] += 1;
+                self.observer.enter_level(l, *self.problem.updates());
+                i = chooser.choose(self.problem);
+                // TODO: return option from choose
+                if let Some(bj) = &mut self.backjump {
+                    bj.choose(l as usize, i);
+                }
+                let mut degree = self.problem.branch_degree(i);
+                let prepared = degree != 0;
+                if prepared {
+                    let len = *self.problem.len(i);
+                    self.x[l as usize] = *self.problem.opts().dlink(i);
+                    self.problem.prepare_to_branch(i, l, self.x[l as usize]);
+                    // Probing may have removed options, the first among them
+                    self.x[l as usize] = *self.problem.opts().dlink(i);
+                    let pruned = len - *self.problem.len(i);
+                    if let Some(bj) = &mut self.backjump
+                        && pruned > 0
+                    {
+                        bj.blame_all(l as usize);
+                    }
+                    degree -= pruned;
+                }
+                let updates = *self.problem.updates();
+                self.observer.choose(l, i, degree, updates);
+                if prepared {
+                    if self.problem.try_item(i, l, self.x[l as usize]) {
+                        if self.admit(i, l) {
+                            let updates = *self.problem.updates();
+                            self.observer.try_option(
+                                l,
+                                self.x[l as usize],
+                                updates,
+                            );
+                            l += 1;
+                            continue;
+                        }
+                    } else {
+                        self.problem.restore_item(i, l, self.x[l as usize]);
+                    }
+                }
+                l = self.jump(l);
+            }
+            loop {
+                if l == 0 {
+                    self.l = l;
+                    *self.problem.updates() = -*self.problem.updates();
+                    return false;
+                }
+                l -= 1;
+                self.observer.backtrack(l, *self.problem.updates());
+                i = *self.problem.opts().top(self.x[l as usize]) as Uint;
+                if let Some(bj) = &mut self.backjump {
+                    bj.deselect(l as usize, self.x[l as usize]);
+                }
+                if self.problem.try_again(i, l, &mut self.x[l as usize])
+                    && self.admit(i, l)
+                {
+                    let updates = *self.problem.updates();
+                    self.observer.try_option(l, self.x[l as usize], updates);
+                    l += 1;
+                    break;
+                }
+                l = self.jump(l);
+            }
+        }
+    }
+
+    // With backjumping, passes over the options of level l that complete a
+    // nogood. Returns false, with the level restored, if none are left.
+    fn admit(&mut self, i: Uint, l: Uint) -> bool {
+        let Some(bj) = &mut self.backjump else {
+            return true;
+        };
+        let xl = &mut self.x[l as usize];
+        while bj.blocked(l as usize, *xl) {
+            if !self.problem.try_again(i, l, xl) {
+                return false;
+            }
+        }
+        bj.select(l as usize, *xl);
+        true
+    }
+
+    // Level f has run out of options. With backjumping, the levels above it
+    // that had no part in the failure are undone, and the level to resume
+    // from plus one is returned, as the backtracking loop expects.
+    fn jump(&mut self, f: Uint) -> Uint {
+        let Some(bj) = &mut self.backjump else {
+            return f;
+        };
+        let to = bj.fail(f as usize, &self.x).map_or(0, |h| h as Uint + 1);
+        for l in (to..f).rev() {
+            let xl = self.x[l as usize];
+            let i = *self.problem.opts().top(xl) as Uint;
+            bj.deselect(l as usize, xl);
+            self.problem.undo_item(i, l, xl);
+            self.problem.restore_item(i, l, xl);
+        }
+        to
+    }
+
+    pub fn fmt_solution(&mut self) -> &[Int] {
+        let n = self.problem.items().count();
+        self.o.clear();
+        for xj in &self.x[..self.l as usize] {
+            let mut r = *xj;
+            if r <= n {
+                // TODO: report these elemente
+                continue;
+            }
+            while *self.problem.opts().top(r) >= 0 {
+                r += 1;
+            }
+            // Internal option indexes are 1-based
+            self.o.push(-*self.problem.opts().top(r) - 1);
+        }
+        &self.o
+    }
+
+    // Backtracks out of the current partial solution, leaving the problem
+    // as it was before the search started.
+    pub fn reset(&mut self) {
+        let n = self.problem.items().count();
+        while self.l > 0 {
+            self.l -= 1;
+            let l = self.l;
+            let xl = self.x[l as usize];
+            let i = if xl <= n {
+                xl
+            } else {
+                *self.problem.opts().top(xl) as Uint
+            };
+            if let Some(bj) = &mut self.backjump {
+                bj.deselect(l as usize, xl);
+            }
+            self.problem.undo_item(i, l, xl);
+            self.problem.restore_item(i, l, xl);
+        }
+        self.i = 0;
+        self.restart = false;
+    }
+
+    pub fn get_updates(&mut self) -> isize {
+        self.problem.updates().abs()
+    }
+
+    // Mems are only counted with the "mems" feature.
+    pub fn get_mems(&mut self) -> u64 {
+        self.problem.mems() - self.mems.0
+    }
+
+    // The mems used since the previous solution, up to the last one found.
+    pub fn get_solution_mems(&self) -> u64 {
+        self.solution_mems
+    }
+
+    // Limits the total number of nodes visited; next_solution returns false
+    // without finishing the search once the limit is reached.
+    pub fn set_node_limit(&mut self, limit: usize) {
+        self.node_limit = limit;
+    }
+
+    pub fn limit_reached(&self) -> bool {
+        self.limit_reached
+    }
+
+    pub fn get_nodes(&self) -> usize {
+        self.nodes
+    }
+
+    pub fn get_profile(&self) -> &[usize] {
+        &self.profile
+    }
+
+    pub fn observer(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    // Ends the search without restoring the problem.
+    pub fn into_observer(self) -> O {
+        self.observer
+    }
+
+    pub fn backjump(&self) -> Option<&Backjump> {
+        self.backjump.as_ref()
+    }
+}
+
+impl<P: Solve + ExactCover + Reduce, O: Observer> Solver<'_, P, O> {
+    // Jumps back past the levels that had no part in a failure, and learns
+    // nogoods of up to max_nogood options. Set before the search starts.
+    pub fn set_backjumping(&mut self, max_nogood: usize) {
+        assert_eq!(self.l, 0, "search already started");
+        self.backjump = Some(Backjump::new(self.problem, max_nogood));
+    }
+}
+
+pub struct Spec {
+    pub primary: Vec<String>,
+    pub secondary: Vec<String>,
+    pub opts: Vec<Vec<String>>,
+    pub sharp_pref: bool,
+}
+
+impl Spec {
+    pub fn new(spec: &str, sharp_pref: bool) -> Result<Spec> {
+        use std::cmp::Ordering;
+        let mut lines = spec
+            .lines()
+            .map(str::trim)
+            .filter(|s| !s.is_empty() && !s.starts_with('|'));
+        let items =
+            lines.next().ok_or_else(|| anyhow!("No items specified"))?;
+        let opts: Vec<String> = lines.map(String::from).collect();
+        if opts.is_empty() {
+            bail!("No options specified");
+        }
+        let item_list = items
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let items = item_list.split(|e| e == "|").collect::<Vec<_>>();
+        if items.len() > 2 {
+            bail!("Too many '|' separators");
+        }
+        let secondary = if items.len() > 1 {
+            if items[1].is_empty() {
+                bail!("No seecondary items specified");
+            }
+            // TODO: no '#' in secondary?
+            items[1].to_vec()
+        } else {
+            Vec::new()
+        };
+        let mut primary = items[0].to_vec();
+        if primary.is_empty() {
+            bail!("No primary items");
+        }
+        primary.sort_by(|a, b| {
+            let a_sharp = a.contains("#");
+            let b_sharp = b.contains("#");
+            if a_sharp == b_sharp {
+                Ordering::Equal
+            } else if a_sharp == sharp_pref {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        });
+        let opts = opts
+            .iter()
+            .map(|o| o.split_whitespace().map(String::from).collect())
+            .collect();
+        Ok(Spec { primary, secondary, opts, sharp_pref })
+    }
+}
+
+pub struct Rng {
+    state: u32,
+}
+
+#[allow(clippy::should_implement_trait)]
+impl Rng {
+    pub fn new(state: u32) -> Rng {
+        assert_ne!(state, 0);
+        Rng { state }
+    }
+
+    pub fn next(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    pub fn uniform(&mut self, max: u32) -> u32 {
+        let t = 0x80000000 - (0x80000000 % max);
+        let mut r;
+        loop {
+            r = self.next();
+            if t > r {
+                break;
+            }
+        }
+        r % max
+    }
+}
+
+pub enum OptOrder {
+    Seq,
+    Rnd(Rng),
+}
+
+pub enum ItemOrder {
+    Seq,
+    Rnd(Rng),
+}
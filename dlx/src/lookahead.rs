@@ -0,0 +1,192 @@
+use crate::step::{deselect, select};
+use crate::{Dance, Int, Uint};
+
+// Settings and state for probing the options of each chosen item before
+// branching on it. An option is pruned if selecting it leaves some primary
+// item without options, or, with a depth above 1, if the search below it
+// fails within depth - 1 more levels. Probing is only done on levels below
+// max_level, where the trees are widest.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Lookahead {
+    depth: usize,
+    max_level: Uint,
+    // The nodes pruned on each level that probed, deepest last
+    pruned: Vec<(Uint, Vec<Uint>)>,
+}
+
+impl Lookahead {
+    pub fn new(depth: usize, max_level: Uint) -> Lookahead {
+        Lookahead { depth, max_level, pruned: Vec::new() }
+    }
+
+    pub(crate) fn applies(&self, l: Uint) -> bool {
+        self.depth > 0 && l < self.max_level
+    }
+
+    pub(crate) fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub(crate) fn push(&mut self, l: Uint, pruned: Vec<Uint>) {
+        self.pruned.push((l, pruned));
+    }
+
+    // The nodes pruned on level l, if it probed.
+    pub(crate) fn pop(&mut self, l: Uint) -> Option<Vec<Uint>> {
+        match self.pruned.last() {
+            Some((k, _)) if *k == l => self.pruned.pop().map(|(_, p)| p),
+            _ => None,
+        }
+    }
+}
+
+// Whether the problem might still be solvable after searching depth more
+// levels: every primary item has an option, and some option of the item
+// with the fewest passes the next level.
+fn consistent<D: Dance>(dance: &mut D, depth: usize) -> bool {
+    let mut min = Int::MAX;
+    let mut i = 0;
+    let mut p = *dance.rlink(0);
+    while p != 0 {
+        let len = *dance.len(p);
+        if len == 0 {
+            return false;
+        }
+        if len < min {
+            min = len;
+            i = p;
+        }
+        p = *dance.rlink(p);
+    }
+    if depth == 0 || i == 0 {
+        return true;
+    }
+    let mut q = *dance.dlink(i);
+    while q != i {
+        select(dance, q);
+        let ok = consistent(dance, depth - 1);
+        deselect(dance, q);
+        if ok {
+            return true;
+        }
+        q = *dance.dlink(q);
+    }
+    false
+}
+
+// Removes the options of primary item i that fail the probe, returning
+// their nodes in the order they were removed.
+pub(crate) fn prune<D: Dance>(
+    dance: &mut D, i: Uint, depth: usize,
+) -> Vec<Uint> {
+    let mut nodes = Vec::new();
+    let mut p = *dance.dlink(i);
+    while p != i {
+        nodes.push(p);
+        p = *dance.dlink(p);
+    }
+    let mut pruned = Vec::new();
+    for p in nodes {
+        select(dance, p);
+        let ok = consistent(dance, depth - 1);
+        deselect(dance, p);
+        if !ok {
+            dance.hide(p);
+            let u = *dance.ulink(p);
+            let d = *dance.dlink(p);
+            *dance.dlink(u) = d;
+            *dance.ulink(d) = u;
+            *dance.len(i) -= 1;
+            pruned.push(p);
+        }
+    }
+    pruned
+}
+
+pub(crate) fn unprune<D: Dance>(dance: &mut D, i: Uint, pruned: &[Uint]) {
+    for &p in pruned.iter().rev() {
+        let u = *dance.ulink(p);
+        let d = *dance.dlink(p);
+        *dance.dlink(u) = p;
+        *dance.ulink(d) = p;
+        *dance.len(i) += 1;
+        dance.unhide(p);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{
+        DEAD_ENDS_SPEC, assert_restored, langford_spec, search,
+    };
+    use crate::{ItemOrder, OptOrder, Solve, Spec, c, m, mc, x};
+
+    #[test]
+    fn test_probe() {
+        let spec = langford_spec(8);
+        let build = || {
+            x::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap()
+        };
+        let mut problem = build();
+        let (expected, plain) = search(&mut problem);
+        assert_eq!(expected.len(), 300);
+        for depth in 1..=2 {
+            let mut problem = build();
+            problem.set_lookahead(depth, 100).unwrap();
+            let (solutions, nodes) = search(&mut problem);
+            assert_eq!(solutions, expected);
+            assert!(nodes < plain);
+            assert_restored(&mut problem, &build());
+        }
+    }
+
+    #[test]
+    fn test_max_level() {
+        let spec = langford_spec(7);
+        let build = || {
+            x::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap()
+        };
+        let mut counts = Vec::new();
+        for max_level in [0, 1, 3, 100] {
+            let mut problem = build();
+            problem.set_lookahead(1, max_level).unwrap();
+            let (solutions, nodes) = search(&mut problem);
+            assert_eq!(solutions.len(), 52);
+            counts.push(nodes);
+        }
+        assert!(counts.windows(2).all(|w| w[0] >= w[1]), "{:?}", counts);
+        assert!(counts[0] > counts[3]);
+    }
+
+    #[test]
+    fn test_colors() {
+        let spec = Spec::new(DEAD_ENDS_SPEC, false).unwrap();
+        let build = || {
+            c::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap()
+        };
+        let (expected, _) = search(&mut build());
+        let mut problem = build();
+        problem.set_lookahead(2, 10).unwrap();
+        let (solutions, _) = search(&mut problem);
+        assert_eq!(solutions, expected);
+        assert_restored(&mut problem, &build());
+    }
+
+    #[test]
+    fn test_multiplicities() {
+        let spec = Spec::new("1:2|a b\na b\na\nb\n", false).unwrap();
+        let mut problem =
+            m::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        let e = problem.set_lookahead(1, 10).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Lookahead needs a problem without multiplicities"
+        );
+        assert!(problem.set_lookahead(0, 10).is_ok());
+        let mut problem =
+            mc::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        assert!(problem.set_lookahead(1, 10).is_err());
+    }
+}
@@ -1,491 +1,1089 @@
-use anyhow::{Result, bail};
-
-use crate::x;
-use crate::{Dance, Int, Items, OptOrder, Solve, Spec, Uint};
-
-pub fn tweak<D: DanceM<I: ItemsM>>(x: Uint, p: Uint, dance: &mut D) {
-    if *dance.bound(p) != 0 {
-        dance.hide(x);
-    }
-    let d = *dance.dlink(x);
-    *dance.dlink(p) = d;
-    *dance.ulink(d) = p;
-    *dance.len(p) -= 1;
-}
-
-pub fn untweak<D: DanceM<I: ItemsM>>(l: Uint, unblock: bool, dance: &mut D) {
-    let ftl = dance.ft()[l as usize];
-    let p = if ftl <= dance.items().count() {
-        ftl
-    } else {
-        *dance.top(ftl) as Uint
-    };
-    let mut x = ftl;
-    let mut y = p;
-    let z = *dance.dlink(p);
-    *dance.dlink(p) = x;
-    let mut k = 0;
-    while x != z {
-        *dance.ulink(x) = y;
-        k += 1;
-        if unblock {
-            dance.unhide(x);
-        }
-        y = x;
-        x = *dance.dlink(x);
-    }
-    *dance.ulink(z) = y;
-    *dance.len(p) += k;
-    if !unblock {
-        dance.uncover(p);
-    }
-}
-
-pub fn branch_degree<D: DanceM<I: ItemsM>>(i: Uint, dance: &mut D) -> Int {
-    (*dance.len(i) + 1)
-        .saturating_sub((*dance.bound(i)).saturating_sub(dance.slack(i)))
-}
-
-pub fn enter_level<S: SolveM>(solve: &mut S, _: Uint, _: Uint, _: Uint) {
-    solve.ft().push(0);
-}
-
-pub fn prepare_to_branch<S: SolveM>(solve: &mut S, i: Uint, l: Uint, xl: Uint) {
-    *solve.bound(i) -= 1;
-    if *solve.bound(i) == 0 {
-        solve.cover(i);
-        if solve.slack(i) != 0 {
-            solve.ft()[l as usize] = xl;
-        }
-    } else {
-        solve.ft()[l as usize] = xl;
-    }
-}
-
-pub fn try_item<S: SolveM>(solve: &mut S, i: Uint, _: Uint, xl: Uint) -> bool {
-    if solve.slack(i) == 0 && *solve.bound(i) == 0 {
-        if xl == i {
-            return false;
-            // go to M8
-        }
-        // go to M6
-    } else if *solve.len(i) <= (*solve.bound(i) - solve.slack(i)) {
-        return false;
-        // go to M8
-    } else if xl != i {
-        solve.tweak(xl, i);
-    } else if *solve.bound(i) != 0 {
-        let p = *solve.llink(i);
-        let q = *solve.rlink(i);
-        *solve.rlink(p) = q;
-        *solve.llink(q) = p;
-    }
-    // M6
-    if xl != i {
-        let mut p = xl + 1;
-        while p != xl {
-            let j = *solve.top(p);
-            if j <= 0 {
-                p = *solve.ulink(p);
-            } else if j as Uint <= solve.items().primary() {
-                p += 1;
-                *solve.bound(j as Uint) -= 1;
-                if *solve.bound(j as Uint) == 0 {
-                    solve.cover(j as Uint);
-                }
-            } else {
-                solve.commit(p, j as Uint);
-                p += 1;
-            }
-        }
-    }
-    true
-}
-
-pub fn try_again<S: SolveM>(
-    solve: &mut S, i: Uint, l: Uint, xl: &mut Uint,
-) -> bool {
-    let mut i = i;
-    let again = if *xl > solve.items().count() {
-        let mut p = *xl - 1;
-        while p != *xl {
-            let j = *solve.top(p);
-            if j <= 0 {
-                p = *solve.dlink(p);
-            } else if (j as Uint) <= solve.items().primary() {
-                p -= 1;
-                *solve.bound(j as Uint) += 1;
-                if *solve.bound(j as Uint) == 1 {
-                    solve.uncover(j as Uint);
-                }
-            } else {
-                solve.uncommit(p, j as Uint);
-                p -= 1;
-            }
-        }
-        *xl = *solve.dlink(*xl);
-        solve.try_item(i, l, *xl)
-    } else {
-        i = *xl;
-        let p = *solve.llink(i);
-        let q = *solve.rlink(i);
-        *solve.rlink(p) = i;
-        *solve.llink(q) = i;
-        false
-    };
-    if !again {
-        solve.restore_item(i, l, *xl);
-    }
-    again
-}
-
-pub fn restore_item<S: SolveM>(solve: &mut S, i: Uint, l: Uint, _: Uint) {
-    if *solve.bound(i) == 0 && solve.slack(i) == 0 {
-        solve.uncover(i);
-    } else {
-        let unblock = *solve.bound(i) != 0;
-        solve.untweak(l, unblock);
-    }
-    *solve.bound(i) += 1;
-}
-
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
-pub struct INode {
-    left: Uint,
-    right: Uint,
-    slack: Int,
-    bound: Int,
-}
-
-#[derive(Clone, Default, Debug, Eq, PartialEq)]
-pub struct INodes {
-    nodes: Vec<INode>,
-    primary: Uint,
-    len: Uint,
-}
-
-impl INodes {
-    pub fn new(ps: impl IntoIterator<Item = (Int, Int)>, ns: Uint) -> INodes {
-        let mut nodes = vec![Default::default()];
-        for (u, v) in ps.into_iter() {
-            nodes.push(INode { bound: v, slack: v - u, ..Default::default() });
-        }
-        let primary = (nodes.len() - 1) as Uint;
-        for _ in 0..=ns {
-            nodes.push(Default::default());
-        }
-        let mut inodes = INodes { nodes, primary, len: primary + ns };
-        inodes.init_links();
-        inodes
-    }
-
-    pub fn from_spec(spec: &Spec) -> Result<(INodes, Vec<String>)> {
-        use std::collections::HashSet;
-        let mut names: Vec<String> = Vec::new();
-        let mut ps = Vec::new();
-        for item in &spec.primary {
-            let (name, u, v) = if item.contains('|') {
-                let data = item.split('|').collect::<Vec<_>>();
-                if data.len() > 2 {
-                    bail!("Too many '|' (multiplicity) separators");
-                }
-                let name = data[1];
-                let data = data[0];
-                if data.contains(':') {
-                    let data = data.split(':').collect::<Vec<_>>();
-                    if data.len() > 2 {
-                        bail!("Too many ':' (multiplicity) separators");
-                    }
-                    (name, data[0], data[1])
-                } else {
-                    (name, data, data)
-                }
-            } else {
-                (item.as_str(), "1", "1")
-            };
-            names.push(name.into());
-            let u: Int = u.parse().or_else(|_| bail!("non-numeric bound"))?;
-            let v: Int = v.parse().or_else(|_| bail!("non-numeric bound"))?;
-            ps.push((u, v));
-        }
-        for item in &spec.secondary {
-            names.push(item.into());
-        }
-        let mut used = HashSet::new();
-        let unique = names.iter().all(|e| used.insert(e));
-        if !unique {
-            bail!("Duplicate item names");
-        }
-        for name in &names {
-            if !name.chars().all(|c| c.is_alphanumeric() || c == '#') {
-                bail!("Invalid item name");
-            }
-        }
-        let ns = spec.secondary.len() as Uint;
-        Ok((INodes::new(ps, ns), names))
-    }
-
-    #[inline]
-    fn get_node(&mut self, i: Uint) -> &mut INode {
-        if cfg!(feature = "unsafe-fast-index") {
-            unsafe { self.nodes.get_unchecked_mut(i as usize) }
-        } else {
-            &mut self.nodes[i as usize]
-        }
-    }
-}
-
-#[derive(Debug, Eq, PartialEq)]
-pub struct Problem {
-    items: INodes,
-    opts: x::ONodes,
-    ft: Vec<Uint>,
-    updates: isize,
-}
-
-impl Problem {
-    pub fn new(items: INodes, opts: x::ONodes) -> Problem {
-        Problem { items, opts, ft: Vec::new(), updates: 0 }
-    }
-
-    pub fn from_spec(spec: &Spec, order: OptOrder) -> Result<Problem> {
-        let (items, names) = INodes::from_spec(spec)?;
-        let opts = x::ONodes::from_spec(spec, &names, order)?;
-        Ok(Problem::new(items, opts))
-    }
-}
-
-impl Items for INodes {
-    #[inline]
-    fn llink(&mut self, i: Uint) -> &mut Uint {
-        &mut self.get_node(i).left
-    }
-
-    #[inline]
-    fn rlink(&mut self, i: Uint) -> &mut Uint {
-        &mut self.get_node(i).right
-    }
-
-    #[inline]
-    fn primary(&self) -> Uint {
-        self.primary
-    }
-
-    #[inline]
-    fn count(&self) -> Uint {
-        self.len
-    }
-}
-
-pub trait ItemsM: Items {
-    fn bound(&mut self, i: Uint) -> &mut Int;
-    fn slack(&mut self, i: Uint) -> Int;
-}
-
-impl ItemsM for INodes {
-    #[inline]
-    fn bound(&mut self, i: Uint) -> &mut Int {
-        &mut self.get_node(i).bound
-    }
-
-    #[inline]
-    fn slack(&mut self, i: Uint) -> Int {
-        self.get_node(i).slack
-    }
-}
-
-impl Dance for Problem {
-    type I = INodes;
-    type O = x::ONodes;
-
-    #[inline]
-    fn items(&mut self) -> &mut Self::I {
-        &mut self.items
-    }
-
-    #[inline]
-    fn opts(&mut self) -> &mut Self::O {
-        &mut self.opts
-    }
-
-    #[inline]
-    fn updates(&mut self) -> &mut isize {
-        &mut self.updates
-    }
-
-    #[inline]
-    fn cover(&mut self, i: Uint) {
-        x::cover(i, self);
-    }
-
-    #[inline]
-    fn commit(&mut self, p: Uint, j: Uint) {
-        x::commit(p, j, self);
-    }
-
-    #[inline]
-    fn uncover(&mut self, i: Uint) {
-        x::uncover(i, self);
-    }
-
-    #[inline]
-    fn uncommit(&mut self, p: Uint, j: Uint) {
-        x::uncommit(p, j, self);
-    }
-
-    #[inline]
-    fn hide(&mut self, p: Uint) {
-        x::hide(p, self);
-    }
-
-    #[inline]
-    fn unhide(&mut self, p: Uint) {
-        x::unhide(p, self);
-    }
-
-    #[inline]
-    fn branch_degree(&mut self, i: Uint) -> Int {
-        branch_degree(i, self)
-    }
-}
-
-pub trait DanceM: Dance<I: ItemsM> {
-    fn tweak(&mut self, x: Uint, p: Uint);
-    fn untweak(&mut self, l: Uint, unblock: bool);
-    fn ft(&mut self) -> &mut Vec<Uint>;
-
-    #[inline]
-    fn bound(&mut self, i: Uint) -> &mut Int {
-        self.items().bound(i)
-    }
-
-    #[inline]
-    fn slack(&mut self, i: Uint) -> Int {
-        self.items().slack(i)
-    }
-}
-
-impl DanceM for Problem {
-    #[inline]
-    fn tweak(&mut self, x: Uint, p: Uint) {
-        tweak(x, p, self);
-    }
-
-    #[inline]
-    fn untweak(&mut self, l: Uint, unblock: bool) {
-        untweak(l, unblock, self);
-    }
-
-    #[inline]
-    fn ft(&mut self) -> &mut Vec<Uint> {
-        &mut self.ft
-    }
-}
-
-impl Solve for Problem {
-    #[inline]
-    fn enter_level(&mut self, i: Uint, l: Uint, xl: Uint) {
-        enter_level(self, i, l, xl);
-    }
-
-    #[inline]
-    fn prepare_to_branch(&mut self, i: Uint, l: Uint, xl: Uint) {
-        prepare_to_branch(self, i, l, xl);
-    }
-
-    #[inline]
-    fn try_item(&mut self, i: Uint, l: Uint, xl: Uint) -> bool {
-        try_item(self, i, l, xl)
-    }
-
-    #[inline]
-    fn try_again(&mut self, i: Uint, l: Uint, xl: &mut Uint) -> bool {
-        try_again(self, i, l, xl)
-    }
-
-    #[inline]
-    fn restore_item(&mut self, i: Uint, l: Uint, xl: Uint) {
-        restore_item(self, i, l, xl);
-    }
-}
-
-pub trait SolveM: Solve + DanceM<I: ItemsM> {}
-
-impl SolveM for Problem {}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_from_spec() {
-        let spec_str = "
-A B 2:3|C | X Y
-A B X Y
-A C X Y
-C X
-B X
-C Y
-";
-        let spec = Spec::new(spec_str, false).unwrap();
-        let problem = Problem::from_spec(&spec, OptOrder::Seq).unwrap();
-        let ps = vec![(1, 1), (1, 1), (2, 3)];
-        let items = INodes::new(ps, 2);
-        assert_eq!(problem.items, items);
-    }
-
-    #[test]
-    fn test_mc() {
-        use crate::choose::*;
-        use crate::{OptOrder, Rng, Solver};
-        use core::iter::repeat_n;
-        let ps = repeat_n((1, 1), 8)
-            .chain(repeat_n((2, 2), 4))
-            .chain(repeat_n((0, 2), 12));
-        let items = INodes::new(ps, 0);
-
-        let mut os: Vec<Vec<Uint>> = Vec::new();
-        for i in 0..2 {
-            for j in 0..2 {
-                os.push(vec![i, 8 + j, 12 + i + 1 - j, 15 + i + j]);
-                os.push(vec![10 + i, 2 + j, 12 + i + 1 - j, 18 + i + j]);
-                os.push(vec![4 + i, 8 + j, 21 + i + 1 - j, 18 + i + j]);
-                os.push(vec![10 + i, 6 + j, 21 + i + 1 - j, 15 + i + j]);
-            }
-        }
-        let opts =
-            x::ONodes::new(24, 24, &os, OptOrder::Rnd(Rng::new(12345678)));
-
-        let items_init = items.clone();
-        let opts_init = opts.clone();
-        let mut problem = Problem::new(items, opts);
-        let mut solver = Solver::new(&mut problem);
-        let mut solutions: Vec<Vec<Int>> = Vec::new();
-        let mut expected = vec![
-            vec![0, 1, 5, 6, 8, 11, 14, 15],
-            vec![0, 2, 5, 7, 9, 11, 12, 14],
-            vec![0, 3, 6, 7, 8, 9, 13, 14],
-            vec![1, 2, 4, 5, 10, 11, 12, 15],
-            vec![1, 3, 4, 6, 8, 10, 13, 15],
-            vec![2, 3, 4, 7, 9, 10, 12, 13],
-        ];
-        let mut i = 0;
-        let mut chooser = mrv_chooser(prefer_any(), knuth_tiebreak());
-        while solver.next_solution(&mut chooser) {
-            assert!(i <= expected.len(), "too many solutions");
-            solver.fmt_solution();
-            solver.o.sort();
-            solutions.push(solver.o.clone());
-            i += 1;
-        }
-        solutions.sort();
-        expected.sort();
-        assert_eq!(solutions, expected, "wrong solutions");
-        assert_eq!(solver.problem.items, items_init, "items not backtracked");
-        assert_eq!(solver.problem.opts, opts_init, "options not backtracked");
-        assert!(
-            solver.l == 0 && solver.restart == false,
-            "initial state not restored"
-        );
-    }
-}
+use anyhow::{Result, bail};
+
+use crate::Uint;
+use crate::decompose::item_name;
+use crate::nodes::{Nodes, check_links};
+use crate::store::Store;
+use crate::x;
+use crate::{Dance, Int, ItemOrder, Items, Mems, OptOrder, Solve, Spec};
+
+// Removes the option of node x, at the front of the list of item p, from
+// that list, along with all the uses it has left.
+pub fn tweak<D: DanceM<I: ItemsM>>(x: Uint, p: Uint, dance: &mut D) {
+    if *dance.bound(p) != 0 {
+        dance.hide(x);
+    }
+    let w = dance.uses_left(x);
+    let d = *dance.dlink(x);
+    *dance.dlink(p) = d;
+    *dance.ulink(d) = p;
+    *dance.len(p) -= w;
+}
+
+pub fn untweak<D: DanceM<I: ItemsM>>(l: Uint, unblock: bool, dance: &mut D) {
+    let ftl = dance.ft()[l as usize];
+    let p = if ftl <= dance.items().count() {
+        ftl
+    } else {
+        *dance.top(ftl) as Uint
+    };
+    let mut x = ftl;
+    let mut y = p;
+    let z = *dance.dlink(p);
+    *dance.dlink(p) = x;
+    let mut k = 0;
+    while x != z {
+        *dance.ulink(x) = y;
+        k += dance.uses_left(x);
+        if unblock {
+            dance.unhide(x);
+        }
+        y = x;
+        x = *dance.dlink(x);
+    }
+    *dance.ulink(z) = y;
+    *dance.len(p) += k;
+    if !unblock {
+        dance.uncover(p);
+    }
+}
+
+// As x::hide and c::hide, but the option counts once in each list for
+// every use it has left.
+pub fn hide<D: DanceM<I: ItemsM>>(p: Uint, dance: &mut D) {
+    let w = dance.uses_left(p);
+    let mut q = p + 1;
+    while q != p {
+        let (x, u, d) = dance.node(q);
+        if x <= 0 {
+            q = u;
+        } else {
+            if !dance.is_purified(q) {
+                *dance.dlink(u) = d;
+                *dance.ulink(d) = u;
+                *dance.len(x as Uint) -= w;
+                *dance.updates() += 1;
+            }
+            q += 1;
+        }
+    }
+}
+
+pub fn unhide<D: DanceM<I: ItemsM>>(p: Uint, dance: &mut D) {
+    let w = dance.uses_left(p);
+    let mut q = p - 1;
+    while q != p {
+        let (x, u, d) = dance.node(q);
+        if x <= 0 {
+            q = d;
+        } else {
+            if !dance.is_purified(q) {
+                *dance.dlink(u) = q;
+                *dance.ulink(d) = q;
+                *dance.len(x as Uint) += w;
+            }
+            q -= 1;
+        }
+    }
+}
+
+// The number of the option of node p, from the spacer after it, or None
+// if it was deleted.
+fn opt_number<D: Dance>(p: Uint, dance: &mut D) -> Option<usize> {
+    let mut s = p + 1;
+    while *dance.top(s) > 0 {
+        s += 1;
+    }
+    usize::try_from(-*dance.top(s) - 1).ok()
+}
+
+pub fn uses_left<D: DanceM<I: ItemsM>>(p: Uint, dance: &mut D) -> Int {
+    if dance.uses().is_empty() {
+        return 1;
+    }
+    let o = opt_number(p, dance);
+    o.and_then(|o| dance.uses().get(o)).map_or(1, |u| u.left)
+}
+
+// Takes one use of the option of node x, which stays at the front of the
+// list it was selected from for a deeper level to take another. Returns
+// false, changing nothing, if the option has only one use left.
+fn take_use<S: DanceM<I: ItemsM>>(solve: &mut S, x: Uint) -> bool {
+    if solve.uses().is_empty() {
+        return false;
+    }
+    let Some(o) = opt_number(x, solve) else {
+        return false;
+    };
+    match solve.uses().get_mut(o) {
+        Some(u) if u.left > 1 => u.left -= 1,
+        _ => return false,
+    }
+    count_use(solve, x, -1);
+    true
+}
+
+fn give_back_use<S: DanceM<I: ItemsM>>(solve: &mut S, x: Uint) {
+    if let Some(o) = opt_number(x, solve) {
+        solve.uses()[o].left += 1;
+        count_use(solve, x, 1);
+    }
+}
+
+// Adds k to the length of the list of each item of the option of node x.
+fn count_use<S: DanceM<I: ItemsM>>(solve: &mut S, x: Uint, k: Int) {
+    let mut q = x;
+    loop {
+        let j = *solve.top(q);
+        if j <= 0 {
+            q = *solve.ulink(q);
+        } else {
+            if !solve.is_purified(q) {
+                *solve.len(j as Uint) += k;
+            }
+            q += 1;
+        }
+        if q == x {
+            break;
+        }
+    }
+}
+
+pub fn branch_degree<D: DanceM<I: ItemsM>>(i: Uint, dance: &mut D) -> Int {
+    let len = *dance.len(i);
+    (len + 1).saturating_sub((*dance.bound(i)).saturating_sub(dance.slack(i)))
+}
+
+pub fn enter_level<S: SolveM>(solve: &mut S, _: Uint, _: Uint, _: Uint) {
+    solve.ft().push(0);
+}
+
+// Probing can't yet tell when an item with a bound above 1 is left
+// without enough options, so it is refused.
+pub fn set_lookahead(depth: usize) -> Result<()> {
+    if depth != 0 {
+        bail!("Lookahead needs a problem without multiplicities");
+    }
+    Ok(())
+}
+
+pub fn prepare_to_branch<S: SolveM>(solve: &mut S, i: Uint, l: Uint, xl: Uint) {
+    *solve.bound(i) -= 1;
+    if *solve.bound(i) == 0 {
+        solve.cover(i);
+        if solve.slack(i) != 0 {
+            solve.ft()[l as usize] = xl;
+        }
+    } else {
+        solve.ft()[l as usize] = xl;
+    }
+}
+
+pub fn try_item<S: SolveM>(solve: &mut S, i: Uint, _: Uint, xl: Uint) -> bool {
+    if solve.slack(i) == 0 && *solve.bound(i) == 0 {
+        if xl == i {
+            return false;
+            // go to M8
+        }
+        // go to M6
+    } else if *solve.bound(i) - solve.slack(i) >= *solve.len(i) {
+        return false;
+        // go to M8
+    } else if xl != i {
+        if *solve.bound(i) == 0 || !take_use(solve, xl) {
+            solve.tweak(xl, i);
+        }
+    } else if *solve.bound(i) != 0 {
+        let p = *solve.llink(i);
+        let q = *solve.rlink(i);
+        *solve.rlink(p) = q;
+        *solve.llink(q) = p;
+    }
+    // M6
+    if xl != i {
+        let mut p = xl + 1;
+        while p != xl {
+            let j = *solve.top(p);
+            if j <= 0 {
+                p = *solve.ulink(p);
+            } else if j as Uint <= solve.items().primary() {
+                p += 1;
+                *solve.bound(j as Uint) -= 1;
+                if *solve.bound(j as Uint) == 0 {
+                    solve.cover(j as Uint);
+                }
+            } else {
+                use_secondary(solve, p, j as Uint);
+                p += 1;
+            }
+        }
+    }
+    true
+}
+
+// An uncolored secondary item is committed once its capacity is used up.
+// Colored uses don't count against it, since any number of options may
+// agree on a color.
+fn use_secondary<S: DanceM<I: ItemsM>>(solve: &mut S, p: Uint, j: Uint) {
+    if solve.has_color(p) {
+        solve.commit(p, j);
+        return;
+    }
+    *solve.bound(j) -= 1;
+    if *solve.bound(j) == 0 {
+        solve.commit(p, j);
+    }
+}
+
+fn unuse_secondary<S: DanceM<I: ItemsM>>(solve: &mut S, p: Uint, j: Uint) {
+    if solve.has_color(p) {
+        solve.uncommit(p, j);
+        return;
+    }
+    if *solve.bound(j) == 0 {
+        solve.uncommit(p, j);
+    }
+    *solve.bound(j) += 1;
+}
+
+pub fn try_again<S: SolveM>(
+    solve: &mut S, i: Uint, l: Uint, xl: &mut Uint,
+) -> bool {
+    let mut i = i;
+    let again = if *xl > solve.items().count() {
+        solve.undo_item(i, l, *xl);
+        // An option with uses left stayed at the front of the list, and is
+        // passed over now along with them
+        if *solve.bound(i) != 0 && *solve.dlink(i) == *xl {
+            solve.tweak(*xl, i);
+        }
+        *xl = *solve.dlink(*xl);
+        solve.try_item(i, l, *xl)
+    } else {
+        i = *xl;
+        solve.undo_item(i, l, *xl);
+        false
+    };
+    if !again {
+        solve.restore_item(i, l, *xl);
+    }
+    again
+}
+
+pub fn undo_item<S: SolveM>(solve: &mut S, i: Uint, _: Uint, xl: Uint) {
+    if xl > solve.items().count() {
+        let mut p = xl - 1;
+        while p != xl {
+            let j = *solve.top(p);
+            if j <= 0 {
+                p = *solve.dlink(p);
+            } else if (j as Uint) <= solve.items().primary() {
+                p -= 1;
+                *solve.bound(j as Uint) += 1;
+                if *solve.bound(j as Uint) == 1 {
+                    solve.uncover(j as Uint);
+                }
+            } else {
+                unuse_secondary(solve, p, j as Uint);
+                p -= 1;
+            }
+        }
+        if *solve.bound(i) != 0 && *solve.dlink(i) == xl {
+            give_back_use(solve, xl);
+        }
+    } else {
+        let p = *solve.llink(i);
+        let q = *solve.rlink(i);
+        *solve.rlink(p) = i;
+        *solve.llink(q) = i;
+    }
+}
+
+pub fn restore_item<S: SolveM>(solve: &mut S, i: Uint, l: Uint, _: Uint) {
+    if *solve.bound(i) == 0 && solve.slack(i) == 0 {
+        solve.uncover(i);
+    } else {
+        let unblock = *solve.bound(i) != 0;
+        solve.untweak(l, unblock);
+    }
+    *solve.bound(i) += 1;
+}
+
+// Selects the option of node p outside of a search, taking one of its uses
+// and one from the bound of each of its items. An option with no uses left
+// is taken out of every list, as hiding leaves it in the list of p.
+pub fn select<D: DanceM<I: ItemsM>>(p: Uint, dance: &mut D) {
+    if !take_use(dance, p) {
+        dance.hide(p);
+        let u = *dance.ulink(p);
+        let d = *dance.dlink(p);
+        *dance.dlink(u) = d;
+        *dance.ulink(d) = u;
+        let i = *dance.top(p) as Uint;
+        *dance.len(i) -= 1;
+    }
+    let mut q = p;
+    loop {
+        let j = *dance.top(q);
+        if j <= 0 {
+            q = *dance.ulink(q);
+        } else {
+            if j as Uint <= dance.items().primary() {
+                *dance.bound(j as Uint) -= 1;
+                if *dance.bound(j as Uint) == 0 {
+                    dance.cover(j as Uint);
+                }
+            } else {
+                use_secondary(dance, q, j as Uint);
+            }
+            q += 1;
+        }
+        if q == p {
+            break;
+        }
+    }
+}
+
+pub fn deselect<D: DanceM<I: ItemsM>>(p: Uint, dance: &mut D) {
+    let mut q = p - 1;
+    loop {
+        let j = *dance.top(q);
+        if j <= 0 {
+            q = *dance.dlink(q);
+            continue;
+        }
+        if j as Uint <= dance.items().primary() {
+            if *dance.bound(j as Uint) == 0 {
+                dance.uncover(j as Uint);
+            }
+            *dance.bound(j as Uint) += 1;
+        } else {
+            unuse_secondary(dance, q, j as Uint);
+        }
+        if q == p {
+            break;
+        }
+        q -= 1;
+    }
+    let u = *dance.ulink(p);
+    if *dance.dlink(u) == p {
+        give_back_use(dance, p);
+    } else {
+        let d = *dance.dlink(p);
+        *dance.dlink(u) = p;
+        *dance.ulink(d) = p;
+        let i = *dance.top(p) as Uint;
+        *dance.len(i) += 1;
+        dance.unhide(p);
+    }
+}
+
+// Splits an item declaration u:v|name or v|name into its name and bounds,
+// with no lower bound given for v|name. A plain name has v = 1. An item
+// that can't be used at all is an error, as it would block every option
+// that has it.
+fn parse_bounds(item: &str) -> Result<(&str, Option<Int>, Int)> {
+    let Some((data, name)) = item.split_once('|') else {
+        return Ok((item, None, 1));
+    };
+    if name.contains('|') {
+        bail!("Too many '|' (multiplicity) separators");
+    }
+    let (u, v) = match data.split_once(':') {
+        Some((u, v)) => (Some(u), v),
+        None => (None, data),
+    };
+    if v.contains(':') {
+        bail!("Too many ':' (multiplicity) separators");
+    }
+    let u = u.map(str::parse).transpose();
+    let u: Option<Int> = u.or_else(|_| bail!("non-numeric bound"))?;
+    let v: Int = v.parse().or_else(|_| bail!("non-numeric bound"))?;
+    if v < 1 {
+        bail!("Item {} has a bound below 1", name);
+    }
+    Ok((name, u, v))
+}
+
+// Splits a use count prefix u:v| or v| off the first item of an option,
+// which may then be selected from u to v times, or exactly v times for v|,
+// as with the bounds of items. A v of * leaves the uses unbounded, and *|
+// is short for 0:*|.
+fn option_uses(opt: &[String]) -> Result<(Int, Option<Int>, Vec<String>)> {
+    let Some((data, first)) = opt.first().and_then(|s| s.split_once('|'))
+    else {
+        return Ok((0, Some(1), opt.to_vec()));
+    };
+    let (u, v) = match data.split_once(':') {
+        Some(uv) => uv,
+        None if data == "*" => ("0", data),
+        None => (data, data),
+    };
+    let u: Int = u.parse().or_else(|_| bail!("non-numeric use count"))?;
+    let v: Option<Int> = match v {
+        "*" => None,
+        v => Some(v.parse().or_else(|_| bail!("non-numeric use count"))?),
+    };
+    if u < 0 || v.is_some_and(|v| v < 1 || u > v) {
+        bail!("Invalid use count {}", data);
+    }
+    let mut items = Vec::new();
+    if !first.is_empty() {
+        items.push(first.to_string());
+    }
+    items.extend(opt[1..].iter().cloned());
+    Ok((u, v, items))
+}
+
+// The uses an option has left, and the item that counts them if it must
+// be used a least number of times, or 0. Options beyond those a problem
+// has uses for are used once.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Uses {
+    pub left: Int,
+    pub item: Uint,
+}
+
+// Strips the use counts off the options of a spec, returning the uses of
+// each option, or none if every option is used once. An option that must
+// be used at least u times gets a new primary item with bounds u:v that it
+// covers, named uses followed by the option number, with as many more u's
+// in front as it takes for no item of the spec to start the same way. An
+// option with unbounded uses can be used as often as the smallest bound of
+// its primary items allows.
+pub(crate) fn parse_uses(spec: &Spec) -> Result<(Spec, Vec<Uses>)> {
+    use std::collections::HashMap;
+    let mut stripped = Spec {
+        primary: spec.primary.clone(),
+        secondary: spec.secondary.clone(),
+        opts: Vec::new(),
+        sharp_pref: spec.sharp_pref,
+    };
+    let mut bounds = HashMap::new();
+    for item in &spec.primary {
+        let (name, _, v) = parse_bounds(item)?;
+        bounds.insert(name, v);
+    }
+    let names = spec.primary.iter().chain(&spec.secondary);
+    let names = names.map(|item| item_name(item)).collect::<Vec<_>>();
+    let mut tag = String::from("uses");
+    while names.iter().any(|name| name.starts_with(&tag)) {
+        tag.insert(0, 'u');
+    }
+    let mut uses = Vec::new();
+    for (o, opt) in spec.opts.iter().enumerate() {
+        let (u, v, mut items) = option_uses(opt)?;
+        let v = v.unwrap_or_else(|| {
+            let vs = items.iter().filter_map(|i| bounds.get(i.as_str()));
+            vs.copied().min().unwrap_or(1)
+        });
+        if u > v {
+            bail!("Option {} can't be used {} times", o, u);
+        }
+        let mut item = 0;
+        if u > 0 {
+            let name = format!("{}{}", tag, o);
+            stripped.primary.push(format!("{}:{}|{}", u, v, name));
+            item = stripped.primary.len() as Uint;
+            items.push(name);
+        }
+        stripped.opts.push(items);
+        uses.push(Uses { left: v, item });
+    }
+    if uses.iter().all(|u| *u == Uses { left: 1, item: 0 }) {
+        uses.clear();
+    }
+    Ok((stripped, uses))
+}
+
+// Gives the options their uses, counting each option in the lists of its
+// items once for every use.
+pub(crate) fn set_uses<D: DanceM<I: ItemsM>>(dance: &mut D, uses: Vec<Uses>) {
+    let mut s = dance.items().count() + 1;
+    loop {
+        let last = *dance.dlink(s);
+        if last <= s {
+            break;
+        }
+        let o = -*dance.top(last + 1) - 1;
+        let w = uses.get(o as usize).map_or(1, |u| u.left);
+        for q in s + 1..=last {
+            let i = *dance.top(q) as Uint;
+            *dance.len(i) += w - 1;
+        }
+        s = last + 1;
+    }
+    *dance.uses() = uses;
+}
+
+// Drops the least number of uses of option o once it is deleted, by giving
+// the item that counted them a lower bound of 0.
+pub(crate) fn drop_min_uses<D: DanceM<I: ItemsM>>(dance: &mut D, o: usize) {
+    let Some(uses) = dance.uses().get_mut(o) else {
+        return;
+    };
+    let i = std::mem::take(&mut uses.item);
+    if i != 0 {
+        let v = *dance.bound(i);
+        dance.items().set_slack(i, v);
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct INode {
+    left: Uint,
+    right: Uint,
+    slack: Int,
+    bound: Int,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct INodes {
+    nodes: Nodes<INode>,
+    primary: Uint,
+    len: Uint,
+    mems: Mems,
+}
+
+// As with x::INodes, mems are left out.
+impl PartialEq for INodes {
+    fn eq(&self, other: &INodes) -> bool {
+        let fields = (&self.nodes, self.primary, self.len);
+        fields == (&other.nodes, other.primary, other.len)
+    }
+}
+
+impl Eq for INodes {}
+
+impl INodes {
+    // Primary items have bounds (u, v), and secondary items a capacity v.
+    pub fn new(
+        ps: impl IntoIterator<Item = (Int, Int)>,
+        ss: impl IntoIterator<Item = Int>, order: ItemOrder,
+    ) -> INodes {
+        let mut nodes = vec![Default::default()];
+        for (u, v) in ps.into_iter() {
+            nodes.push(INode { bound: v, slack: v - u, ..Default::default() });
+        }
+        let primary = (nodes.len() - 1) as Uint;
+        for v in ss.into_iter() {
+            nodes.push(INode { bound: v, ..Default::default() });
+        }
+        let len = (nodes.len() - 1) as Uint;
+        nodes.push(Default::default());
+        let nodes = Nodes::from_vec(nodes);
+        let mut inodes = INodes { nodes, primary, len, mems: Mems::default() };
+        inodes.init_links(order);
+        inodes
+    }
+
+    pub fn from_spec(
+        spec: &Spec, order: ItemOrder,
+    ) -> Result<(INodes, Vec<String>)> {
+        use std::collections::HashSet;
+        let mut names: Vec<String> = Vec::new();
+        let mut ps = Vec::new();
+        for item in &spec.primary {
+            let (name, u, v) = parse_bounds(item)?;
+            names.push(name.into());
+            ps.push((u.unwrap_or(v), v));
+        }
+        let mut ss = Vec::new();
+        for item in &spec.secondary {
+            // Secondary items have no minimum, so v| means at most v
+            let (name, u, v) = parse_bounds(item)?;
+            if u.is_some_and(|u| u != 0) {
+                bail!("Secondary item {} has a lower bound", name);
+            }
+            names.push(name.into());
+            ss.push(v);
+        }
+        let mut used = HashSet::new();
+        let unique = names.iter().all(|e| used.insert(e));
+        if !unique {
+            bail!("Duplicate item names");
+        }
+        for name in &names {
+            if !name.chars().all(|c| c.is_alphanumeric() || c == '#') {
+                bail!("Invalid item name");
+            }
+        }
+        Ok((INodes::new(ps, ss, order), names))
+    }
+
+    #[inline]
+    fn get_node(&mut self, i: Uint) -> &mut INode {
+        self.mems.add();
+        self.nodes.get_mut(i)
+    }
+}
+
+#[derive(Debug)]
+pub struct Problem {
+    items: INodes,
+    opts: x::ONodes,
+    uses: Vec<Uses>,
+    ft: Vec<Uint>,
+    updates: isize,
+}
+
+// Problems compare equal whatever is left on the stack of first tweaks.
+impl PartialEq for Problem {
+    fn eq(&self, other: &Problem) -> bool {
+        let fields = (&self.items, &self.opts, &self.uses, self.updates);
+        fields == (&other.items, &other.opts, &other.uses, other.updates)
+    }
+}
+
+impl Eq for Problem {}
+
+impl Problem {
+    pub fn new(items: INodes, opts: x::ONodes) -> Problem {
+        let problem = Problem::from_parts(items, opts, Vec::new());
+        problem.unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn from_spec(
+        spec: &Spec, order: OptOrder, item_order: ItemOrder,
+    ) -> Result<Problem> {
+        let (spec, uses) = parse_uses(spec)?;
+        let (items, names) = INodes::from_spec(&spec, item_order)?;
+        let opts = x::ONodes::from_spec(&spec, &names, order)?;
+        let mut problem = Problem::new(items, opts);
+        set_uses(&mut problem, uses);
+        Ok(problem)
+    }
+}
+
+impl Items for INodes {
+    #[inline]
+    fn llink(&mut self, i: Uint) -> &mut Uint {
+        &mut self.get_node(i).left
+    }
+
+    #[inline]
+    fn rlink(&mut self, i: Uint) -> &mut Uint {
+        &mut self.get_node(i).right
+    }
+
+    #[inline]
+    fn primary(&self) -> Uint {
+        self.primary
+    }
+
+    #[inline]
+    fn count(&self) -> Uint {
+        self.len
+    }
+
+    #[inline]
+    fn mems(&self) -> u64 {
+        self.mems.get()
+    }
+
+    fn insert_nodes(&mut self, i: Uint, k: Uint) {
+        // New items are secondary, with a capacity of 1
+        let node = INode { bound: 1, ..Default::default() };
+        self.nodes.insert_many(i as usize, k as usize, node);
+        self.len += k;
+    }
+}
+
+pub trait ItemsM: Items {
+    fn bound(&mut self, i: Uint) -> &mut Int;
+    fn slack(&mut self, i: Uint) -> Int;
+    fn set_slack(&mut self, i: Uint, slack: Int);
+}
+
+impl ItemsM for INodes {
+    #[inline]
+    fn bound(&mut self, i: Uint) -> &mut Int {
+        &mut self.get_node(i).bound
+    }
+
+    #[inline]
+    fn slack(&mut self, i: Uint) -> Int {
+        self.get_node(i).slack
+    }
+
+    fn set_slack(&mut self, i: Uint, slack: Int) {
+        self.get_node(i).slack = slack;
+    }
+}
+
+impl Store for Problem {
+    const KIND: u8 = 1;
+
+    fn item_bounds(&mut self, i: Uint) -> (Int, Int) {
+        let v = *self.items.bound(i);
+        (v - self.items.slack(i), v)
+    }
+
+    fn blank_items(np: Uint, _: Uint, bounds: &[(Int, Int)]) -> INodes {
+        blank_items(np, bounds)
+    }
+
+    fn opt_uses(&mut self) -> Vec<Uses> {
+        self.uses.clone()
+    }
+
+    fn blank_opts(size: usize, _: Vec<Int>) -> x::ONodes {
+        x::ONodes::blank(size)
+    }
+
+    fn from_parts(
+        items: INodes, opts: x::ONodes, uses: Vec<Uses>,
+    ) -> Result<Problem> {
+        let ft = Vec::new();
+        let mut problem = Problem { items, opts, uses, ft, updates: 0 };
+        check_links(&mut problem)?;
+        Ok(problem)
+    }
+}
+
+// Items of the given bounds, of which the first np are primary.
+pub(crate) fn blank_items(np: Uint, bounds: &[(Int, Int)]) -> INodes {
+    let (ps, ss) = bounds.split_at(np as usize);
+    let ss = ss.iter().map(|&(_, v)| v);
+    INodes::new(ps.iter().copied(), ss, ItemOrder::Seq)
+}
+
+impl Dance for Problem {
+    type I = INodes;
+    type O = x::ONodes;
+
+    #[inline]
+    fn items(&mut self) -> &mut Self::I {
+        &mut self.items
+    }
+
+    #[inline]
+    fn opts(&mut self) -> &mut Self::O {
+        &mut self.opts
+    }
+
+    #[inline]
+    fn updates(&mut self) -> &mut isize {
+        &mut self.updates
+    }
+
+    #[inline]
+    fn cover(&mut self, i: Uint) {
+        x::cover(i, self);
+    }
+
+    #[inline]
+    fn commit(&mut self, p: Uint, j: Uint) {
+        x::commit(p, j, self);
+    }
+
+    #[inline]
+    fn uncover(&mut self, i: Uint) {
+        x::uncover(i, self);
+    }
+
+    #[inline]
+    fn uncommit(&mut self, p: Uint, j: Uint) {
+        x::uncommit(p, j, self);
+    }
+
+    #[inline]
+    fn hide(&mut self, p: Uint) {
+        hide(p, self);
+    }
+
+    #[inline]
+    fn unhide(&mut self, p: Uint) {
+        unhide(p, self);
+    }
+
+    #[inline]
+    fn branch_degree(&mut self, i: Uint) -> Int {
+        branch_degree(i, self)
+    }
+
+    #[inline]
+    fn uses_left(&mut self, p: Uint) -> Int {
+        uses_left(p, self)
+    }
+}
+
+pub trait DanceM: Dance<I: ItemsM> {
+    fn tweak(&mut self, x: Uint, p: Uint);
+    fn untweak(&mut self, l: Uint, unblock: bool);
+    fn ft(&mut self) -> &mut Vec<Uint>;
+    fn uses(&mut self) -> &mut Vec<Uses>;
+
+    #[inline]
+    fn has_color(&mut self, _p: Uint) -> bool {
+        false
+    }
+
+    // Whether node p was left in its list by purifying its item.
+    #[inline]
+    fn is_purified(&mut self, _p: Uint) -> bool {
+        false
+    }
+
+    #[inline]
+    fn bound(&mut self, i: Uint) -> &mut Int {
+        self.items().bound(i)
+    }
+
+    #[inline]
+    fn slack(&mut self, i: Uint) -> Int {
+        self.items().slack(i)
+    }
+}
+
+impl DanceM for Problem {
+    #[inline]
+    fn tweak(&mut self, x: Uint, p: Uint) {
+        tweak(x, p, self);
+    }
+
+    #[inline]
+    fn untweak(&mut self, l: Uint, unblock: bool) {
+        untweak(l, unblock, self);
+    }
+
+    #[inline]
+    fn ft(&mut self) -> &mut Vec<Uint> {
+        &mut self.ft
+    }
+
+    #[inline]
+    fn uses(&mut self) -> &mut Vec<Uses> {
+        &mut self.uses
+    }
+}
+
+impl Solve for Problem {
+    #[inline]
+    fn enter_level(&mut self, i: Uint, l: Uint, xl: Uint) {
+        enter_level(self, i, l, xl);
+    }
+
+    #[inline]
+    fn prepare_to_branch(&mut self, i: Uint, l: Uint, xl: Uint) {
+        prepare_to_branch(self, i, l, xl);
+    }
+
+    #[inline]
+    fn try_item(&mut self, i: Uint, l: Uint, xl: Uint) -> bool {
+        try_item(self, i, l, xl)
+    }
+
+    #[inline]
+    fn try_again(&mut self, i: Uint, l: Uint, xl: &mut Uint) -> bool {
+        try_again(self, i, l, xl)
+    }
+
+    #[inline]
+    fn undo_item(&mut self, i: Uint, l: Uint, xl: Uint) {
+        undo_item(self, i, l, xl);
+    }
+
+    #[inline]
+    fn restore_item(&mut self, i: Uint, l: Uint, xl: Uint) {
+        restore_item(self, i, l, xl);
+    }
+
+    fn set_lookahead(&mut self, depth: usize, _: Uint) -> Result<()> {
+        set_lookahead(depth)
+    }
+}
+
+pub trait SolveM: Solve + DanceM<I: ItemsM> {}
+
+impl SolveM for Problem {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::solutions;
+    use crate::{Opts, opt_starts};
+
+    #[test]
+    fn test_from_spec() {
+        let spec_str = "
+A B 2:3|C | X Y
+A B X Y
+A C X Y
+C X
+B X
+C Y
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let problem =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        let ps = vec![(1, 1), (1, 1), (2, 3)];
+        let items = INodes::new(ps, [1, 1], ItemOrder::Seq);
+        assert_eq!(problem.items, items);
+    }
+
+    #[test]
+    fn test_mc() {
+        use crate::choose::*;
+        use crate::{OptOrder, Rng, Solver};
+        use core::iter::repeat_n;
+        let ps = repeat_n((1, 1), 8)
+            .chain(repeat_n((2, 2), 4))
+            .chain(repeat_n((0, 2), 12));
+        let items = INodes::new(ps, [], ItemOrder::Seq);
+
+        let mut os: Vec<Vec<Uint>> = Vec::new();
+        for i in 0..2 {
+            for j in 0..2 {
+                os.push(vec![i, 8 + j, 12 + i + 1 - j, 15 + i + j]);
+                os.push(vec![10 + i, 2 + j, 12 + i + 1 - j, 18 + i + j]);
+                os.push(vec![4 + i, 8 + j, 21 + i + 1 - j, 18 + i + j]);
+                os.push(vec![10 + i, 6 + j, 21 + i + 1 - j, 15 + i + j]);
+            }
+        }
+        let opts =
+            x::ONodes::new(24, 24, &os, OptOrder::Rnd(Rng::new(12345678)));
+
+        let items_init = items.clone();
+        let opts_init = opts.clone();
+        let mut problem = Problem::new(items, opts);
+        let mut solver = Solver::new(&mut problem);
+        let mut solutions: Vec<Vec<Int>> = Vec::new();
+        let mut expected = vec![
+            vec![0, 1, 5, 6, 8, 11, 14, 15],
+            vec![0, 2, 5, 7, 9, 11, 12, 14],
+            vec![0, 3, 6, 7, 8, 9, 13, 14],
+            vec![1, 2, 4, 5, 10, 11, 12, 15],
+            vec![1, 3, 4, 6, 8, 10, 13, 15],
+            vec![2, 3, 4, 7, 9, 10, 12, 13],
+        ];
+        let mut i = 0;
+        let mut chooser = mrv_chooser(prefer_any(), knuth_tiebreak());
+        while solver.next_solution(&mut chooser) {
+            assert!(i <= expected.len(), "too many solutions");
+            solver.fmt_solution();
+            solver.o.sort();
+            solutions.push(solver.o.clone());
+            i += 1;
+        }
+        solutions.sort();
+        expected.sort();
+        assert_eq!(solutions, expected, "wrong solutions");
+        assert_eq!(solver.problem.items, items_init, "items not backtracked");
+        assert_eq!(solver.problem.opts, opts_init, "options not backtracked");
+        assert!(
+            solver.l == 0 && solver.restart == false,
+            "initial state not restored"
+        );
+    }
+
+    #[test]
+    fn test_uses() {
+        use crate::Rng;
+        let spec_str = "
+4|a 2|b
+0:3|a
+0:2|a b
+b
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let expected = vec![vec![0, 0, 0, 1, 2], vec![0, 0, 1, 1]];
+        let mut problem =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        assert_eq!(*problem.opts().opt_count(), 3);
+        assert_eq!(opt_starts(&mut problem).len(), 3);
+        assert_eq!(solutions(&mut problem), expected);
+        let order = OptOrder::Rnd(Rng::new(4321));
+        let mut problem =
+            Problem::from_spec(&spec, order, ItemOrder::Seq).unwrap();
+        assert_eq!(solutions(&mut problem), expected);
+
+        let spec_str = "
+0:5|a b
+2:3|a
+a b
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let mut problem =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        let init =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        let expected = vec![vec![0, 0, 0, 1], vec![0, 0, 1]];
+        assert_eq!(solutions(&mut problem), expected);
+        assert_eq!(problem.items, init.items, "items not backtracked");
+        assert_eq!(problem.opts, init.opts, "options not backtracked");
+        let spec = Spec::new("2|a\n2|a\na", false).unwrap();
+        let mut problem =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        assert_eq!(solutions(&mut problem), vec![vec![0, 0]]);
+        let spec = Spec::new("a uses0\n1:2|a\nuses0", false).unwrap();
+        let mut problem =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        assert_eq!(solutions(&mut problem), vec![vec![0, 1]]);
+
+        for bad in ["0|a", "3:2|a", "x|a", "-1:2|a", "2:*|a"] {
+            let spec = Spec::new(&format!("a\n{}", bad), false).unwrap();
+            assert!(
+                Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_unbounded_uses() {
+        let spec_str = "
+3|a 2|b
+*|a
+a b
+*|b
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let mut problem =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        let init =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        let expected = vec![vec![0, 0, 0, 2, 2], vec![0, 0, 1, 2]];
+        assert_eq!(solutions(&mut problem), expected);
+        assert_eq!(problem.items, init.items, "items not backtracked");
+        assert_eq!(problem.opts, init.opts, "options not backtracked");
+        assert_eq!(problem.uses, init.uses, "uses not backtracked");
+        // Each option is stored once, however many times it can be used
+        let spec = Spec::new("3|a 2|b\na\na b\nb", false).unwrap();
+        let once =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        assert_eq!(problem.opts.size(), once.opts.size());
+    }
+
+    #[test]
+    fn test_secondary_bounds() {
+        let spec_str = "
+a b c | 2|n
+a n
+b n
+c n
+a
+b
+c
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let mut problem =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        let init =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        let found = solutions(&mut problem);
+        assert_eq!(found.len(), 7);
+        assert!(!found.contains(&vec![0, 1, 2]));
+        assert!(found.contains(&vec![0, 1, 5]));
+        assert_eq!(problem.items, init.items, "items not backtracked");
+        assert_eq!(problem.opts, init.opts, "options not backtracked");
+
+        for bad in ["1:2|n", "0:x|n", "x|n", "0|n", "-1|n", "0:0|n"] {
+            let spec = Spec::new(&format!("a | {}\na", bad), false).unwrap();
+            assert!(
+                Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                    .is_err()
+            );
+        }
+    }
+}
@@ -1,182 +1,300 @@
-use anyhow::Result;
-
-use crate::{Dance, Int, Solve, Spec, Uint};
-use crate::{c, m, x};
-
-#[derive(Debug, Eq, PartialEq)]
-pub struct Problem {
-    items: m::INodes,
-    opts: c::ONodes,
-    ft: Vec<Uint>,
-    updates: isize,
-}
-
-impl Problem {
-    pub fn new(items: m::INodes, opts: c::ONodes) -> Problem {
-        Problem { items, opts, ft: Vec::new(), updates: 0 }
-    }
-
-    pub fn from_spec(spec: &Spec) -> Result<Problem> {
-        let (items, names) = m::INodes::from_spec(spec)?;
-        let opts = c::ONodes::from_spec(spec, &names)?;
-        Ok(Problem::new(items, opts))
-    }
-}
-
-impl Dance for Problem {
-    type I = m::INodes;
-    type O = c::ONodes;
-
-    #[inline]
-    fn items(&mut self) -> &mut Self::I {
-        &mut self.items
-    }
-
-    #[inline]
-    fn opts(&mut self) -> &mut Self::O {
-        &mut self.opts
-    }
-
-    #[inline]
-    fn updates(&mut self) -> &mut isize {
-        &mut self.updates
-    }
-
-    #[inline]
-    fn cover(&mut self, i: Uint) {
-        x::cover(i, self);
-    }
-
-    #[inline]
-    fn commit(&mut self, p: Uint, j: Uint) {
-        c::commit(p, j, self);
-    }
-
-    #[inline]
-    fn uncover(&mut self, i: Uint) {
-        x::uncover(i, self);
-    }
-
-    #[inline]
-    fn uncommit(&mut self, p: Uint, j: Uint) {
-        c::uncommit(p, j, self);
-    }
-
-    #[inline]
-    fn hide(&mut self, p: Uint) {
-        c::hide(p, self);
-    }
-
-    #[inline]
-    fn unhide(&mut self, p: Uint) {
-        c::unhide(p, self);
-    }
-
-    #[inline]
-    fn branch_degree(&mut self, i: Uint) -> Int {
-        m::branch_degree(i, self)
-    }
-}
-
-impl c::DanceC for Problem {
-    #[inline]
-    fn purify(&mut self, p: Uint) {
-        c::purify(p, self);
-    }
-
-    #[inline]
-    fn unpurify(&mut self, p: Uint) {
-        c::unpurify(p, self);
-    }
-}
-
-impl m::DanceM for Problem {
-    #[inline]
-    fn tweak(&mut self, x: Uint, p: Uint) {
-        m::tweak(x, p, self);
-    }
-
-    #[inline]
-    fn untweak(&mut self, l: Uint, unblock: bool) {
-        m::untweak(l, unblock, self);
-    }
-
-    #[inline]
-    fn ft(&mut self) -> &mut Vec<Uint> {
-        &mut self.ft
-    }
-}
-
-impl Solve for Problem {
-    #[inline]
-    fn enter_level(&mut self, i: Uint, l: Uint, xl: Uint) {
-        m::enter_level(self, i, l, xl);
-    }
-
-    #[inline]
-    fn prepare_to_branch(&mut self, i: Uint, l: Uint, xl: Uint) {
-        m::prepare_to_branch(self, i, l, xl);
-    }
-
-    #[inline]
-    fn try_item(&mut self, i: Uint, l: Uint, xl: Uint) -> bool {
-        m::try_item(self, i, l, xl)
-    }
-
-    #[inline]
-    fn try_again(&mut self, i: Uint, l: Uint, xl: &mut Uint) -> bool {
-        m::try_again(self, i, l, xl)
-    }
-
-    #[inline]
-    fn restore_item(&mut self, i: Uint, l: Uint, xl: Uint) {
-        m::restore_item(self, i, l, xl);
-    }
-}
-
-impl m::SolveM for Problem {}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_mcc() {
-        use crate::choose::*;
-        use crate::{OptOrder, Solver};
-        let ps = vec![(1, 1), (1, 1), (2, 3)];
-        let items = m::INodes::new(ps, 2);
-        let os = vec![
-            vec![(0, 0), (1, 0), (3, 0), (4, 0)],
-            vec![(0, 0), (2, 0), (3, 1), (4, 1)],
-            vec![(2, 0), (3, 0)],
-            vec![(1, 0), (3, 1)],
-            vec![(2, 0), (4, 1)],
-        ];
-        let opts = c::ONodes::new(5, 3, &os, OptOrder::Seq);
-        let items_init = items.clone();
-        let opts_init = opts.clone();
-        let mut problem = Problem::new(items, opts);
-        let mut solver = Solver::new(&mut problem);
-        let mut solutions: Vec<Vec<Int>> = Vec::new();
-        let mut expected = vec![vec![1, 3, 4]];
-        let mut i = 0;
-        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
-        while solver.next_solution(&mut chooser) {
-            assert!(i <= expected.len(), "too many solutions");
-            solver.fmt_solution();
-            solver.o.sort();
-            solutions.push(solver.o.clone());
-            i += 1;
-        }
-        solutions.sort();
-        expected.sort();
-        assert_eq!(solutions, expected, "wrong solutions");
-        assert_eq!(solver.problem.items, items_init, "items not backtracked");
-        assert_eq!(solver.problem.opts, opts_init, "options not backtracked");
-        assert!(
-            solver.l == 0 && solver.restart == false,
-            "initial state not restored"
-        );
-    }
-}
+use anyhow::Result;
+
+use crate::nodes::check_links;
+use crate::store::Store;
+use crate::{Dance, Int, ItemOrder, OptOrder, Solve, Spec, Uint};
+use crate::{c, m, x};
+
+#[derive(Debug)]
+pub struct Problem {
+    items: m::INodes,
+    opts: c::ONodes,
+    uses: Vec<m::Uses>,
+    ft: Vec<Uint>,
+    updates: isize,
+}
+
+// Problems compare equal whatever is left on the stack of first tweaks.
+impl PartialEq for Problem {
+    fn eq(&self, other: &Problem) -> bool {
+        let fields = (&self.items, &self.opts, &self.uses, self.updates);
+        fields == (&other.items, &other.opts, &other.uses, other.updates)
+    }
+}
+
+impl Eq for Problem {}
+
+impl Problem {
+    pub fn new(items: m::INodes, opts: c::ONodes) -> Problem {
+        let problem = Problem::from_parts(items, opts, Vec::new());
+        problem.unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn from_spec(
+        spec: &Spec, order: OptOrder, item_order: ItemOrder,
+    ) -> Result<Problem> {
+        let (spec, uses) = m::parse_uses(spec)?;
+        let (items, names) = m::INodes::from_spec(&spec, item_order)?;
+        let opts = c::ONodes::from_spec(&spec, &names, order)?;
+        let mut problem = Problem::new(items, opts);
+        m::set_uses(&mut problem, uses);
+        Ok(problem)
+    }
+}
+
+impl Store for Problem {
+    const KIND: u8 = 3;
+
+    fn item_bounds(&mut self, i: Uint) -> (Int, Int) {
+        use m::ItemsM;
+        let v = *self.items.bound(i);
+        (v - self.items.slack(i), v)
+    }
+
+    fn node_color(&mut self, p: Uint) -> Int {
+        use c::OptsC;
+        self.opts.color(p)
+    }
+
+    fn opt_uses(&mut self) -> Vec<m::Uses> {
+        self.uses.clone()
+    }
+
+    fn blank_items(np: Uint, _: Uint, bounds: &[(Int, Int)]) -> m::INodes {
+        m::blank_items(np, bounds)
+    }
+
+    fn blank_opts(size: usize, colors: Vec<Int>) -> c::ONodes {
+        c::ONodes::blank(size, colors)
+    }
+
+    fn from_parts(
+        items: m::INodes, opts: c::ONodes, uses: Vec<m::Uses>,
+    ) -> Result<Problem> {
+        let ft = Vec::new();
+        let mut problem = Problem { items, opts, uses, ft, updates: 0 };
+        check_links(&mut problem)?;
+        Ok(problem)
+    }
+}
+
+impl Dance for Problem {
+    type I = m::INodes;
+    type O = c::ONodes;
+
+    #[inline]
+    fn items(&mut self) -> &mut Self::I {
+        &mut self.items
+    }
+
+    #[inline]
+    fn opts(&mut self) -> &mut Self::O {
+        &mut self.opts
+    }
+
+    #[inline]
+    fn updates(&mut self) -> &mut isize {
+        &mut self.updates
+    }
+
+    #[inline]
+    fn cover(&mut self, i: Uint) {
+        x::cover(i, self);
+    }
+
+    #[inline]
+    fn commit(&mut self, p: Uint, j: Uint) {
+        c::commit(p, j, self);
+    }
+
+    #[inline]
+    fn uncover(&mut self, i: Uint) {
+        x::uncover(i, self);
+    }
+
+    #[inline]
+    fn uncommit(&mut self, p: Uint, j: Uint) {
+        c::uncommit(p, j, self);
+    }
+
+    #[inline]
+    fn hide(&mut self, p: Uint) {
+        m::hide(p, self);
+    }
+
+    #[inline]
+    fn unhide(&mut self, p: Uint) {
+        m::unhide(p, self);
+    }
+
+    #[inline]
+    fn branch_degree(&mut self, i: Uint) -> Int {
+        m::branch_degree(i, self)
+    }
+
+    #[inline]
+    fn uses_left(&mut self, p: Uint) -> Int {
+        m::uses_left(p, self)
+    }
+}
+
+impl c::DanceC for Problem {
+    #[inline]
+    fn purify(&mut self, p: Uint) {
+        c::purify(p, self);
+    }
+
+    #[inline]
+    fn unpurify(&mut self, p: Uint) {
+        c::unpurify(p, self);
+    }
+}
+
+impl m::DanceM for Problem {
+    #[inline]
+    fn tweak(&mut self, x: Uint, p: Uint) {
+        m::tweak(x, p, self);
+    }
+
+    #[inline]
+    fn untweak(&mut self, l: Uint, unblock: bool) {
+        m::untweak(l, unblock, self);
+    }
+
+    #[inline]
+    fn ft(&mut self) -> &mut Vec<Uint> {
+        &mut self.ft
+    }
+
+    #[inline]
+    fn uses(&mut self) -> &mut Vec<m::Uses> {
+        &mut self.uses
+    }
+
+    #[inline]
+    fn has_color(&mut self, p: Uint) -> bool {
+        c::DanceC::color(self, p) != 0
+    }
+
+    #[inline]
+    fn is_purified(&mut self, p: Uint) -> bool {
+        c::DanceC::color(self, p) < 0
+    }
+}
+
+impl Solve for Problem {
+    #[inline]
+    fn enter_level(&mut self, i: Uint, l: Uint, xl: Uint) {
+        m::enter_level(self, i, l, xl);
+    }
+
+    #[inline]
+    fn prepare_to_branch(&mut self, i: Uint, l: Uint, xl: Uint) {
+        m::prepare_to_branch(self, i, l, xl);
+    }
+
+    #[inline]
+    fn try_item(&mut self, i: Uint, l: Uint, xl: Uint) -> bool {
+        m::try_item(self, i, l, xl)
+    }
+
+    #[inline]
+    fn try_again(&mut self, i: Uint, l: Uint, xl: &mut Uint) -> bool {
+        m::try_again(self, i, l, xl)
+    }
+
+    #[inline]
+    fn undo_item(&mut self, i: Uint, l: Uint, xl: Uint) {
+        m::undo_item(self, i, l, xl);
+    }
+
+    #[inline]
+    fn restore_item(&mut self, i: Uint, l: Uint, xl: Uint) {
+        m::restore_item(self, i, l, xl);
+    }
+
+    fn set_lookahead(&mut self, depth: usize, _: Uint) -> Result<()> {
+        m::set_lookahead(depth)
+    }
+}
+
+impl m::SolveM for Problem {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::solutions;
+
+    #[test]
+    fn test_mcc() {
+        use crate::choose::*;
+        use crate::{OptOrder, Solver};
+        let ps = vec![(1, 1), (1, 1), (2, 3)];
+        let items = m::INodes::new(ps, [1, 1], ItemOrder::Seq);
+        let os = vec![
+            vec![(0, 0), (1, 0), (3, 0), (4, 0)],
+            vec![(0, 0), (2, 0), (3, 1), (4, 1)],
+            vec![(2, 0), (3, 0)],
+            vec![(1, 0), (3, 1)],
+            vec![(2, 0), (4, 1)],
+        ];
+        let opts = c::ONodes::new(5, 3, &os, OptOrder::Seq);
+        let items_init = items.clone();
+        let opts_init = opts.clone();
+        let mut problem = Problem::new(items, opts);
+        let mut solver = Solver::new(&mut problem);
+        let mut solutions: Vec<Vec<Int>> = Vec::new();
+        let mut expected = vec![vec![1, 3, 4]];
+        let mut i = 0;
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        while solver.next_solution(&mut chooser) {
+            assert!(i <= expected.len(), "too many solutions");
+            solver.fmt_solution();
+            solver.o.sort();
+            solutions.push(solver.o.clone());
+            i += 1;
+        }
+        solutions.sort();
+        expected.sort();
+        assert_eq!(solutions, expected, "wrong solutions");
+        assert_eq!(solver.problem.items, items_init, "items not backtracked");
+        assert_eq!(solver.problem.opts, opts_init, "options not backtracked");
+        assert!(
+            solver.l == 0 && solver.restart == false,
+            "initial state not restored"
+        );
+    }
+
+    #[test]
+    fn test_uses() {
+        use crate::OptOrder;
+        let spec_str = "
+2|p q | x
+2|p x:A
+p x:B
+q x:A
+q x:B
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let mut problem =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        assert_eq!(solutions(&mut problem), vec![vec![0, 0, 2]]);
+    }
+
+    #[test]
+    fn test_secondary_bounds() {
+        use crate::OptOrder;
+        // Colored uses of n don't count against its capacity of 1
+        let spec_str = "
+a b c | 1|n
+a n:X
+b n:X
+c n
+c
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let mut problem =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        assert_eq!(solutions(&mut problem), vec![vec![0, 1, 3]]);
+    }
+}
@@ -0,0 +1,586 @@
+use std::fmt::Debug;
+use std::ops::{Deref, DerefMut};
+
+use anyhow::{Result, bail};
+
+use crate::{Dance, Int, Items, Opts, Uint};
+
+// Node storage. It holds exactly its nodes and indexes are checked, so a
+// bad link panics. With the "masked-index" feature, storage is padded to a
+// power of two and indexes are masked rather than checked: every index is
+// in range whatever the links hold, and a bad link reads the wrong node,
+// for up to twice the memory. Either way, the links are checked once when a
+// problem is built, and indexes are checked before masking in debug builds.
+#[derive(Clone, Debug)]
+pub struct Nodes<T> {
+    data: Vec<T>,
+    len: usize,
+    // Always data.len() - 1
+    #[cfg(feature = "masked-index")]
+    mask: usize,
+}
+
+impl<T: Clone + Default> Default for Nodes<T> {
+    fn default() -> Nodes<T> {
+        Nodes::from_vec(Vec::new())
+    }
+}
+
+impl<T: Clone + Default> Nodes<T> {
+    #[cfg(not(feature = "masked-index"))]
+    pub fn from_vec(data: Vec<T>) -> Nodes<T> {
+        let len = data.len();
+        Nodes { data, len }
+    }
+
+    #[cfg(feature = "masked-index")]
+    pub fn from_vec(mut data: Vec<T>) -> Nodes<T> {
+        let len = data.len();
+        data.resize(len.next_power_of_two().max(1), Default::default());
+        let mask = data.len() - 1;
+        Nodes { data, len, mask }
+    }
+
+    #[cfg(not(feature = "masked-index"))]
+    #[inline]
+    pub fn get_mut(&mut self, i: Uint) -> &mut T {
+        &mut self.data[i as usize]
+    }
+
+    #[cfg(feature = "masked-index")]
+    #[inline]
+    pub fn get_mut(&mut self, i: Uint) -> &mut T {
+        debug_assert!((i as usize) < self.len, "Node {} out of range", i);
+        let i = i as usize & self.mask;
+        // SAFETY: data.len() is mask + 1, so the masked index is in range
+        unsafe { self.data.get_unchecked_mut(i) }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // The number of nodes that fit without growing.
+    #[cfg(not(feature = "masked-index"))]
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    #[cfg(feature = "masked-index")]
+    pub fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    // Makes room for exactly additional more nodes.
+    #[cfg(not(feature = "masked-index"))]
+    pub fn reserve(&mut self, additional: usize) {
+        self.data.reserve_exact(additional);
+    }
+
+    // Makes room for at least additional more nodes, rounded up to a power
+    // of two.
+    #[cfg(feature = "masked-index")]
+    pub fn reserve(&mut self, additional: usize) {
+        let size = (self.len + additional).next_power_of_two();
+        if size > self.data.len() {
+            self.data.resize(size, Default::default());
+            self.mask = size - 1;
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.data[..self.len]
+    }
+
+    pub fn push(&mut self, node: T) {
+        self.insert(self.len, node);
+    }
+
+    pub fn insert(&mut self, i: usize, node: T) {
+        self.insert_many(i, 1, node);
+    }
+
+    // Inserts k copies of a node before node i, moving the nodes after it
+    // only once.
+    #[cfg(not(feature = "masked-index"))]
+    pub fn insert_many(&mut self, i: usize, k: usize, node: T) {
+        self.data.splice(i..i, std::iter::repeat_n(node, k));
+        self.len += k;
+    }
+
+    #[cfg(feature = "masked-index")]
+    pub fn insert_many(&mut self, i: usize, k: usize, node: T) {
+        assert!(i <= self.len);
+        if self.len + k > self.data.len() {
+            let size = (self.len + k).next_power_of_two();
+            self.data.resize(size, Default::default());
+            self.mask = size - 1;
+        }
+        self.data[i..self.len + k].rotate_right(k);
+        self.data[i..i + k].fill(node);
+        self.len += k;
+    }
+}
+
+impl<T: Clone + Default + PartialEq> PartialEq for Nodes<T> {
+    fn eq(&self, other: &Nodes<T>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T: Clone + Default + Eq> Eq for Nodes<T> {}
+
+// Conversion of a link between the type it is stored in and the type it is
+// used as.
+pub trait Narrowing<T>: Copy {
+    fn narrow(v: T) -> Self;
+    fn widen(self) -> T;
+}
+
+// The integer type that a problem stores the links of its option nodes in.
+// Links are always read and written as Uint and Int: directly for Uint, and
+// through a Narrow for the narrower types, which halve or quarter the memory
+// of problems small enough for them.
+pub trait Link: Narrowing<Uint> + Default + Debug + Eq + 'static {
+    type Signed: Narrowing<Int> + Default + Debug + Eq + 'static;
+    type Ref<'a>: DerefMut<Target = Uint>;
+    type SignedRef<'a>: DerefMut<Target = Int>;
+
+    // The most nodes a problem can have with links of this type, so that
+    // every node index and item length fits in Signed.
+    const MAX_NODES: usize;
+
+    fn get(slot: &mut Self) -> Self::Ref<'_>;
+    fn get_signed(slot: &mut Self::Signed) -> Self::SignedRef<'_>;
+}
+
+impl Narrowing<Uint> for Uint {
+    #[inline]
+    fn narrow(v: Uint) -> Uint {
+        v
+    }
+
+    #[inline]
+    fn widen(self) -> Uint {
+        self
+    }
+}
+
+impl Narrowing<Int> for Int {
+    #[inline]
+    fn narrow(v: Int) -> Int {
+        v
+    }
+
+    #[inline]
+    fn widen(self) -> Int {
+        self
+    }
+}
+
+impl Link for Uint {
+    type Signed = Int;
+    type Ref<'a> = &'a mut Uint;
+    type SignedRef<'a> = &'a mut Int;
+
+    const MAX_NODES: usize = Int::MAX as usize;
+
+    #[inline]
+    fn get(slot: &mut Uint) -> &mut Uint {
+        slot
+    }
+
+    #[inline]
+    fn get_signed(slot: &mut Int) -> &mut Int {
+        slot
+    }
+}
+
+// A link stored in a narrower type, widened to be read and written. It is
+// narrowed back into its slot when dropped, if it was borrowed mutably.
+pub struct Narrow<'a, S: Narrowing<T>, T: Copy> {
+    slot: &'a mut S,
+    value: T,
+    dirty: bool,
+}
+
+impl<'a, S: Narrowing<T>, T: Copy> Narrow<'a, S, T> {
+    #[inline]
+    fn new(slot: &'a mut S) -> Narrow<'a, S, T> {
+        let value = slot.widen();
+        Narrow { slot, value, dirty: false }
+    }
+}
+
+impl<S: Narrowing<T>, T: Copy> Deref for Narrow<'_, S, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<S: Narrowing<T>, T: Copy> DerefMut for Narrow<'_, S, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        self.dirty = true;
+        &mut self.value
+    }
+}
+
+impl<S: Narrowing<T>, T: Copy> Drop for Narrow<'_, S, T> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.dirty {
+            *self.slot = S::narrow(self.value);
+        }
+    }
+}
+
+macro_rules! narrow_link {
+    ($u:ty, $i:ty) => {
+        impl Narrowing<Uint> for $u {
+            #[inline]
+            fn narrow(v: Uint) -> $u {
+                debug_assert!(v <= <$u>::MAX as Uint, "Link {} too wide", v);
+                v as $u
+            }
+
+            #[inline]
+            fn widen(self) -> Uint {
+                self as Uint
+            }
+        }
+
+        impl Narrowing<Int> for $i {
+            #[inline]
+            fn narrow(v: Int) -> $i {
+                debug_assert!(
+                    v >= <$i>::MIN as Int && v <= <$i>::MAX as Int,
+                    "Link {} too wide",
+                    v
+                );
+                v as $i
+            }
+
+            #[inline]
+            fn widen(self) -> Int {
+                self as Int
+            }
+        }
+
+        impl Link for $u {
+            type Signed = $i;
+            type Ref<'a> = Narrow<'a, $u, Uint>;
+            type SignedRef<'a> = Narrow<'a, $i, Int>;
+
+            const MAX_NODES: usize = <$i>::MAX as usize;
+
+            #[inline]
+            fn get(slot: &mut $u) -> Narrow<'_, $u, Uint> {
+                Narrow::new(slot)
+            }
+
+            #[inline]
+            fn get_signed(slot: &mut $i) -> Narrow<'_, $i, Int> {
+                Narrow::new(slot)
+            }
+        }
+    };
+}
+
+narrow_link!(u16, i16);
+#[cfg(not(feature = "32-bit"))]
+narrow_link!(u32, i32);
+
+// The hot fields of option nodes, each in an array of its own, so that
+// walking a list only brings its links into the cache. For item headers,
+// top holds the length of the list. The "aos-links" feature keeps the
+// fields of each node together instead. The links are stored as S.
+#[cfg(not(feature = "aos-links"))]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Links<S: Link = Uint> {
+    top: Nodes<S::Signed>,
+    up: Nodes<S>,
+    down: Nodes<S>,
+}
+
+#[cfg(not(feature = "aos-links"))]
+impl<S: Link> Links<S> {
+    pub fn new(size: usize) -> Links<S> {
+        assert!(size <= S::MAX_NODES, "Too many nodes for the link type");
+        Links {
+            top: Nodes::from_vec(vec![Default::default(); size]),
+            up: Nodes::from_vec(vec![Default::default(); size]),
+            down: Nodes::from_vec(vec![Default::default(); size]),
+        }
+    }
+
+    #[inline]
+    pub fn top(&mut self, i: Uint) -> S::SignedRef<'_> {
+        S::get_signed(self.top.get_mut(i))
+    }
+
+    #[inline]
+    pub fn up(&mut self, i: Uint) -> S::Ref<'_> {
+        S::get(self.up.get_mut(i))
+    }
+
+    #[inline]
+    pub fn down(&mut self, i: Uint) -> S::Ref<'_> {
+        S::get(self.down.get_mut(i))
+    }
+
+    // The (top, up, down) fields of node i. The three arrays always have
+    // the same length, so the index is checked once for all of them.
+    #[cfg(not(feature = "masked-index"))]
+    #[inline]
+    pub fn node(&mut self, i: Uint) -> (Int, Uint, Uint) {
+        let i = i as usize;
+        assert!(i < self.len(), "Node {} out of range", i);
+        // SAFETY: up and down are as long as top
+        unsafe {
+            let top = *self.top.data.get_unchecked(i);
+            let up = *self.up.data.get_unchecked(i);
+            let down = *self.down.data.get_unchecked(i);
+            (top.widen(), up.widen(), down.widen())
+        }
+    }
+
+    #[cfg(feature = "masked-index")]
+    #[inline]
+    pub fn node(&mut self, i: Uint) -> (Int, Uint, Uint) {
+        let top = self.top.get_mut(i).widen();
+        let up = self.up.get_mut(i).widen();
+        (top, up, self.down.get_mut(i).widen())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.top.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.top.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.top.capacity()
+    }
+
+    // Inserts k nodes of zeros before node i.
+    pub fn insert(&mut self, i: usize, k: usize) {
+        assert!(self.len() + k <= S::MAX_NODES, "Too many nodes");
+        self.top.insert_many(i, k, Default::default());
+        self.up.insert_many(i, k, Default::default());
+        self.down.insert_many(i, k, Default::default());
+    }
+
+    // The (top, up, down) fields of each node.
+    pub fn fields(&self) -> Vec<(Int, Uint, Uint)> {
+        let top = self.top.as_slice().iter();
+        let up = self.up.as_slice().iter();
+        let down = self.down.as_slice().iter();
+        top.zip(up)
+            .zip(down)
+            .map(|((&t, &u), &d)| (t.widen(), u.widen(), d.widen()))
+            .collect()
+    }
+}
+
+#[cfg(feature = "aos-links")]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Links<S: Link = Uint> {
+    nodes: Nodes<(S::Signed, S, S)>,
+}
+
+#[cfg(feature = "aos-links")]
+impl<S: Link> Links<S> {
+    pub fn new(size: usize) -> Links<S> {
+        assert!(size <= S::MAX_NODES, "Too many nodes for the link type");
+        Links {
+            nodes: Nodes::from_vec(vec![Default::default(); size]),
+        }
+    }
+
+    #[inline]
+    pub fn top(&mut self, i: Uint) -> S::SignedRef<'_> {
+        S::get_signed(&mut self.nodes.get_mut(i).0)
+    }
+
+    #[inline]
+    pub fn up(&mut self, i: Uint) -> S::Ref<'_> {
+        S::get(&mut self.nodes.get_mut(i).1)
+    }
+
+    #[inline]
+    pub fn down(&mut self, i: Uint) -> S::Ref<'_> {
+        S::get(&mut self.nodes.get_mut(i).2)
+    }
+
+    #[inline]
+    pub fn node(&mut self, i: Uint) -> (Int, Uint, Uint) {
+        let (top, up, down) = *self.nodes.get_mut(i);
+        (top.widen(), up.widen(), down.widen())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
+
+    // Inserts k nodes of zeros before node i.
+    pub fn insert(&mut self, i: usize, k: usize) {
+        assert!(self.len() + k <= S::MAX_NODES, "Too many nodes");
+        self.nodes.insert_many(i, k, Default::default());
+    }
+
+    // The (top, up, down) fields of each node.
+    pub fn fields(&self) -> Vec<(Int, Uint, Uint)> {
+        let nodes = self.nodes.as_slice().iter();
+        nodes
+            .map(|&(t, u, d)| (t.widen(), u.widen(), d.widen()))
+            .collect()
+    }
+}
+
+// Checks that every link of a problem is in range, that the item and
+// option lists are properly doubly linked, and that the length of each item
+// counts every use left of the options in its list.
+pub fn check_links<D: Dance + ?Sized>(dance: &mut D) -> Result<()> {
+    let n = dance.items().count();
+    let size = dance.opts().size();
+    for h in [0, n + 1] {
+        let mut i = h;
+        for _ in 0..=n + 1 {
+            let r = *dance.rlink(i);
+            if r > n + 1 || *dance.llink(r) != i {
+                bail!("Bad link from item {}", i);
+            }
+            i = r;
+            if i == h {
+                break;
+            }
+        }
+        if i != h {
+            bail!("Item list {} doesn't close", h);
+        }
+    }
+    for i in 1..=n {
+        let mut p = i;
+        let mut len = 0;
+        for _ in 0..size {
+            let d = *dance.dlink(p);
+            if d >= size || *dance.ulink(d) != p {
+                bail!("Bad link from node {}", p);
+            }
+            p = d;
+            if p == i {
+                break;
+            }
+            if p <= n + 1 || *dance.top(p) != i as Int {
+                bail!("Node {} is in the list of item {}", p, i);
+            }
+            len += dance.uses_left(p);
+        }
+        if p != i || *dance.len(i) != len {
+            bail!("Option list of item {} is broken", i);
+        }
+    }
+    for p in (n + 1)..size {
+        if *dance.ulink(p) >= size || *dance.dlink(p) >= size {
+            bail!("Bad link from node {}", p);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ItemOrder, OptOrder, x};
+
+    #[test]
+    fn test_nodes() {
+        let mut nodes = Nodes::from_vec(vec![1, 2, 3]);
+        assert_eq!(nodes.len(), 3);
+        nodes.push(4);
+        nodes.push(5);
+        nodes.insert(0, 0);
+        assert_eq!(nodes.as_slice(), &[0, 1, 2, 3, 4, 5]);
+        assert_eq!(*nodes.get_mut(5), 5);
+        assert_eq!(nodes, Nodes::from_vec(vec![0, 1, 2, 3, 4, 5]));
+        let mut nodes = Nodes::default();
+        nodes.reserve(3);
+        nodes.push(1);
+        assert_eq!(nodes.as_slice(), &[1]);
+        if cfg!(feature = "masked-index") {
+            assert_eq!(Nodes::from_vec(vec![0; 5]).capacity(), 8);
+        } else {
+            assert_eq!(Nodes::from_vec(vec![0; 5]).capacity(), 5);
+        }
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, not(feature = "masked-index")))]
+    #[should_panic]
+    fn test_out_of_range() {
+        let mut nodes = Nodes::from_vec(vec![1, 2, 3]);
+        nodes.get_mut(3);
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, not(feature = "masked-index")))]
+    #[should_panic]
+    fn test_node_out_of_range() {
+        let mut links = Links::<Uint>::new(3);
+        *links.top(2) = -1;
+        *links.up(2) = 1;
+        *links.down(2) = 0;
+        assert_eq!(links.node(2), (-1, 1, 0));
+        links.node(3);
+    }
+
+    #[test]
+    fn test_narrow_links() {
+        let mut links = Links::<u16>::new(3);
+        *links.top(2) = -1;
+        *links.up(2) += 2;
+        *links.down(1) = 2;
+        assert_eq!(*links.down(2), 0);
+        assert_eq!(links.node(2), (-1, 2, 0));
+        assert_eq!(links.fields(), vec![(0, 0, 0), (0, 0, 2), (-1, 2, 0)]);
+        assert_eq!(Links::<Uint>::new(3).fields(), vec![(0, 0, 0); 3]);
+    }
+
+    #[test]
+    fn test_check_links() {
+        let os: Vec<Vec<Uint>> = vec![vec![0, 1], vec![1, 2], vec![0, 2]];
+        let mut problem =
+            x::make_problem(3, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        assert!(check_links(&mut problem).is_ok());
+        let p = *problem.dlink(1);
+        *problem.ulink(p) = 40;
+        assert!(check_links(&mut problem).is_err());
+        *problem.ulink(p) = 1;
+        *problem.rlink(2) = 1;
+        assert!(check_links(&mut problem).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{Int, Uint};
+
+// Callbacks made by the solver as the search proceeds. Each is given the
+// current level and the number of updates made so far; the default methods
+// do nothing, so an observer only needs to implement the events it wants.
+pub trait Observer {
+    fn enter_level(&mut self, _l: Uint, _updates: isize) {}
+
+    // Item i was chosen for branching on level l; it has the given number
+    // of options to try.
+    fn choose(&mut self, _l: Uint, _i: Uint, _degree: Int, _updates: isize) {}
+
+    // The option containing node xl was selected on level l.
+    fn try_option(&mut self, _l: Uint, _xl: Uint, _updates: isize) {}
+
+    // Level l has run out of options and the search is returning to it.
+    fn backtrack(&mut self, _l: Uint, _updates: isize) {}
+
+    // A solution was found with l selected options.
+    fn solution(&mut self, _l: Uint, _updates: isize) {}
+}
+
+#[derive(Default)]
+pub struct NoObserver;
+
+impl Observer for NoObserver {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::choose::*;
+    use crate::testing::xc_problem;
+    use crate::{ItemOrder, OptOrder, Solve, Solver, x};
+
+    #[derive(Default)]
+    struct Counter {
+        levels: Vec<Uint>,
+        chosen: Vec<(Uint, Uint, Int)>,
+        tried: usize,
+        backtracks: usize,
+        solutions: Vec<Uint>,
+        updates: isize,
+    }
+
+    impl Observer for Counter {
+        fn enter_level(&mut self, l: Uint, _updates: isize) {
+            self.levels.push(l);
+        }
+
+        fn choose(&mut self, l: Uint, i: Uint, degree: Int, _updates: isize) {
+            self.chosen.push((l, i, degree));
+        }
+
+        fn try_option(&mut self, _l: Uint, _xl: Uint, updates: isize) {
+            self.tried += 1;
+            self.updates = updates;
+        }
+
+        fn backtrack(&mut self, _l: Uint, _updates: isize) {
+            self.backtracks += 1;
+        }
+
+        fn solution(&mut self, l: Uint, _updates: isize) {
+            self.solutions.push(l);
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let mut problem = xc_problem();
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        let mut solver =
+            Solver::with_observer(&mut problem, Counter::default());
+        while solver.next_solution(&mut chooser) {}
+        let updates = solver.get_updates();
+        let profile = solver.get_profile().to_vec();
+        let counter = solver.observer();
+        assert_eq!(counter.solutions, vec![3]);
+        assert_eq!(counter.chosen[0], (0, 1, 2));
+        assert_eq!(
+            counter.chosen.len(),
+            profile.iter().sum::<usize>(),
+            "one choice per node"
+        );
+        assert_eq!(counter.levels.len(), counter.chosen.len());
+        assert_eq!(counter.levels.iter().max(), Some(&2));
+        assert_eq!(counter.tried, counter.backtracks);
+        assert!(counter.updates > 0 && counter.updates <= updates);
+    }
+
+    #[test]
+    fn test_pruned_degree() {
+        // Langford pairs for n = 4, which probing prunes
+        let mut os: Vec<Vec<Uint>> = Vec::new();
+        for k in 1..=4 {
+            for p in 0..8 - k - 1 {
+                os.push(vec![k - 1, 4 + p, 4 + p + k + 1]);
+            }
+        }
+        for depth in [0, 1] {
+            let mut problem =
+                x::make_problem(4, 8, &os, OptOrder::Seq, ItemOrder::Seq);
+            problem.set_lookahead(depth, 100).unwrap();
+            let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+            let mut solver =
+                Solver::with_observer(&mut problem, Counter::default());
+            while solver.next_solution(&mut chooser) {}
+            let counter = solver.observer();
+            assert_eq!(counter.solutions.len(), 2);
+            // Every option reported is tried
+            let degrees: Int = counter.chosen.iter().map(|c| c.2).sum();
+            assert_eq!(degrees as usize, counter.tried, "depth {}", depth);
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use crate::step::{ExactCover, deselect, opt_number, select};
+use crate::{Int, Items, Opts, Uint};
+
+// The best partial solution found: non-conflicting options, the total
+// weight of the primary items they cover, and the primary items they leave
+// uncovered, numbered from 0.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PartialCover {
+    pub opts: Vec<Int>,
+    pub weight: u64,
+    pub uncovered: Vec<Uint>,
+}
+
+// Finds a set of options covering primary items of the greatest total
+// weight, treating each primary item as optional. Items are left uncovered
+// by covering them without an option, which removes their options, and a
+// branch is cut off once the weight of the items that still have options
+// cannot beat the best cover found.
+pub struct MaxCover<'a, D> {
+    dance: &'a mut D,
+    // The option number of each node, or -1 for headers and spacers
+    opt_of: Vec<Int>,
+    // Indexed by item, from 1
+    weights: Vec<u64>,
+    selected: Vec<Uint>,
+    skipped: Vec<Uint>,
+    weight: u64,
+    best: Option<PartialCover>,
+    nodes: usize,
+}
+
+impl<'a, D: ExactCover> MaxCover<'a, D> {
+    pub fn new(dance: &'a mut D) -> MaxCover<'a, D> {
+        let n = dance.items().count();
+        let np = dance.items().primary();
+        let size = dance.opts().size();
+        let mut opt_of = vec![-1; size as usize];
+        for p in (n + 2)..size {
+            if *dance.top(p) > 0 {
+                opt_of[p as usize] = opt_number(dance, p);
+            }
+        }
+        MaxCover {
+            dance,
+            opt_of,
+            weights: vec![1; (np + 1) as usize],
+            selected: Vec::new(),
+            skipped: Vec::new(),
+            weight: 0,
+            best: None,
+            nodes: 0,
+        }
+    }
+
+    // Sets the weight of each primary item, in the order of the spec.
+    pub fn with_weights(mut self, weights: &[u64]) -> MaxCover<'a, D> {
+        assert_eq!(weights.len() + 1, self.weights.len());
+        self.weights[1..].copy_from_slice(weights);
+        self
+    }
+
+    pub fn solve(&mut self) -> PartialCover {
+        self.best = None;
+        self.nodes = 0;
+        self.search();
+        self.best.take().unwrap()
+    }
+
+    pub fn get_nodes(&self) -> usize {
+        self.nodes
+    }
+
+    fn search(&mut self) {
+        self.nodes += 1;
+        // The weight that can still be covered, and the item to branch on
+        let mut open = 0;
+        let mut min = Int::MAX;
+        let mut i = 0;
+        let mut p = *self.dance.rlink(0);
+        while p != 0 {
+            let len = *self.dance.len(p);
+            if len > 0 {
+                open += self.weights[p as usize];
+            }
+            if len < min {
+                min = len;
+                i = p;
+            }
+            p = *self.dance.rlink(p);
+        }
+        let best = self.best.as_ref().map(|b| b.weight);
+        if best.is_some_and(|b| self.weight + open <= b) {
+            return;
+        }
+        if i == 0 {
+            self.record();
+            return;
+        }
+        let mut p = *self.dance.dlink(i);
+        while p != i {
+            let w = self.option_weight(p);
+            select(self.dance, p);
+            self.selected.push(p);
+            self.weight += w;
+            self.search();
+            self.weight -= w;
+            self.selected.pop();
+            deselect(self.dance, p);
+            if self.is_complete() {
+                return;
+            }
+            p = *self.dance.dlink(p);
+        }
+        self.dance.cover(i);
+        self.skipped.push(i);
+        self.search();
+        self.skipped.pop();
+        self.dance.uncover(i);
+    }
+
+    // The weight of the primary items of the option of node p.
+    fn option_weight(&mut self, p: Uint) -> u64 {
+        let np = self.dance.items().primary();
+        let mut w = 0;
+        let mut q = p;
+        loop {
+            let j = *self.dance.top(q);
+            if j <= 0 {
+                q = *self.dance.ulink(q);
+            } else {
+                if j as Uint <= np {
+                    w += self.weights[j as usize];
+                }
+                q += 1;
+            }
+            if q == p {
+                break;
+            }
+        }
+        w
+    }
+
+    fn record(&mut self) {
+        let mut uncovered =
+            self.skipped.iter().map(|&i| i - 1).collect::<Vec<_>>();
+        uncovered.sort();
+        let opts = self.selected.iter().map(|&p| self.opt_of[p as usize]);
+        self.best = Some(PartialCover {
+            opts: opts.collect(),
+            weight: self.weight,
+            uncovered,
+        });
+    }
+
+    // Whether the best cover covers everything, so nothing can beat it.
+    fn is_complete(&self) -> bool {
+        self.best.as_ref().is_some_and(|b| b.uncovered.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_restored;
+    use crate::{ItemOrder, OptOrder, Spec, c, x};
+
+    #[test]
+    fn test_max_cover() {
+        let os: Vec<Vec<Uint>> = vec![vec![0, 1], vec![1, 2, 4], vec![2, 3]];
+        let mut problem =
+            x::make_problem(5, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        let init = x::make_problem(5, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        let mut max = MaxCover::new(&mut problem);
+        let best = max.solve();
+        assert_eq!(best.opts, vec![0, 2]);
+        assert_eq!(best.weight, 4);
+        assert_eq!(best.uncovered, vec![4]);
+
+        let mut max =
+            MaxCover::new(&mut problem).with_weights(&[1, 1, 1, 1, 5]);
+        let best = max.solve();
+        assert_eq!(best.opts, vec![1]);
+        assert_eq!(best.weight, 7);
+        assert_eq!(best.uncovered, vec![0, 3]);
+        assert_restored(&mut problem, &init);
+    }
+
+    #[test]
+    fn test_board() {
+        // Dominoes on a 3x3 board always leave a corner or the center
+        let mut spec_str = String::new();
+        for r in 0..3 {
+            for c in 0..3 {
+                spec_str += &format!("r{}c{} ", r, c);
+            }
+        }
+        spec_str += "\n";
+        for r in 0..3 {
+            for c in 0..3 {
+                if c < 2 {
+                    spec_str += &format!("r{}c{} r{}c{}\n", r, c, r, c + 1);
+                }
+                if r < 2 {
+                    spec_str += &format!("r{}c{} r{}c{}\n", r, c, r + 1, c);
+                }
+            }
+        }
+        let spec = Spec::new(&spec_str, false).unwrap();
+        let mut problem =
+            x::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        let best = MaxCover::new(&mut problem).solve();
+        assert_eq!(best.opts.len(), 4);
+        assert_eq!(best.weight, 8);
+        assert_eq!(best.uncovered.len(), 1);
+        assert!([0, 2, 4, 6, 8].contains(&best.uncovered[0]));
+
+        let mut weights = vec![10; 9];
+        weights[4] = 1;
+        let mut max = MaxCover::new(&mut problem).with_weights(&weights);
+        assert_eq!(max.solve().uncovered, vec![4]);
+    }
+
+    #[test]
+    fn test_colors() {
+        let spec_str = "
+p q r | x
+p x:A
+q x:B
+r x:A
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let mut problem =
+            c::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        let best = MaxCover::new(&mut problem).with_weights(&[1, 1, 3]).solve();
+        assert_eq!(best.weight, 4);
+        assert_eq!(best.uncovered, vec![1]);
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+
+use crate::choose::Choose;
+use crate::{Int, Rng, Solve, Solver};
+
+// The Luby sequence 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, ... for i = 1, 2, 3, ...
+pub fn luby(i: u64) -> u64 {
+    assert!(i > 0);
+    let mut i = i;
+    loop {
+        let k = 64 - i.leading_zeros();
+        if i == (1 << k) - 1 {
+            return 1 << (k - 1);
+        }
+        i -= (1 << (k - 1)) - 1;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RestartReport {
+    pub solution: Option<Vec<Int>>,
+    pub restarts: usize,
+    pub nodes: usize,
+    pub updates: isize,
+}
+
+impl fmt::Display for RestartReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = if self.solution.is_some() {
+            "solved"
+        } else {
+            "no solution"
+        };
+        write!(
+            f,
+            "{} after {} restarts, {} nodes, {} updates",
+            result, self.restarts, self.nodes, self.updates
+        )
+    }
+}
+
+// Searches for a first solution in runs whose node budgets are unit times
+// the Luby sequence. Each run gets a chooser made from a new seed, drawn
+// from an Rng started with seed; the problem is restored between runs and
+// after the search. Gives up after max_runs runs.
+pub fn luby_restarts<P, C, F>(
+    problem: &mut P, unit: usize, seed: u32, max_runs: usize,
+    mut make_chooser: F,
+) -> RestartReport
+where
+    P: Solve,
+    C: Choose<P>,
+    F: FnMut(u32) -> C,
+{
+    let mut seeds = Rng::new(seed);
+    let mut report = RestartReport::default();
+    for run in 1..=max_runs {
+        let mut chooser = make_chooser(seeds.next());
+        *problem.updates() = 0;
+        let mut solver = Solver::new(problem);
+        solver.set_node_limit(unit.saturating_mul(luby(run as u64) as usize));
+        let found = solver.next_solution(&mut chooser);
+        if found {
+            report.solution = Some(solver.fmt_solution().to_vec());
+        }
+        report.nodes += solver.get_nodes();
+        report.updates += solver.get_updates();
+        let finished = found || !solver.limit_reached();
+        solver.reset();
+        if finished {
+            break;
+        }
+        report.restarts += 1;
+    }
+    *problem.updates() = 0;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::choose::*;
+    use crate::testing::{assert_restored, xc_problem};
+
+    #[test]
+    fn test_luby() {
+        let seq = (1..=15).map(luby).collect::<Vec<_>>();
+        assert_eq!(seq, vec![1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn test_restarts() {
+        let mut problem = xc_problem();
+        let init = xc_problem();
+        let report = luby_restarts(&mut problem, 1, 1234, 100, |seed| {
+            mrv_chooser(prefer_any(), rnd_tiebreak(seed))
+        });
+        let mut solution = report.solution.clone().unwrap();
+        solution.sort();
+        assert_eq!(solution, vec![0, 3, 4]);
+        assert!(report.restarts > 0);
+        assert!(report.nodes > report.restarts);
+        assert_eq!(problem, init, "problem not restored");
+
+        let report = luby_restarts(&mut problem, 1, 1234, 2, |seed| {
+            mrv_chooser(prefer_any(), rnd_tiebreak(seed))
+        });
+        assert!(report.solution.is_none());
+        assert_eq!(report.restarts, 2);
+        assert_eq!(report.nodes, 2);
+        assert_eq!(problem, init, "problem not restored");
+    }
+
+    #[test]
+    fn test_resume() {
+        let mut problem = xc_problem();
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        let mut solver = Solver::new(&mut problem);
+        solver.set_node_limit(2);
+        assert!(!solver.next_solution(&mut chooser));
+        assert!(solver.limit_reached());
+        solver.set_node_limit(usize::MAX);
+        assert!(solver.next_solution(&mut chooser));
+        assert!(!solver.limit_reached());
+        let mut solution = solver.fmt_solution().to_vec();
+        solution.sort();
+        assert_eq!(solution, vec![0, 3, 4]);
+        assert!(!solver.next_solution(&mut chooser));
+        assert!(!solver.limit_reached());
+        assert_restored(&mut problem, &xc_problem());
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use crate::step::{ExactCover, deselect, opt_number, select};
+use crate::{Int, Items, Opts, Rng, Uint};
+
+// Samples solutions uniformly at random by counting the solutions below
+// each branch exactly. Counts are memoized on the uncovered primary items
+// and the options that are still live, which together determine what is
+// left of the problem.
+pub struct Sampler<'a, D> {
+    dance: &'a mut D,
+    // The option number of each node, or -1 for headers and spacers
+    opt_of: Vec<Int>,
+    memo: HashMap<Vec<u64>, u128>,
+    rng: Rng,
+}
+
+impl<'a, D: ExactCover> Sampler<'a, D> {
+    pub fn new(dance: &'a mut D, seed: u32) -> Sampler<'a, D> {
+        let n = dance.items().count();
+        let size = dance.opts().size();
+        let mut opt_of = vec![-1; size as usize];
+        for p in (n + 2)..size {
+            if *dance.top(p) > 0 {
+                opt_of[p as usize] = opt_number(dance, p);
+            }
+        }
+        Sampler {
+            dance,
+            opt_of,
+            memo: HashMap::new(),
+            rng: Rng::new(seed),
+        }
+    }
+
+    // The number of solutions of the problem in its current state.
+    pub fn count(&mut self) -> u128 {
+        if *self.dance.rlink(0) == 0 {
+            return 1;
+        }
+        let key = self.key();
+        if let Some(&c) = self.memo.get(&key) {
+            return c;
+        }
+        let i = self.choose();
+        let mut total = 0;
+        let mut p = *self.dance.dlink(i);
+        while p != i {
+            select(self.dance, p);
+            total += self.count();
+            deselect(self.dance, p);
+            p = *self.dance.dlink(p);
+        }
+        self.memo.insert(key, total);
+        total
+    }
+
+    // Returns a solution chosen uniformly at random, with the options in
+    // the order they were selected, or None if there are no solutions.
+    pub fn sample(&mut self) -> Option<Vec<Int>> {
+        let mut total = self.count();
+        if total == 0 {
+            return None;
+        }
+        let mut selected = Vec::new();
+        while *self.dance.rlink(0) != 0 {
+            let mut r = uniform(&mut self.rng, total);
+            let i = self.choose();
+            let mut p = *self.dance.dlink(i);
+            loop {
+                select(self.dance, p);
+                let c = self.count();
+                if r < c {
+                    total = c;
+                    break;
+                }
+                r -= c;
+                deselect(self.dance, p);
+                p = *self.dance.dlink(p);
+            }
+            selected.push(p);
+        }
+        let solution = selected.iter().map(|&p| self.opt_of[p as usize]);
+        let solution = solution.collect();
+        for &p in selected.iter().rev() {
+            deselect(self.dance, p);
+        }
+        Some(solution)
+    }
+
+    // The uncovered primary item with the fewest options.
+    fn choose(&mut self) -> Uint {
+        let mut min = Int::MAX;
+        let mut i = 0;
+        let mut p = *self.dance.rlink(0);
+        while p != 0 {
+            let len = *self.dance.len(p);
+            if len < min {
+                min = len;
+                i = p;
+            }
+            p = *self.dance.rlink(p);
+        }
+        i
+    }
+
+    fn key(&mut self) -> Vec<u64> {
+        let np = self.dance.items().primary() as usize;
+        let m = *self.dance.opts().opt_count() as usize;
+        let mut key = vec![0u64; (np + m).div_ceil(64)];
+        let mut i = *self.dance.rlink(0);
+        while i != 0 {
+            let k = (i - 1) as usize;
+            key[k / 64] |= 1 << (k % 64);
+            let mut p = *self.dance.dlink(i);
+            while p != i {
+                let k = np + self.opt_of[p as usize] as usize;
+                key[k / 64] |= 1 << (k % 64);
+                p = *self.dance.dlink(p);
+            }
+            i = *self.dance.rlink(i);
+        }
+        key
+    }
+}
+
+// A uniform random number in [0, max).
+fn uniform(rng: &mut Rng, max: u128) -> u128 {
+    let zone = u128::MAX - (u128::MAX % max);
+    loop {
+        let mut r = 0u128;
+        for _ in 0..4 {
+            r = (r << 32) | rng.next() as u128;
+        }
+        if r < zone {
+            return r % max;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{XCC_SPEC, assert_restored, solutions};
+    use crate::{ItemOrder, OptOrder, Spec, c, x};
+
+    #[test]
+    fn test_uniform() {
+        let spec_str = "
+a b c d
+a b
+c d
+a c
+b d
+a
+b
+c
+d
+a b c d
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let mut problem =
+            x::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        let all = solutions(&mut problem);
+        assert_eq!(all.len(), 8);
+        let init = x::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+            .unwrap();
+        let mut sampler = Sampler::new(&mut problem, 12345);
+        assert_eq!(sampler.count(), 8);
+        let mut hits = vec![0; all.len()];
+        let n = 8000;
+        for _ in 0..n {
+            let mut solution = sampler.sample().unwrap();
+            solution.sort();
+            let k = all.iter().position(|s| *s == solution).unwrap();
+            hits[k] += 1;
+        }
+        for h in hits {
+            assert!((800..1200).contains(&h), "biased sample: {}", h);
+        }
+        assert_restored(&mut problem, &init);
+    }
+
+    #[test]
+    fn test_colors() {
+        let spec = Spec::new(XCC_SPEC, false).unwrap();
+        let mut problem =
+            c::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        let all = solutions(&mut problem);
+        let mut sampler = Sampler::new(&mut problem, 1);
+        assert_eq!(sampler.count(), all.len() as u128);
+        let mut solution = sampler.sample().unwrap();
+        solution.sort();
+        assert!(all.contains(&solution));
+    }
+
+    #[test]
+    fn test_no_solution() {
+        let os: Vec<Vec<Uint>> = vec![vec![0, 1], vec![1, 2]];
+        let mut problem =
+            x::make_problem(3, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        let mut sampler = Sampler::new(&mut problem, 1);
+        assert_eq!(sampler.count(), 0);
+        assert_eq!(sampler.sample(), None);
+    }
+}
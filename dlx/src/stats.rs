@@ -0,0 +1,167 @@
+use std::fmt;
+
+use crate::observe::Observer;
+use crate::{Int, Uint};
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LevelStats {
+    pub nodes: usize,
+    // The number of times an item with each branch degree was chosen
+    pub degrees: Vec<usize>,
+    pub solutions: usize,
+    pub dead_ends: usize,
+    pub updates: isize,
+}
+
+// Per-level statistics of a run, gathered by observing the solver.
+#[derive(Default)]
+pub struct SearchStats {
+    levels: Vec<LevelStats>,
+    last: isize,
+}
+
+impl SearchStats {
+    pub fn new() -> SearchStats {
+        Default::default()
+    }
+
+    pub fn levels(&self) -> &[LevelStats] {
+        &self.levels
+    }
+
+    fn level(&mut self, l: Uint) -> &mut LevelStats {
+        let l = l as usize;
+        if self.levels.len() <= l {
+            self.levels.resize(l + 1, Default::default());
+        }
+        &mut self.levels[l]
+    }
+
+    // Charges the updates made since the last event to level l.
+    fn spend(&mut self, l: Uint, updates: isize) {
+        let spent = updates - self.last;
+        self.last = updates;
+        self.level(l).updates += spent;
+    }
+}
+
+impl Observer for SearchStats {
+    fn choose(&mut self, l: Uint, _: Uint, degree: Int, updates: isize) {
+        self.spend(l, updates);
+        let level = self.level(l);
+        level.nodes += 1;
+        let d = degree as usize;
+        if level.degrees.len() <= d {
+            level.degrees.resize(d + 1, 0);
+        }
+        level.degrees[d] += 1;
+        if degree == 0 {
+            level.dead_ends += 1;
+        }
+    }
+
+    fn try_option(&mut self, l: Uint, _: Uint, updates: isize) {
+        self.spend(l, updates);
+    }
+
+    fn backtrack(&mut self, l: Uint, updates: isize) {
+        self.spend(l, updates);
+    }
+
+    fn solution(&mut self, l: Uint, _: isize) {
+        for k in 0..l {
+            self.level(k).solutions += 1;
+        }
+    }
+}
+
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>5} {:>10} {:>10} {:>10} {:>12}  degrees",
+            "level", "nodes", "solutions", "dead ends", "updates"
+        )?;
+        for (l, level) in self.levels.iter().enumerate() {
+            let degrees = level
+                .degrees
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| **c > 0)
+                .map(|(d, c)| format!("{}:{}", d, c))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(
+                f,
+                "{:>5} {:>10} {:>10} {:>10} {:>12}  {}",
+                l,
+                level.nodes,
+                level.solutions,
+                level.dead_ends,
+                level.updates,
+                degrees
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::choose::*;
+    use crate::testing::xc_problem;
+    use crate::{Int, ItemOrder, OptOrder, Solver, Spec, x};
+
+    #[test]
+    fn test_stats() {
+        let mut problem = xc_problem();
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        let mut solver =
+            Solver::with_observer(&mut problem, SearchStats::new());
+        while solver.next_solution(&mut chooser) {}
+        let profile = solver.get_profile().to_vec();
+        let updates = solver.get_updates();
+        let stats = solver.into_observer();
+        let levels = stats.levels();
+        let nodes = levels.iter().map(|s| s.nodes).collect::<Vec<_>>();
+        assert_eq!(nodes, profile);
+        assert_eq!(levels[0].degrees, vec![0, 0, 1]);
+        assert_eq!(levels[0].solutions, 1);
+        assert_eq!(levels[2].solutions, 1);
+        assert_eq!(levels.iter().map(|s| s.updates).sum::<isize>(), updates);
+        for level in levels {
+            assert_eq!(level.degrees.iter().sum::<usize>(), level.nodes);
+            assert_eq!(level.dead_ends, level.degrees[0]);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let spec_str = "
+a b c
+a b
+a c
+b c
+a
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let mut problem =
+            x::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        let mut solver =
+            Solver::with_observer(&mut problem, SearchStats::new());
+        let mut solutions: Vec<Vec<Int>> = Vec::new();
+        while solver.next_solution(&mut chooser) {
+            solutions.push(solver.fmt_solution().to_vec());
+        }
+        assert_eq!(solutions, vec![vec![2, 3]]);
+        let table = solver.into_observer().to_string();
+        let rows = table.lines().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[0].starts_with("level"));
+        assert!(rows[1].ends_with("  2:1"));
+        assert!(rows[2].ends_with("  0:1 1:1"));
+    }
+}
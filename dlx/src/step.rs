@@ -0,0 +1,355 @@
+use anyhow::{Result, anyhow};
+
+use crate::m::DanceM;
+use crate::nodes::Link;
+use crate::{Dance, Int, Items, Uint, opt_starts};
+use crate::{c, m, mc, x};
+
+// Selecting and deselecting options outside of a search. By default an
+// option covers each of its items once.
+pub trait Step: Dance + Sized {
+    // Selects the option of node p, which must be in the list of a primary
+    // item.
+    fn select(&mut self, p: Uint) {
+        select(self, p);
+    }
+
+    fn deselect(&mut self, p: Uint) {
+        deselect(self, p);
+    }
+
+    // How many more times the active primary item i has to be covered.
+    fn needed(&mut self, _i: Uint) -> Int {
+        1
+    }
+}
+
+impl<S: Link> Step for x::Problem<S> {}
+
+impl Step for c::Problem {}
+
+impl Step for m::Problem {
+    fn select(&mut self, p: Uint) {
+        m::select(p, self);
+    }
+
+    fn deselect(&mut self, p: Uint) {
+        m::deselect(p, self);
+    }
+
+    fn needed(&mut self, i: Uint) -> Int {
+        *self.bound(i) - self.slack(i)
+    }
+}
+
+impl Step for mc::Problem {
+    fn select(&mut self, p: Uint) {
+        m::select(p, self);
+    }
+
+    fn deselect(&mut self, p: Uint) {
+        m::deselect(p, self);
+    }
+
+    fn needed(&mut self, i: Uint) -> Int {
+        *self.bound(i) - self.slack(i)
+    }
+}
+
+// Problems where selecting an option covers each of its items exactly
+// once, as counting and sampling solutions option by option assumes.
+pub trait ExactCover: Step {}
+
+impl<S: Link> ExactCover for x::Problem<S> {}
+
+impl ExactCover for c::Problem {}
+
+pub struct Stepper<'a, D> {
+    dance: &'a mut D,
+    // The first node of each option, or 0 if the option was skipped
+    starts: Vec<Uint>,
+    // The selected options, and the primary node each was selected from
+    stack: Vec<(Int, Uint)>,
+}
+
+impl<'a, D: Step> Stepper<'a, D> {
+    pub fn new(dance: &'a mut D) -> Stepper<'a, D> {
+        let starts = opt_starts(dance);
+        Stepper { dance, starts, stack: Vec::new() }
+    }
+
+    pub(crate) fn dance(&mut self) -> &mut D {
+        self.dance
+    }
+
+    pub fn selected(&self) -> Vec<Int> {
+        self.stack.iter().map(|s| s.0).collect()
+    }
+
+    // Returns false, leaving the problem unchanged, if the option has been
+    // eliminated by the options that are already selected.
+    pub fn apply(&mut self, o: Int) -> Result<bool> {
+        let start = usize::try_from(o)
+            .ok()
+            .and_then(|o| self.starts.get(o).copied())
+            .filter(|&p| p != 0)
+            .ok_or_else(|| anyhow!("Option {} is not in the problem", o))?;
+        let p = primary_node(self.dance, start);
+        if !is_live(self.dance, p) {
+            return Ok(false);
+        }
+        self.dance.select(p);
+        self.stack.push((o, p));
+        Ok(true)
+    }
+
+    pub fn undo(&mut self) -> Option<Int> {
+        let (o, p) = self.stack.pop()?;
+        self.dance.deselect(p);
+        Some(o)
+    }
+
+    pub fn undo_all(&mut self) {
+        while self.undo().is_some() {}
+    }
+
+    // Whether every primary item has been covered as often as it has to
+    // be.
+    pub fn is_solved(&mut self) -> bool {
+        let mut i = *self.dance.rlink(0);
+        while i != 0 {
+            if self.dance.needed(i) > 0 {
+                return false;
+            }
+            i = *self.dance.rlink(i);
+        }
+        true
+    }
+
+    pub fn is_active(&mut self, i: Uint) -> bool {
+        let i = i + 1;
+        let l = *self.dance.llink(i);
+        *self.dance.rlink(l) == i
+    }
+
+    // The options that can still be selected to cover item i.
+    pub fn options(&mut self, i: Uint) -> Vec<Int> {
+        let mut os = Vec::new();
+        if !self.is_active(i) {
+            return os;
+        }
+        let i = i + 1;
+        let mut p = *self.dance.dlink(i);
+        while p != i {
+            os.push(opt_number(self.dance, p));
+            p = *self.dance.dlink(p);
+        }
+        os
+    }
+
+    pub fn degree(&mut self, i: Uint) -> Int {
+        self.dance.branch_degree(i + 1)
+    }
+
+    // The uncovered primary items whose remaining options all have to be
+    // selected, as often as they can be, along with each of those options.
+    pub fn forced(&mut self) -> Vec<(Uint, Int)> {
+        let mut forced = Vec::new();
+        let mut i = *self.dance.rlink(0);
+        while i != 0 {
+            let needed = self.dance.needed(i);
+            if needed > 0 && *self.dance.len(i) == needed {
+                let mut p = *self.dance.dlink(i);
+                while p != i {
+                    forced.push((i - 1, opt_number(self.dance, p)));
+                    p = *self.dance.dlink(p);
+                }
+            }
+            i = *self.dance.rlink(i);
+        }
+        forced
+    }
+
+    // The uncovered primary items that have too few remaining options to
+    // be covered as often as they have to be.
+    pub fn dead_ends(&mut self) -> Vec<Uint> {
+        let mut dead = Vec::new();
+        let mut i = *self.dance.rlink(0);
+        while i != 0 {
+            if self.dance.needed(i) > *self.dance.len(i) {
+                dead.push(i - 1);
+            }
+            i = *self.dance.rlink(i);
+        }
+        dead
+    }
+}
+
+// Covers the items of the option containing node p, which must be in the
+// list of a primary item.
+pub(crate) fn select<D: Dance>(dance: &mut D, p: Uint) {
+    let i = *dance.top(p) as Uint;
+    dance.cover(i);
+    let mut q = p + 1;
+    while q != p {
+        let j = *dance.top(q);
+        if j <= 0 {
+            q = *dance.ulink(q);
+        } else {
+            dance.commit(q, j as Uint);
+            q += 1;
+        }
+    }
+}
+
+pub(crate) fn deselect<D: Dance>(dance: &mut D, p: Uint) {
+    let mut q = p - 1;
+    while q != p {
+        let j = *dance.top(q);
+        if j <= 0 {
+            q = *dance.dlink(q);
+        } else {
+            dance.uncommit(q, j as Uint);
+            q -= 1;
+        }
+    }
+    let i = *dance.top(p) as Uint;
+    dance.uncover(i);
+}
+
+pub(crate) fn primary_node<D: Dance>(dance: &mut D, start: Uint) -> Uint {
+    let np = dance.items().primary();
+    let mut p = start;
+    while *dance.top(p) as Uint > np {
+        p += 1;
+    }
+    p
+}
+
+pub(crate) fn opt_number<D: Dance>(dance: &mut D, p: Uint) -> Int {
+    let mut r = p;
+    while *dance.top(r) > 0 {
+        r += 1;
+    }
+    // Internal option indexes are 1-based
+    -*dance.top(r) - 1
+}
+
+// An option is live if all of its items are still active and none of its
+// nodes have been hidden.
+pub(crate) fn is_live<D: Dance>(dance: &mut D, p: Uint) -> bool {
+    let mut q = p;
+    loop {
+        let j = *dance.top(q);
+        if j <= 0 {
+            q = *dance.ulink(q);
+        } else {
+            let j = j as Uint;
+            let l = *dance.llink(j);
+            if *dance.rlink(l) != j {
+                return false;
+            }
+            let u = *dance.ulink(q);
+            if *dance.dlink(u) != q {
+                return false;
+            }
+            q += 1;
+        }
+        if q == p {
+            return true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{XCC_SPEC, assert_restored, xc_problem};
+    use crate::{ItemOrder, OptOrder, Spec};
+
+    #[test]
+    fn test_step() {
+        let mut problem = xc_problem();
+        let init = xc_problem();
+        let mut stepper = Stepper::new(&mut problem);
+        assert_eq!(stepper.options(0), vec![1, 3]);
+        assert_eq!(stepper.degree(3), 3);
+        assert!(stepper.forced().is_empty());
+        assert!(stepper.apply(3).unwrap());
+        assert!(!stepper.is_active(0));
+        assert!(stepper.options(0).is_empty());
+        assert_eq!(stepper.options(6), vec![4]);
+        assert_eq!(stepper.forced(), vec![(1, 4), (2, 0), (4, 0), (6, 4)]);
+        assert!(!stepper.apply(5).unwrap(), "eliminated option applied");
+        assert!(stepper.apply(4).unwrap());
+        assert!(stepper.dead_ends().is_empty());
+        assert!(stepper.apply(0).unwrap());
+        assert!(stepper.is_solved());
+        assert_eq!(stepper.selected(), vec![3, 4, 0]);
+        assert_eq!(stepper.undo(), Some(0));
+        assert_eq!(stepper.undo(), Some(4));
+        stepper.undo_all();
+        assert!(stepper.apply(0).unwrap());
+        assert!(stepper.apply(1).unwrap());
+        assert_eq!(stepper.dead_ends(), vec![1, 5]);
+        stepper.undo_all();
+        assert!(stepper.apply(7).is_err());
+        assert_restored(&mut problem, &init);
+    }
+
+    #[test]
+    fn test_step_colors() {
+        let spec = Spec::new(XCC_SPEC, false).unwrap();
+        let mut problem =
+            c::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        let mut stepper = Stepper::new(&mut problem);
+        assert!(stepper.apply(3).unwrap());
+        assert_eq!(stepper.options(0), vec![1]);
+        assert_eq!(stepper.forced(), vec![(0, 1)]);
+        assert!(stepper.apply(1).unwrap());
+        assert!(stepper.is_solved());
+
+        let mut problem =
+            mc::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        let init = mc::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq)
+            .unwrap();
+        let mut stepper = Stepper::new(&mut problem);
+        assert!(stepper.apply(3).unwrap());
+        assert_eq!(stepper.forced(), vec![(0, 1)]);
+        assert!(stepper.apply(1).unwrap());
+        assert!(stepper.is_solved());
+        stepper.undo_all();
+        assert_restored(&mut problem, &init);
+    }
+
+    #[test]
+    fn test_step_multiplicities() {
+        let spec = Spec::new("2|a b\n0:2|a\na b\nb", false).unwrap();
+        let build =
+            || m::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq);
+        let mut problem = build().unwrap();
+        let mut stepper = Stepper::new(&mut problem);
+        assert_eq!(stepper.options(0), vec![0, 1]);
+        assert!(stepper.forced().is_empty());
+        assert!(stepper.apply(0).unwrap());
+        assert!(stepper.is_active(0));
+        assert_eq!(stepper.options(0), vec![0, 1]);
+        assert!(stepper.apply(0).unwrap());
+        assert!(!stepper.is_active(0));
+        assert_eq!(stepper.forced(), vec![(1, 2)]);
+        assert!(!stepper.is_solved());
+        assert!(stepper.apply(2).unwrap());
+        assert!(stepper.is_solved());
+        stepper.undo_all();
+        assert!(stepper.apply(1).unwrap());
+        assert_eq!(stepper.options(0), vec![0]);
+        assert!(stepper.dead_ends().is_empty());
+        assert!(stepper.apply(0).unwrap());
+        assert!(!stepper.apply(0).unwrap(), "option used too often");
+        assert!(stepper.is_solved());
+        stepper.undo_all();
+        assert_restored(&mut problem, &build().unwrap());
+    }
+}
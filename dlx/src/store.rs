@@ -0,0 +1,489 @@
+use std::io::{Read, Write};
+
+use anyhow::{Result, bail};
+
+use crate::m::Uses;
+use crate::{Dance, Int, Items, Opts, Uint};
+
+// A built problem in binary form, so that one that was slow to make, or
+// that was reduced by preprocessing, can be reloaded without parsing its
+// spec again. The file holds the links as they are, along with the bounds
+// of the items and the uses of the options of m and mc problems, the colors
+// of c and mc problems, and optionally the item names. Numbers are little endian and as wide as
+// Uint, and a checksum of the rest of the file comes last. Problems should
+// be saved between searches, when their links are all restored.
+const MAGIC: &[u8; 4] = b"DLXB";
+const VERSION: u16 = 2;
+
+// Bits of the kind of problem in a file
+const BOUNDS: u8 = 1;
+const COLORS: u8 = 2;
+
+const UINT: usize = std::mem::size_of::<Uint>();
+const HEADER: usize = 8 + 6 * UINT;
+
+pub trait Store: Dance + Sized {
+    // Which of BOUNDS and COLORS the problem has.
+    const KIND: u8;
+
+    // The bounds (u, v) of item i, where v is its bound at rest and u is v
+    // less its slack.
+    fn item_bounds(&mut self, _i: Uint) -> (Int, Int) {
+        (1, 1)
+    }
+
+    fn node_color(&mut self, _p: Uint) -> Int {
+        0
+    }
+
+    // The uses of the options of m and mc problems, if they have any.
+    fn opt_uses(&mut self) -> Vec<Uses> {
+        Vec::new()
+    }
+
+    // Items and nodes of the given bounds and colors, for the links to be
+    // filled in. Bounds and colors are empty for problems without them.
+    fn blank_items(np: Uint, ns: Uint, bounds: &[(Int, Int)]) -> Self::I;
+    fn blank_opts(size: usize, colors: Vec<Int>) -> Self::O;
+
+    // The problem with the given items, nodes and option uses, if their
+    // links are sound.
+    fn from_parts(
+        items: Self::I, opts: Self::O, uses: Vec<Uses>,
+    ) -> Result<Self>;
+}
+
+// FNV-1a
+fn checksum(data: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for &b in data {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
+pub fn to_bytes<P: Store>(problem: &mut P, names: &[String]) -> Vec<u8> {
+    let np = problem.items().primary();
+    let n = problem.items().count();
+    let size = problem.opts().size();
+    assert!(
+        names.is_empty() || names.len() == n as usize,
+        "Names don't match the items"
+    );
+    debug_assert!(at_rest(problem), "Problem saved during a search");
+    let mut buf = Vec::with_capacity(HEADER + 5 * UINT * size as usize);
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&VERSION.to_le_bytes());
+    buf.push(P::KIND);
+    buf.push(UINT as u8);
+    let count = *problem.opts().opt_count();
+    let nfree = problem.opts().free().len() as Uint;
+    for k in [np, n - np, size, count as Uint, nfree, names.len() as Uint] {
+        buf.extend_from_slice(&k.to_le_bytes());
+    }
+    for i in 0..n + 2 {
+        buf.extend_from_slice(&problem.llink(i).to_le_bytes());
+        buf.extend_from_slice(&problem.rlink(i).to_le_bytes());
+    }
+    if P::KIND & BOUNDS != 0 {
+        for i in 1..=n {
+            let (u, v) = problem.item_bounds(i);
+            buf.extend_from_slice(&u.to_le_bytes());
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        let uses = problem.opt_uses();
+        buf.extend_from_slice(&(uses.len() as Uint).to_le_bytes());
+        for u in uses {
+            buf.extend_from_slice(&u.left.to_le_bytes());
+            buf.extend_from_slice(&u.item.to_le_bytes());
+        }
+    }
+    for p in 0..size {
+        buf.extend_from_slice(&problem.top(p).to_le_bytes());
+        buf.extend_from_slice(&problem.ulink(p).to_le_bytes());
+        buf.extend_from_slice(&problem.dlink(p).to_le_bytes());
+    }
+    if P::KIND & COLORS != 0 {
+        for p in 0..size {
+            buf.extend_from_slice(&problem.node_color(p).to_le_bytes());
+        }
+    }
+    for k in 0..nfree as usize {
+        buf.extend_from_slice(&problem.opts().free()[k].to_le_bytes());
+    }
+    for name in names {
+        buf.extend_from_slice(&(name.len() as Uint).to_le_bytes());
+        buf.extend_from_slice(name.as_bytes());
+    }
+    let sum = checksum(&buf);
+    buf.extend_from_slice(&sum.to_le_bytes());
+    buf
+}
+
+pub fn save<P: Store>(
+    problem: &mut P, names: &[String], w: &mut impl Write,
+) -> Result<()> {
+    w.write_all(&to_bytes(problem, names))?;
+    Ok(())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, k: usize) -> Result<&'a [u8]> {
+        if k > self.data.len() {
+            bail!("Truncated file");
+        }
+        let (head, rest) = self.data.split_at(k);
+        self.data = rest;
+        Ok(head)
+    }
+
+    // Fails early, before anything is allocated, if the file is too short
+    // to hold count entries of the given width.
+    fn expect(&self, count: Uint, width: usize) -> Result<usize> {
+        match (count as usize).checked_mul(width) {
+            Some(k) if k <= self.data.len() => Ok(count as usize),
+            _ => bail!("Truncated file"),
+        }
+    }
+
+    fn uint(&mut self) -> Result<Uint> {
+        Ok(Uint::from_le_bytes(self.take(UINT)?.try_into()?))
+    }
+
+    fn int(&mut self) -> Result<Int> {
+        Ok(Int::from_le_bytes(self.take(UINT)?.try_into()?))
+    }
+}
+
+// Rebuilds a problem saved by to_bytes, along with its item names, if they
+// were saved. The data may as well be a mapped file.
+pub fn from_bytes<P: Store>(data: &[u8]) -> Result<(P, Vec<String>)> {
+    if data.len() < HEADER + 8 || &data[..4] != MAGIC {
+        bail!("Not a problem file");
+    }
+    let (data, sum) = data.split_at(data.len() - 8);
+    if checksum(data) != u64::from_le_bytes(sum.try_into()?) {
+        bail!("Bad checksum");
+    }
+    let version = u16::from_le_bytes(data[4..6].try_into()?);
+    if version != VERSION {
+        bail!("Unsupported version {}", version);
+    }
+    if data[6] != P::KIND {
+        bail!("Wrong kind of problem {}", data[6]);
+    }
+    if data[7] as usize != UINT {
+        bail!("Saved with {}-byte numbers", data[7]);
+    }
+    let mut r = Reader { data: &data[8..] };
+    let np = r.uint()?;
+    let ns = r.uint()?;
+    let size = r.uint()?;
+    let count = r.int()?;
+    let nfree = r.uint()?;
+    let nnames = r.uint()?;
+    let n = match np.checked_add(ns) {
+        Some(n) if np > 0 && n < Uint::MAX - 2 => n,
+        _ => bail!("Bad item counts"),
+    };
+    if size < n + 2 || (nnames != 0 && nnames != n) {
+        bail!("Bad sizes");
+    }
+    let mut links = Vec::with_capacity(r.expect(n + 2, 2 * UINT)?);
+    for _ in 0..n + 2 {
+        links.push((r.uint()?, r.uint()?));
+    }
+    let mut bounds = Vec::new();
+    let mut uses = Vec::new();
+    if P::KIND & BOUNDS != 0 {
+        bounds.reserve(r.expect(n, 2 * UINT)?);
+        for _ in 0..n {
+            bounds.push((r.int()?, r.int()?));
+        }
+        let nuses = r.uint()?;
+        uses.reserve(r.expect(nuses, 2 * UINT)?);
+        for _ in 0..nuses {
+            uses.push(Uses { left: r.int()?, item: r.uint()? });
+        }
+    }
+    let mut nodes = Vec::with_capacity(r.expect(size, 3 * UINT)?);
+    for _ in 0..size {
+        nodes.push((r.int()?, r.uint()?, r.uint()?));
+    }
+    let mut colors = Vec::new();
+    if P::KIND & COLORS != 0 {
+        colors.reserve(r.expect(size, UINT)?);
+        for _ in 0..size {
+            colors.push(r.int()?);
+        }
+    }
+    let mut free = Vec::with_capacity(r.expect(nfree, UINT)?);
+    for _ in 0..nfree {
+        let s = r.uint()?;
+        if s >= size {
+            bail!("Bad free spacer {}", s);
+        }
+        free.push(s);
+    }
+    let mut names = Vec::with_capacity(r.expect(nnames, UINT)?);
+    for _ in 0..nnames {
+        let len = r.uint()?;
+        let bytes = r.take(r.expect(len, 1)?)?;
+        names.push(String::from_utf8(bytes.to_vec())?);
+    }
+    if !r.data.is_empty() {
+        bail!("Trailing data");
+    }
+    check_options(n, count, &nodes, &free)?;
+    if bounds.iter().any(|&(u, v)| u < 0 || u > v || v < 1) {
+        bail!("Bad item bounds");
+    }
+    if uses.iter().any(|u| u.left < 1 || u.item > np) {
+        bail!("Bad option uses");
+    }
+    if colors.iter().any(|&c| c < 0) {
+        bail!("Bad colors");
+    }
+
+    let mut items = P::blank_items(np, ns, &bounds);
+    for (i, (l, r)) in (0..).zip(links) {
+        *items.llink(i) = l;
+        *items.rlink(i) = r;
+    }
+    let mut opts = P::blank_opts(size as usize, colors);
+    for (p, (t, u, d)) in (0..).zip(nodes) {
+        *opts.top(p) = t;
+        *opts.ulink(p) = u;
+        *opts.dlink(p) = d;
+    }
+    *opts.opt_count() = count;
+    *opts.free() = free;
+    Ok((P::from_parts(items, opts, uses)?, names))
+}
+
+// Checks the nodes that check_links doesn't reach. Each option is followed
+// by a spacer holding its number, or 0 if it was deleted, in which case the
+// spacer before it is on the free list.
+fn check_options(
+    n: Uint, count: Int, nodes: &[(Int, Uint, Uint)], free: &[Uint],
+) -> Result<()> {
+    let size = nodes.len() as Uint;
+    let mut deleted = Vec::new();
+    let mut s = n + 1;
+    loop {
+        let last = nodes[s as usize].2;
+        if last <= s {
+            break;
+        }
+        if last >= size - 1 {
+            bail!("Option after spacer {} runs past the end", s);
+        }
+        let opt = &nodes[(s + 1) as usize..=last as usize];
+        if opt.iter().any(|&(t, _, _)| t < 1 || t as Uint > n) {
+            bail!("Bad item in the option after spacer {}", s);
+        }
+        let (t, u, _) = nodes[(last + 1) as usize];
+        if t > 0 || t < -count || u != s + 1 {
+            bail!("Bad spacer {}", last + 1);
+        }
+        if t == 0 {
+            deleted.push(s);
+        }
+        s = last + 1;
+    }
+    if s != size - 1 {
+        bail!("Nodes after the last option");
+    }
+    let mut free = free.to_vec();
+    free.sort_unstable();
+    if free != deleted {
+        bail!("Free spacers don't match the deleted options");
+    }
+    Ok(())
+}
+
+// Whether the problem is between searches, with every item in its list and
+// no node left purified.
+fn at_rest<P: Store>(problem: &mut P) -> bool {
+    let np = problem.items().primary();
+    let n = problem.items().count();
+    for (h, k) in [(0, np), (n + 1, n - np)] {
+        let mut i = *problem.rlink(h);
+        let mut m = 0;
+        while i != h && m <= k {
+            i = *problem.rlink(i);
+            m += 1;
+        }
+        if m != k {
+            return false;
+        }
+    }
+    let size = problem.opts().size();
+    P::KIND & COLORS == 0 || (0..size).all(|p| problem.node_color(p) >= 0)
+}
+
+pub fn load<P: Store>(r: &mut impl Read) -> Result<(P, Vec<String>)> {
+    let mut data = Vec::new();
+    r.read_to_end(&mut data)?;
+    from_bytes(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::Edit;
+    use crate::testing::solutions;
+    use crate::{ItemOrder, OptOrder, Spec, c, m, mc, x};
+
+    fn round_trip<P: Store + crate::Solve + std::fmt::Debug + Eq>(
+        mut problem: P, names: &[String],
+    ) {
+        let bytes = to_bytes(&mut problem, names);
+        let (mut loaded, loaded_names) = from_bytes::<P>(&bytes).unwrap();
+        assert_eq!(loaded, problem);
+        assert_eq!(loaded_names, names);
+        assert_eq!(solutions(&mut loaded), solutions(&mut problem));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let spec_str = "
+a b c d e f g
+c e
+a d g
+b c f
+a d f
+b g
+d e g
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let problem =
+            x::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq);
+        let names: Vec<String> = "abcdefg".chars().map(String::from).collect();
+        round_trip(problem.unwrap(), &names);
+
+        let spec_str = "
+a b c | x y
+a b x y:A
+a c x:A y
+c x:B
+b x:A
+c y:B
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let names: Vec<String> = "abcxy".chars().map(String::from).collect();
+        let problem =
+            c::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq);
+        round_trip(problem.unwrap(), &names);
+
+        let spec_str = "
+A B 2:3|C | 2|X Y
+A B X Y
+A C X Y
+C X
+B X
+C Y
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let problem =
+            m::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq);
+        round_trip(problem.unwrap(), &[]);
+
+        let spec_str = "
+A B 2:3|C | 2|X Y
+A B X Y:P
+A C X Y:Q
+C X
+B X
+C Y:P
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let problem =
+            mc::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq);
+        round_trip(problem.unwrap(), &[]);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let os: Vec<Vec<Uint>> = vec![vec![0, 1], vec![1, 2], vec![0, 2]];
+        let mut problem =
+            x::make_problem(3, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        let mut bytes = Vec::new();
+        save(&mut problem, &[], &mut bytes).unwrap();
+        let (loaded, _) = load::<x::Problem>(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded, problem);
+
+        // Deleted options leave free spacers behind
+        let mut edited =
+            x::make_problem(3, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        edited.delete_option(1).unwrap();
+        let saved = to_bytes(&mut edited, &[]);
+        let (loaded, _) = from_bytes::<x::Problem>(&saved).unwrap();
+        assert_eq!(loaded, edited);
+
+        let mut bad = bytes.clone();
+        bad[HEADER + 3] ^= 1;
+        let e = from_bytes::<x::Problem>(&bad).unwrap_err();
+        assert_eq!(e.to_string(), "Bad checksum");
+        let e = from_bytes::<x::Problem>(&bytes[..bytes.len() - 1]);
+        assert!(e.is_err());
+        let e = from_bytes::<c::Problem>(&bytes).unwrap_err();
+        assert!(e.to_string().starts_with("Wrong kind"));
+
+        // A broken link with a good checksum is caught when the problem is
+        // rebuilt
+        let bad = patch(&bytes, HEADER, 7);
+        assert!(from_bytes::<x::Problem>(&bad).is_err());
+
+        // So are spacers and free lists that don't match the options. The
+        // spacer after the second option is node 10.
+        let spacer = HEADER + 5 * 2 * UINT + 10 * 3 * UINT;
+        let bad = patch(&bytes, spacer, 1);
+        let e = from_bytes::<x::Problem>(&bad).unwrap_err();
+        assert_eq!(e.to_string(), "Bad spacer 10");
+        let bad = patch(&bytes, spacer, -4);
+        assert!(from_bytes::<x::Problem>(&bad).is_err());
+        let bad = patch(&bytes, spacer, 0);
+        let e = from_bytes::<x::Problem>(&bad).unwrap_err();
+        assert!(e.to_string().starts_with("Free spacers"));
+    }
+
+    #[test]
+    fn test_load_bounds() {
+        let spec = Spec::new("1:2|a b\na b\n0:2|a\nb\n", false).unwrap();
+        let problem =
+            m::Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq);
+        let mut problem = problem.unwrap();
+        let bytes = to_bytes(&mut problem, &[]);
+        assert!(from_bytes::<m::Problem>(&bytes).is_ok());
+        // The bound of a, then its lower bound
+        let bound = HEADER + 4 * 2 * UINT + UINT;
+        let bad = patch(&bytes, bound, 0);
+        let e = from_bytes::<m::Problem>(&bad).unwrap_err();
+        assert_eq!(e.to_string(), "Bad item bounds");
+        let bad = patch(&bytes, bound - UINT, 3);
+        assert!(from_bytes::<m::Problem>(&bad).is_err());
+        // The uses left of the second option, which has to match the
+        // lengths of its items
+        let uses = bound + 3 * UINT + UINT + 2 * UINT;
+        let bad = patch(&bytes, uses, 0);
+        let e = from_bytes::<m::Problem>(&bad).unwrap_err();
+        assert_eq!(e.to_string(), "Bad option uses");
+        let bad = patch(&bytes, uses, 3);
+        assert!(from_bytes::<m::Problem>(&bad).is_err());
+    }
+
+    // The bytes with the number at offset k replaced, and a good checksum.
+    fn patch(bytes: &[u8], k: usize, value: Int) -> Vec<u8> {
+        let mut bad = bytes[..bytes.len() - 8].to_vec();
+        bad[k..k + UINT].copy_from_slice(&value.to_le_bytes());
+        let sum = checksum(&bad);
+        bad.extend_from_slice(&sum.to_le_bytes());
+        bad
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use anyhow::{Result, bail};
+
+use crate::decompose::{find, item_name};
+use crate::{Int, Spec};
+
+// An automorphism of a spec: a permutation of its items, numbered with the
+// primary items first, and the permutation of its options that it induces.
+// Item declarations, including multiplicity prefixes, and colors are kept.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Symmetry {
+    items: Vec<usize>,
+    opts: Vec<usize>,
+}
+
+// Each option as a sorted list of its items and their colors.
+fn option_keys(spec: &Spec) -> Result<Vec<Vec<(usize, &str)>>> {
+    let names = spec.primary.iter().chain(spec.secondary.iter());
+    let idx = names
+        .enumerate()
+        .map(|(i, item)| (item_name(item), i))
+        .collect::<HashMap<_, _>>();
+    let mut keys = Vec::new();
+    for opt in &spec.opts {
+        let mut key = Vec::new();
+        for itm in opt {
+            let mut parts = itm.splitn(2, ':');
+            let name = parts.next().unwrap();
+            let Some(&i) = idx.get(name) else {
+                bail!("Unknown item {} in option", name);
+            };
+            key.push((i, parts.next().unwrap_or("")));
+        }
+        key.sort();
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+// The declaration of each item apart from its name, which a symmetry has
+// to preserve.
+fn item_kinds(spec: &Spec) -> Vec<(bool, &str)> {
+    let np = spec.primary.len();
+    let names = spec.primary.iter().chain(spec.secondary.iter());
+    names
+        .enumerate()
+        .map(|(i, item)| (i < np, &item[..item.len() - item_name(item).len()]))
+        .collect()
+}
+
+impl Symmetry {
+    // Checks that a permutation of the items maps options to options, and
+    // finds the permutation of the options.
+    pub fn from_items(spec: &Spec, items: &[usize]) -> Result<Symmetry> {
+        let kinds = item_kinds(spec);
+        if items.len() != kinds.len() {
+            bail!("Expected a permutation of {} items", kinds.len());
+        }
+        let mut seen = vec![false; items.len()];
+        for (i, &j) in items.iter().enumerate() {
+            if j >= items.len() || seen[j] {
+                bail!("Not a permutation of the items");
+            }
+            seen[j] = true;
+            if kinds[i] != kinds[j] {
+                bail!("Item {} cannot be mapped to item {}", i, j);
+            }
+        }
+        let keys = option_keys(spec)?;
+        let mut idx: HashMap<_, Vec<usize>> = HashMap::new();
+        for (o, key) in keys.iter().enumerate() {
+            idx.entry(key).or_default().push(o);
+        }
+        // Identical options are matched up in order
+        let mut used = HashMap::new();
+        let mut opts = Vec::with_capacity(keys.len());
+        for key in &keys {
+            let mut image =
+                key.iter().map(|&(i, c)| (items[i], c)).collect::<Vec<_>>();
+            image.sort();
+            let Some(os) = idx.get(&image) else {
+                bail!("The permutation does not map options to options");
+            };
+            let k = used.entry(image).or_insert(0);
+            opts.push(os[*k]);
+            *k += 1;
+        }
+        Ok(Symmetry { items: items.to_vec(), opts })
+    }
+
+    pub fn identity(spec: &Spec) -> Symmetry {
+        let n = spec.primary.len() + spec.secondary.len();
+        Symmetry {
+            items: (0..n).collect(),
+            opts: (0..spec.opts.len()).collect(),
+        }
+    }
+
+    pub fn items(&self) -> &[usize] {
+        &self.items
+    }
+
+    pub fn opts(&self) -> &[usize] {
+        &self.opts
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.items.iter().enumerate().all(|(i, &j)| i == j)
+    }
+
+    // The symmetry that applies other first and then self.
+    pub fn compose(&self, other: &Symmetry) -> Symmetry {
+        Symmetry {
+            items: other.items.iter().map(|&i| self.items[i]).collect(),
+            opts: other.opts.iter().map(|&o| self.opts[o]).collect(),
+        }
+    }
+
+    // The image of a set of options, sorted.
+    pub fn apply(&self, solution: &[Int]) -> Vec<Int> {
+        let mut image = solution
+            .iter()
+            .map(|&o| self.opts[o as usize] as Int)
+            .collect::<Vec<_>>();
+        image.sort();
+        image
+    }
+}
+
+// Closes a set of symmetries under composition, giving up once the group
+// has more than limit elements.
+pub fn generate(
+    spec: &Spec, gens: &[Symmetry], limit: usize,
+) -> Result<Vec<Symmetry>> {
+    let id = Symmetry::identity(spec);
+    let mut seen = HashSet::from([id.clone()]);
+    let mut group = vec![id];
+    let mut k = 0;
+    while k < group.len() {
+        for g in gens {
+            let h = g.compose(&group[k]);
+            if seen.insert(h.clone()) {
+                if group.len() == limit {
+                    bail!("The group has more than {} elements", limit);
+                }
+                group.push(h);
+            }
+        }
+        k += 1;
+    }
+    Ok(group)
+}
+
+fn hash<T: Hash>(t: T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    t.hash(&mut hasher);
+    hasher.finish()
+}
+
+// The incidence graph of a spec, with the items as vertices 0..n and the
+// options after them. Edges are labeled with the color of the item in the
+// option.
+struct Graph {
+    adj: Vec<Vec<(u64, usize)>>,
+    init: Vec<u64>,
+}
+
+impl Graph {
+    fn new(spec: &Spec) -> Result<Graph> {
+        let kinds = item_kinds(spec);
+        let n = kinds.len();
+        let mut adj = vec![Vec::new(); n + spec.opts.len()];
+        for (o, key) in option_keys(spec)?.into_iter().enumerate() {
+            for (i, c) in key {
+                let label = hash(c);
+                adj[i].push((label, n + o));
+                adj[n + o].push((label, i));
+            }
+        }
+        let mut init = kinds.iter().map(hash).collect::<Vec<_>>();
+        init.resize(adj.len(), hash("option"));
+        Ok(Graph { adj, init })
+    }
+
+    // Refines a coloring until vertices of the same color have the same
+    // numbers of neighbors of each color along edges of each label.
+    fn refine(&self, colors: &mut Vec<u64>) {
+        let mut classes = count_classes(colors);
+        loop {
+            let next = (0..colors.len())
+                .map(|v| {
+                    let mut nbrs = self.adj[v]
+                        .iter()
+                        .map(|&(label, u)| (label, colors[u]))
+                        .collect::<Vec<_>>();
+                    nbrs.sort_unstable();
+                    hash((colors[v], nbrs))
+                })
+                .collect::<Vec<_>>();
+            let k = count_classes(&next);
+            *colors = next;
+            if k == classes {
+                break;
+            }
+            classes = k;
+        }
+    }
+}
+
+fn count_classes(colors: &[u64]) -> usize {
+    colors.iter().collect::<HashSet<_>>().len()
+}
+
+fn sorted(colors: &[u64]) -> Vec<u64> {
+    let mut colors = colors.to_vec();
+    colors.sort_unstable();
+    colors
+}
+
+struct Search<'a> {
+    spec: &'a Spec,
+    graph: Graph,
+    found: Vec<Symmetry>,
+    limit: usize,
+}
+
+impl Search<'_> {
+    // Individualizes one vertex of the smallest nontrivial class on the
+    // left, and each vertex of the same color in turn on the right.
+    fn search(&mut self, left: &[u64], right: &[u64], depth: usize) {
+        let mut classes: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (v, &c) in left.iter().enumerate() {
+            classes.entry(c).or_default().push(v);
+        }
+        let cell = classes
+            .values()
+            .filter(|vs| vs.len() > 1)
+            .min_by_key(|vs| (vs.len(), vs[0]));
+        let Some(cell) = cell else {
+            self.leaf(left, right);
+            return;
+        };
+        let v = cell[0];
+        let mark = hash(("individual", depth));
+        let mut next_left = left.to_vec();
+        next_left[v] = hash((left[v], mark));
+        self.graph.refine(&mut next_left);
+        let target = sorted(&next_left);
+        for w in 0..right.len() {
+            if right[w] != left[v] {
+                continue;
+            }
+            let mut next_right = right.to_vec();
+            next_right[w] = hash((right[w], mark));
+            self.graph.refine(&mut next_right);
+            if sorted(&next_right) == target {
+                self.search(&next_left, &next_right, depth + 1);
+            }
+            if self.found.len() >= self.limit {
+                return;
+            }
+        }
+    }
+
+    // Both colorings are discrete, so matching colors gives a bijection.
+    fn leaf(&mut self, left: &[u64], right: &[u64]) {
+        let n = self.spec.primary.len() + self.spec.secondary.len();
+        let pos = right
+            .iter()
+            .enumerate()
+            .map(|(w, &c)| (c, w))
+            .collect::<HashMap<_, _>>();
+        let items = left[..n].iter().map(|c| pos[c]).collect::<Vec<_>>();
+        if let Ok(g) = Symmetry::from_items(self.spec, &items) {
+            self.found.push(g);
+        }
+    }
+}
+
+// Finds the automorphisms of a spec, up to limit of them, by the usual
+// individualization and refinement search on its incidence graph. If
+// fewer than limit are returned, they are the whole group.
+pub fn automorphisms(spec: &Spec, limit: usize) -> Result<Vec<Symmetry>> {
+    let graph = Graph::new(spec)?;
+    let mut colors = graph.init.clone();
+    graph.refine(&mut colors);
+    let mut search = Search { spec, graph, found: Vec::new(), limit };
+    search.search(&colors, &colors, 0);
+    Ok(search.found)
+}
+
+// Enumerates each class of symmetric solutions once. The options of a
+// root item are cut down to one from each orbit under the symmetries that
+// fix the item; of the solutions that remain, only the least one of each
+// class in lexicographic order is accepted.
+pub struct SymmetryBreaker {
+    group: Vec<Symmetry>,
+    // The options of the original spec that were kept
+    keep: Vec<bool>,
+    // Option numbers in the original spec of the options that were kept
+    opts: Vec<usize>,
+}
+
+impl SymmetryBreaker {
+    // The group should be closed under composition, as with automorphisms
+    // or generate; otherwise some classes may be enumerated more than once.
+    // Returns the breaker and the restricted spec to solve.
+    pub fn new(
+        spec: &Spec, group: Vec<Symmetry>,
+    ) -> Result<(SymmetryBreaker, Spec)> {
+        let np = spec.primary.len();
+        let keys = option_keys(spec)?;
+        let mut keep = vec![true; spec.opts.len()];
+        // The primary item whose options are cut down the most
+        let mut best = (0, Vec::new());
+        for i in 0..np {
+            let col = (0..keys.len())
+                .filter(|&o| keys[o].iter().any(|&(j, _)| j == i))
+                .collect::<Vec<_>>();
+            let mut parent = (0..keys.len()).collect::<Vec<_>>();
+            for g in group.iter().filter(|g| g.items[i] == i) {
+                for &o in &col {
+                    let a = find(&mut parent, o);
+                    let b = find(&mut parent, g.opts[o]);
+                    // The least option of each orbit is its root
+                    parent[a.max(b)] = a.min(b);
+                }
+            }
+            let cut = col.iter().filter(|&&o| find(&mut parent, o) != o);
+            let cut = cut.copied().collect::<Vec<_>>();
+            if cut.len() > best.1.len() {
+                best = (i, cut);
+            }
+        }
+        for o in best.1 {
+            keep[o] = false;
+        }
+        let opts = (0..keep.len()).filter(|&o| keep[o]).collect::<Vec<_>>();
+        let restricted = Spec {
+            primary: spec.primary.clone(),
+            secondary: spec.secondary.clone(),
+            opts: opts.iter().map(|&o| spec.opts[o].clone()).collect(),
+            sharp_pref: spec.sharp_pref,
+        };
+        Ok((SymmetryBreaker { group, keep, opts }, restricted))
+    }
+
+    pub fn group(&self) -> &[Symmetry] {
+        &self.group
+    }
+
+    // Maps a solution of the restricted spec back to the original option
+    // numbers, sorted, if it is the one accepted from its class.
+    pub fn accept(&self, solution: &[Int]) -> Option<Vec<Int>> {
+        let s = solution.iter().map(|&o| self.opts[o as usize] as Int);
+        let mut s = s.collect::<Vec<_>>();
+        s.sort();
+        for g in &self.group {
+            let t = g.apply(&s);
+            if t < s && t.iter().all(|&o| self.keep[o as usize]) {
+                return None;
+            }
+        }
+        Some(s)
+    }
+
+    // The number of distinct solutions in the class of a solution given in
+    // original option numbers, so that the unreduced count is the sum of
+    // the class sizes of the accepted solutions.
+    pub fn class_size(&self, solution: &[Int]) -> usize {
+        let images = self.group.iter().map(|g| g.apply(solution));
+        images.collect::<HashSet<_>>().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::choose::*;
+    use crate::testing::langford_spec;
+    use crate::{ItemOrder, OptOrder, Solver, x};
+
+    // The accepted solutions of the restricted spec, and the sum of their
+    // class sizes.
+    fn solve(spec: &Spec, group: Vec<Symmetry>) -> (usize, usize) {
+        let (breaker, restricted) = SymmetryBreaker::new(spec, group).unwrap();
+        let mut problem =
+            x::Problem::from_spec(&restricted, OptOrder::Seq, ItemOrder::Seq)
+                .unwrap();
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        let mut solver = Solver::new(&mut problem);
+        let (mut classes, mut total) = (0, 0);
+        while solver.next_solution(&mut chooser) {
+            if let Some(s) = breaker.accept(solver.fmt_solution()) {
+                classes += 1;
+                total += breaker.class_size(&s);
+            }
+        }
+        (classes, total)
+    }
+
+    #[test]
+    fn test_dominoes() {
+        let spec_str = "
+a b c d e f
+a b
+b c
+d e
+e f
+a d
+b e
+c f
+";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let group = automorphisms(&spec, 100).unwrap();
+        assert_eq!(group.len(), 4);
+        assert_eq!(group.iter().filter(|g| g.is_identity()).count(), 1);
+        assert_eq!(solve(&spec, group), (2, 3));
+    }
+
+    #[test]
+    fn test_generators() {
+        let n = 7;
+        let spec = langford_spec(n);
+        // Reversing the positions
+        let mut items = (0..n).collect::<Vec<_>>();
+        items.extend((0..2 * n).map(|p| n + 2 * n - 1 - p));
+        let reverse = Symmetry::from_items(&spec, &items).unwrap();
+        let group = generate(&spec, &[reverse], 10).unwrap();
+        assert_eq!(group.len(), 2);
+        assert_eq!(automorphisms(&spec, 10).unwrap(), group);
+        assert_eq!(solve(&spec, group), (26, 52));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let spec = langford_spec(4);
+        let mut items = (0..12).collect::<Vec<_>>();
+        items.swap(0, 1);
+        assert!(Symmetry::from_items(&spec, &items).is_err());
+        items.swap(0, 1);
+        items.swap(3, 4);
+        assert!(Symmetry::from_items(&spec, &items).is_err());
+        items.swap(3, 4);
+        items[0] = 1;
+        assert!(Symmetry::from_items(&spec, &items).is_err());
+    }
+}
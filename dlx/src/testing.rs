@@ -0,0 +1,97 @@
+use std::fmt::Debug;
+
+use crate::choose::{mrv_chooser, no_tiebreak, prefer_any};
+use crate::{Dance, Int, ItemOrder, OptOrder, Solve, Solver, Spec, Uint, x};
+
+// Knuth's example with colors, which has the single solution [1, 3].
+pub const XCC_SPEC: &str = "
+p q r | x y
+p q x y:A
+p r x:A y
+p x:B
+q x:A
+r y:B
+";
+
+// A problem with colors whose search meets dead ends, with five solutions.
+pub const DEAD_ENDS_SPEC: &str = "
+p q r s | x y
+p q x:A
+p x:B
+q y:A
+q x:B y:B
+r x:A
+r y:B
+s x:B
+s y:A
+r s
+";
+
+// Knuth's exact cover example, which has the single solution [0, 3, 4].
+pub fn xc_problem() -> x::Problem {
+    let os: Vec<Vec<Uint>> = vec![
+        vec![2, 4],
+        vec![0, 3, 6],
+        vec![1, 2, 5],
+        vec![0, 3, 5],
+        vec![1, 6],
+        vec![3, 4, 6],
+    ];
+    x::make_problem(7, 0, &os, OptOrder::Seq, ItemOrder::Seq)
+}
+
+// Langford pairs, with the positions as primary items.
+pub fn langford_spec(n: usize) -> Spec {
+    let mut spec_str = String::new();
+    for k in 1..=n {
+        spec_str += &format!("{} ", k);
+    }
+    for p in 0..2 * n {
+        spec_str += &format!("s{} ", p);
+    }
+    spec_str += "\n";
+    for k in 1..=n {
+        for p in 0..2 * n - k - 1 {
+            spec_str += &format!("{} s{} s{}\n", k, p, p + k + 1);
+        }
+    }
+    Spec::new(&spec_str, false).unwrap()
+}
+
+// The solutions with their options sorted, in sorted order, so that
+// searches can be compared whatever order they take.
+pub fn sorted(solutions: impl IntoIterator<Item = Vec<Int>>) -> Vec<Vec<Int>> {
+    let mut solutions = solutions
+        .into_iter()
+        .map(|mut s| {
+            s.sort();
+            s
+        })
+        .collect::<Vec<_>>();
+    solutions.sort();
+    solutions
+}
+
+// All solutions, as sorted, and the number of nodes it took to find them.
+pub fn search<P: Solve>(problem: &mut P) -> (Vec<Vec<Int>>, usize) {
+    let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+    let mut solver = Solver::new(problem);
+    let mut solutions = Vec::new();
+    while solver.next_solution(&mut chooser) {
+        solutions.push(solver.fmt_solution().to_vec());
+    }
+    (sorted(solutions), solver.get_nodes())
+}
+
+pub fn solutions<P: Solve>(problem: &mut P) -> Vec<Vec<Int>> {
+    search(problem).0
+}
+
+// Checks that the problem is back as it started, apart from its count of
+// updates.
+pub fn assert_restored<P: Dance + Debug + PartialEq>(
+    problem: &mut P, init: &P,
+) {
+    *problem.updates() = 0;
+    assert_eq!(*problem, *init, "problem not restored");
+}
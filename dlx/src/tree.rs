@@ -0,0 +1,171 @@
+use std::io::{Result, Write};
+
+use crate::observe::Observer;
+use crate::step::opt_number;
+use crate::{Dance, Int, Items, Uint};
+
+pub struct TreeNode {
+    pub parent: Option<usize>,
+    // The option node tried on the way to this node, 0 for the root
+    pub xl: Uint,
+    // The item chosen for branching, 0 if no item was chosen
+    pub item: Uint,
+    pub degree: Int,
+    pub solution: bool,
+}
+
+// Records the search tree of a run, up to a limit on the number of nodes.
+pub struct SearchTree {
+    nodes: Vec<TreeNode>,
+    path: Vec<Option<usize>>,
+    limit: usize,
+    truncated: bool,
+}
+
+impl SearchTree {
+    pub fn new(limit: usize) -> SearchTree {
+        SearchTree {
+            nodes: Vec::new(),
+            path: Vec::new(),
+            limit,
+            truncated: false,
+        }
+    }
+
+    pub fn nodes(&self) -> &[TreeNode] {
+        &self.nodes
+    }
+
+    // True if nodes were left out because the limit was reached.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn add_node(&mut self, parent: Option<usize>, xl: Uint) -> Option<usize> {
+        if self.nodes.len() >= self.limit {
+            self.truncated = true;
+            return None;
+        }
+        self.nodes.push(TreeNode {
+            parent,
+            xl,
+            item: 0,
+            degree: 0,
+            solution: false,
+        });
+        Some(self.nodes.len() - 1)
+    }
+
+    fn current(&mut self, l: Uint) -> Option<usize> {
+        let l = l as usize;
+        if l == 0 && self.path.is_empty() {
+            let root = self.add_node(None, 0);
+            self.path.push(root);
+        }
+        self.path.get(l).copied().flatten()
+    }
+
+    // Writes the tree as a DOT graph. Nodes are labelled with the chosen
+    // item and its degree, edges with the option that was tried; items and
+    // options are numbered as in the input data.
+    pub fn write_dot<D: Dance, W: Write>(
+        &self, dance: &mut D, w: &mut W,
+    ) -> Result<()> {
+        let n = dance.items().count();
+        writeln!(w, "digraph search {{")?;
+        writeln!(w, "  node [shape=box fontname=monospace];")?;
+        for (k, node) in self.nodes.iter().enumerate() {
+            let label = if node.solution {
+                "solution".to_string()
+            } else if node.item == 0 {
+                "?".to_string()
+            } else {
+                format!("item {} ({})", node.item - 1, node.degree)
+            };
+            let style = if node.solution {
+                " style=filled fillcolor=palegreen"
+            } else if node.item != 0 && node.degree == 0 {
+                " style=filled fillcolor=mistyrose"
+            } else {
+                ""
+            };
+            writeln!(w, "  n{} [label=\"{}\"{}];", k, label, style)?;
+            if let Some(parent) = node.parent {
+                let label = if node.xl <= n {
+                    "skip".to_string()
+                } else {
+                    opt_number(dance, node.xl).to_string()
+                };
+                writeln!(w, "  n{} -> n{} [label=\"{}\"];", parent, k, label)?;
+            }
+        }
+        if self.truncated {
+            writeln!(w, "  more [label=\"...\" shape=plaintext];")?;
+        }
+        writeln!(w, "}}")
+    }
+}
+
+impl Observer for SearchTree {
+    fn choose(&mut self, l: Uint, i: Uint, degree: Int, _: isize) {
+        if let Some(k) = self.current(l) {
+            self.nodes[k].item = i;
+            self.nodes[k].degree = degree;
+        }
+    }
+
+    fn try_option(&mut self, l: Uint, xl: Uint, _: isize) {
+        let parent = self.current(l);
+        let child = parent.and_then(|p| self.add_node(Some(p), xl));
+        self.path.truncate(l as usize + 1);
+        self.path.push(child);
+    }
+
+    fn solution(&mut self, l: Uint, _: isize) {
+        if let Some(k) = self.current(l) {
+            self.nodes[k].solution = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Solver;
+    use crate::choose::*;
+    use crate::testing::xc_problem;
+
+    #[test]
+    fn test_search_tree() {
+        let mut problem = xc_problem();
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        let mut solver =
+            Solver::with_observer(&mut problem, SearchTree::new(100));
+        while solver.next_solution(&mut chooser) {}
+        let profile = solver.get_profile().iter().sum::<usize>();
+        let tree = solver.into_observer();
+        assert!(!tree.truncated());
+        let nodes = tree.nodes();
+        assert_eq!(nodes.iter().filter(|n| n.solution).count(), 1);
+        assert_eq!(nodes.iter().filter(|n| n.item != 0).count(), profile);
+        assert_eq!((nodes[0].item, nodes[0].degree), (1, 2));
+        let mut dot = Vec::new();
+        tree.write_dot(&mut problem, &mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("digraph search {"));
+        assert!(dot.contains("n0 -> n1 [label=\"1\"];"));
+        assert_eq!(dot.matches("palegreen").count(), 1);
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut problem = xc_problem();
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        let mut solver =
+            Solver::with_observer(&mut problem, SearchTree::new(3));
+        while solver.next_solution(&mut chooser) {}
+        let tree = solver.into_observer();
+        assert!(tree.truncated());
+        assert_eq!(tree.nodes().len(), 3);
+    }
+}
@@ -1,529 +1,945 @@
-use anyhow::{Result, anyhow, bail};
-
-use crate::{Dance, Int, Items, OptData, OptOrder, Opts, Solve, Spec, Uint};
-
-pub fn cover<D: Dance>(i: Uint, dance: &mut D) {
-    // TODO: increment updates
-    let mut p = *dance.dlink(i);
-    while p != i {
-        dance.hide(p);
-        p = *dance.dlink(p);
-    }
-    let l = *dance.llink(i);
-    let r = *dance.rlink(i);
-    *dance.rlink(l) = r;
-    *dance.llink(r) = l;
-    *dance.updates() += 1;
-}
-
-pub fn commit<D: Dance>(_: Uint, j: Uint, dance: &mut D) {
-    cover(j, dance);
-}
-
-pub fn uncover<D: Dance>(i: Uint, dance: &mut D) {
-    let l = *dance.llink(i);
-    let r = *dance.rlink(i);
-    *dance.rlink(l) = i;
-    *dance.llink(r) = i;
-    let mut p = *dance.ulink(i);
-    while p != i {
-        dance.unhide(p);
-        p = *dance.ulink(p);
-    }
-}
-
-pub fn uncommit<D: Dance>(_: Uint, j: Uint, dance: &mut D) {
-    uncover(j, dance);
-}
-
-pub fn hide<D: Dance>(p: Uint, dance: &mut D) {
-    let mut q = p + 1;
-    while q != p {
-        let x = *dance.top(q);
-        let u = *dance.ulink(q);
-        let d = *dance.dlink(q);
-        if x <= 0 {
-            q = u;
-        } else {
-            *dance.dlink(u) = d;
-            *dance.ulink(d) = u;
-            *dance.len(x as Uint) -= 1;
-            q += 1;
-            *dance.updates() += 1;
-        }
-    }
-}
-
-pub fn unhide<D: Dance>(p: Uint, dance: &mut D) {
-    let mut q = p - 1;
-    while q != p {
-        let x = *dance.top(q);
-        let u = *dance.ulink(q);
-        let d = *dance.dlink(q);
-        if x <= 0 {
-            q = d;
-        } else {
-            *dance.dlink(u) = q;
-            *dance.ulink(d) = q;
-            *dance.len(x as Uint) += 1;
-            q -= 1;
-        }
-    }
-}
-
-pub fn branch_degree<D: Dance>(i: Uint, dance: &mut D) -> Int {
-    *dance.len(i) as Int
-}
-
-pub fn prepare_to_branch<S: Solve>(solve: &mut S, i: Uint, _: Uint, _: Uint) {
-    solve.cover(i);
-}
-
-pub fn try_item<S: Solve>(solve: &mut S, i: Uint, xl: Uint) -> bool {
-    if xl == i {
-        return false;
-    }
-    let mut p = xl + 1;
-    while p != xl {
-        let j = *solve.top(p);
-        if j <= 0 {
-            p = *solve.ulink(p);
-        } else {
-            solve.commit(p, j as Uint);
-            p += 1;
-        }
-    }
-    true
-}
-
-pub fn try_again<S: Solve>(
-    solve: &mut S, i: Uint, l: Uint, xl: &mut Uint,
-) -> bool {
-    let mut p = *xl - 1;
-    while p != *xl {
-        let j = *solve.top(p);
-        if j <= 0 {
-            p = *solve.dlink(p);
-        } else {
-            solve.uncommit(p, j as Uint);
-            p -= 1;
-        }
-    }
-    *xl = *solve.dlink(*xl);
-    let again = solve.try_item(i, l, *xl);
-    if !again {
-        solve.restore_item(i, l, *xl);
-    }
-    again
-}
-
-pub fn restore_item<S: Solve>(solve: &mut S, i: Uint) {
-    solve.uncover(i);
-}
-
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
-pub struct INode {
-    left: Uint,
-    right: Uint,
-}
-
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
-pub struct INodes {
-    nodes: Vec<INode>,
-    primary: Uint,
-    len: Uint,
-}
-
-impl INodes {
-    #[allow(clippy::unnecessary_cast)]
-    pub fn new(np: Uint, ns: Uint) -> INodes {
-        assert!((np as u64) < Int::MAX as u64);
-        assert!((ns as u64) < Int::MAX as u64);
-        let mut nodes = INodes {
-            nodes: vec![Default::default(); (np + ns + 2) as usize],
-            primary: np,
-            len: np + ns,
-        };
-        nodes.init_links();
-        nodes
-    }
-
-    pub fn from_spec(spec: &Spec) -> Result<(INodes, Vec<String>)> {
-        use std::collections::HashSet;
-        let np = spec.primary.len() as Uint;
-        let ns = spec.secondary.len() as Uint;
-        let mut names = spec.primary.clone();
-        names.extend(spec.secondary.clone());
-        let mut used = HashSet::new();
-        let unique = names.iter().all(|e| used.insert(e));
-        if !unique {
-            bail!("Duplicate item names");
-        }
-        for name in &names {
-            if !name.chars().all(|c| c.is_alphanumeric() || c == '#') {
-                bail!("Invalid item name");
-            }
-        }
-        Ok((INodes::new(np, ns), names))
-    }
-
-    #[inline]
-    fn get_node(&mut self, i: Uint) -> &mut INode {
-        if cfg!(feature = "unsafe-fast-index") {
-            unsafe { self.nodes.get_unchecked_mut(i as usize) }
-        } else {
-            &mut self.nodes[i as usize]
-        }
-    }
-}
-
-#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
-struct ONode {
-    hdr_info: Int,
-    up: Uint,
-    down: Uint,
-}
-
-#[derive(Clone, Default, Debug, Eq, PartialEq)]
-pub struct ONodes {
-    nodes: Vec<ONode>,
-}
-
-impl ONodes {
-    pub fn new(n: Uint, np: Uint, os: &[Vec<Uint>], order: OptOrder) -> ONodes {
-        let mut onodes =
-            ONodes { nodes: vec![Default::default(); (n + 2) as usize] };
-        onodes.init_links(n, np, order, os);
-        onodes
-    }
-
-    pub fn from_spec(
-        spec: &Spec, names: &[String], order: OptOrder,
-    ) -> Result<ONodes> {
-        use std::collections::{HashMap, HashSet};
-        let mut idx = HashMap::new();
-        for (i, name) in names.iter().enumerate() {
-            idx.insert(name, i);
-        }
-        let mut os = Vec::new();
-        for opt in &spec.opts {
-            let mut is = Vec::new();
-            let mut used = HashSet::new();
-            for itm in opt {
-                let i = idx.get(itm).ok_or_else(|| anyhow!("Invalid item"))?;
-                if !used.insert(itm) {
-                    bail!("Duplicate items in option");
-                }
-                is.push(*i as Uint);
-            }
-            os.push(is);
-        }
-        let n = (spec.primary.len() + spec.secondary.len()) as Uint;
-        let opts = ONodes::new(n, spec.primary.len() as Uint, &os, order);
-        Ok(opts)
-    }
-
-    #[inline]
-    fn get_node(&mut self, i: Uint) -> &mut ONode {
-        if cfg!(feature = "unsafe-fast-index") {
-            unsafe { self.nodes.get_unchecked_mut(i as usize) }
-        } else {
-            &mut self.nodes[i as usize]
-        }
-    }
-}
-
-pub fn make_problem(
-    np: Uint, ns: Uint, os: &[Vec<Uint>], order: OptOrder,
-) -> Problem {
-    Problem::new(INodes::new(np, ns), ONodes::new(np + ns, np, os, order))
-}
-
-#[derive(Debug, Eq, PartialEq)]
-pub struct Problem {
-    items: INodes,
-    opts: ONodes,
-    updates: isize,
-}
-
-impl Problem {
-    pub fn new(items: INodes, opts: ONodes) -> Problem {
-        Problem { items, opts, updates: 0 }
-    }
-
-    pub fn from_spec(spec: &Spec, order: OptOrder) -> Result<Problem> {
-        let (items, names) = INodes::from_spec(spec)?;
-        let opts = ONodes::from_spec(spec, &names, order)?;
-        Ok(Problem::new(items, opts))
-    }
-}
-
-impl Items for INodes {
-    #[inline]
-    fn llink(&mut self, i: Uint) -> &mut Uint {
-        &mut self.get_node(i).left
-    }
-
-    #[inline]
-    fn rlink(&mut self, i: Uint) -> &mut Uint {
-        &mut self.get_node(i).right
-    }
-
-    #[inline]
-    fn primary(&self) -> Uint {
-        self.primary
-    }
-
-    #[inline]
-    fn count(&self) -> Uint {
-        self.len
-    }
-}
-
-impl OptData for Uint {
-    fn new_item(i: Uint) -> Self {
-        i
-    }
-    fn get_item(&self) -> Uint {
-        *self
-    }
-}
-
-impl Opts for ONodes {
-    type Data = Uint;
-
-    #[inline]
-    fn len(&mut self, i: Uint) -> &mut Int {
-        &mut self.get_node(i).hdr_info
-    }
-
-    #[inline]
-    fn top(&mut self, i: Uint) -> &mut Int {
-        &mut self.get_node(i).hdr_info
-    }
-
-    #[inline]
-    fn ulink(&mut self, i: Uint) -> &mut Uint {
-        &mut self.get_node(i).up
-    }
-
-    #[inline]
-    fn dlink(&mut self, i: Uint) -> &mut Uint {
-        &mut self.get_node(i).down
-    }
-
-    fn set_data(&mut self, _pk: Uint, s: Uint) -> Uint {
-        self.nodes.push(Default::default());
-        s
-    }
-
-    fn get_data_item(s: Self::Data) -> Uint {
-        s
-    }
-}
-
-impl Dance for Problem {
-    type I = INodes;
-    type O = ONodes;
-
-    #[inline]
-    fn items(&mut self) -> &mut Self::I {
-        &mut self.items
-    }
-
-    #[inline]
-    fn opts(&mut self) -> &mut Self::O {
-        &mut self.opts
-    }
-
-    #[inline]
-    fn updates(&mut self) -> &mut isize {
-        &mut self.updates
-    }
-
-    #[inline]
-    fn cover(&mut self, i: Uint) {
-        cover(i, self);
-    }
-
-    #[inline]
-    fn commit(&mut self, p: Uint, j: Uint) {
-        commit(p, j, self);
-    }
-
-    #[inline]
-    fn uncover(&mut self, i: Uint) {
-        uncover(i, self);
-    }
-
-    #[inline]
-    fn uncommit(&mut self, p: Uint, j: Uint) {
-        uncommit(p, j, self);
-    }
-
-    #[inline]
-    fn hide(&mut self, p: Uint) {
-        hide(p, self);
-    }
-
-    #[inline]
-    fn unhide(&mut self, p: Uint) {
-        unhide(p, self);
-    }
-
-    #[inline]
-    fn branch_degree(&mut self, i: Uint) -> Int {
-        branch_degree(i, self)
-    }
-}
-
-impl Solve for Problem {
-    fn enter_level(&mut self, _: Uint, _: Uint, _: Uint) {}
-
-    #[inline]
-    fn prepare_to_branch(&mut self, i: Uint, l: Uint, xl: Uint) {
-        prepare_to_branch(self, i, l, xl);
-    }
-
-    #[inline]
-    fn try_item(&mut self, i: Uint, _: Uint, xl: Uint) -> bool {
-        try_item(self, i, xl)
-    }
-
-    #[inline]
-    fn try_again(&mut self, i: Uint, l: Uint, xl: &mut Uint) -> bool {
-        try_again(self, i, l, xl)
-    }
-
-    #[inline]
-    fn restore_item(&mut self, i: Uint, _: Uint, _: Uint) {
-        restore_item(self, i);
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_item_init() {
-        let items = INodes::new(3, 2);
-        let inodes = inodes_data();
-        assert_eq!(items.nodes, inodes, "incorrect items");
-    }
-
-    #[test]
-    fn test_opt_init() {
-        let os: Vec<Vec<Uint>> = vec![
-            vec![0, 1, 3, 4],
-            vec![0, 2, 3, 4],
-            vec![0, 3],
-            vec![1, 3],
-            vec![2, 4],
-        ];
-        let opts = ONodes::new(5, 3, &os, OptOrder::Seq);
-        let onodes = onodes_data();
-        assert_eq!(opts.nodes, onodes, "incorrect options");
-    }
-
-    #[test]
-    fn test_from_spec() {
-        let spec_str = "
-| This is a comment
-p q r | x y
-
-p q x y
-p r x y
-| Another comment
-p x
-q x
-r y";
-        let spec = Spec::new(spec_str, false).unwrap();
-        let problem = Problem::from_spec(&spec, OptOrder::Seq).unwrap();
-        assert_eq!(problem.items.nodes, inodes_data());
-        assert_eq!(problem.opts.nodes, onodes_data());
-    }
-
-    #[test]
-    fn test_xc() {
-        use crate::Solver;
-        use crate::choose::*;
-        let items = INodes::new(7, 0);
-        let os: Vec<Vec<Uint>> = vec![
-            vec![2, 4],
-            vec![0, 3, 6],
-            vec![1, 2, 5],
-            vec![0, 3, 5],
-            vec![1, 6],
-            vec![3, 4, 6],
-        ];
-        let opts = ONodes::new(7, 7, &os, OptOrder::Seq);
-        let items_init = items.clone();
-        let opts_init = opts.clone();
-        let mut problem = Problem::new(items, opts);
-        let mut solver = Solver::new(&mut problem);
-        let mut solutions: Vec<Vec<Int>> = Vec::new();
-        let mut expected = vec![vec![0, 3, 4]];
-        let mut i = 0;
-        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
-        while solver.next_solution(&mut chooser) {
-            assert!(i <= expected.len(), "too many solutions");
-            solver.fmt_solution();
-            solver.o.sort();
-            solutions.push(solver.o.clone());
-            i += 1;
-        }
-        solutions.sort();
-        expected.sort();
-        assert_eq!(solutions, expected, "wrong solutions");
-        assert_eq!(solver.problem.items, items_init, "items not backtracked");
-        assert_eq!(solver.problem.opts, opts_init, "options not backtracked");
-        assert!(
-            solver.l == 0 && solver.restart == false,
-            "initial state not restored"
-        );
-    }
-
-    pub(crate) fn inodes_data() -> Vec<INode> {
-        vec![
-            INode { left: 3, right: 1 },
-            INode { left: 0, right: 2 },
-            INode { left: 1, right: 3 },
-            INode { left: 2, right: 0 },
-            INode { left: 6, right: 5 },
-            INode { left: 4, right: 6 },
-            INode { left: 5, right: 4 },
-        ]
-    }
-
-    fn onodes_data() -> Vec<ONode> {
-        vec![
-            ONode { hdr_info: 0, up: 0, down: 0 },
-            ONode { hdr_info: 3, up: 17, down: 7 },
-            ONode { hdr_info: 2, up: 20, down: 8 },
-            ONode { hdr_info: 2, up: 23, down: 13 },
-            ONode { hdr_info: 4, up: 21, down: 9 },
-            ONode { hdr_info: 3, up: 24, down: 10 },
-            ONode { hdr_info: 0, up: 0, down: 10 },
-            ONode { hdr_info: 1, up: 1, down: 12 },
-            ONode { hdr_info: 2, up: 2, down: 20 },
-            ONode { hdr_info: 4, up: 4, down: 14 },
-            ONode { hdr_info: 5, up: 5, down: 15 },
-            ONode { hdr_info: -1, up: 7, down: 15 },
-            ONode { hdr_info: 1, up: 7, down: 17 },
-            ONode { hdr_info: 3, up: 3, down: 23 },
-            ONode { hdr_info: 4, up: 9, down: 18 },
-            ONode { hdr_info: 5, up: 10, down: 24 },
-            ONode { hdr_info: -2, up: 12, down: 18 },
-            ONode { hdr_info: 1, up: 12, down: 1 },
-            ONode { hdr_info: 4, up: 14, down: 21 },
-            ONode { hdr_info: -3, up: 17, down: 21 },
-            ONode { hdr_info: 2, up: 8, down: 2 },
-            ONode { hdr_info: 4, up: 18, down: 4 },
-            ONode { hdr_info: -4, up: 20, down: 24 },
-            ONode { hdr_info: 3, up: 13, down: 3 },
-            ONode { hdr_info: 5, up: 15, down: 5 },
-            ONode { hdr_info: -5, up: 23, down: 0 },
-        ]
-    }
-}
+use anyhow::{Result, anyhow, bail};
+
+use crate::Uint;
+use crate::choose::{mrv_chooser, no_tiebreak, prefer_any};
+use crate::lookahead::{self, Lookahead};
+use crate::nodes::{Link, Links, Nodes, check_links};
+use crate::store::Store;
+use crate::{Dance, Int, ItemOrder, Items, Mems, OptData, OptOrder, Opts};
+use crate::{Solve, Solver, Spec, csr_size, m, to_csr};
+
+pub fn cover<D: Dance>(i: Uint, dance: &mut D) {
+    let mut p = *dance.dlink(i);
+    while p != i {
+        dance.hide(p);
+        p = *dance.dlink(p);
+    }
+    let l = *dance.llink(i);
+    let r = *dance.rlink(i);
+    *dance.rlink(l) = r;
+    *dance.llink(r) = l;
+    *dance.updates() += 1;
+}
+
+pub fn commit<D: Dance>(_: Uint, j: Uint, dance: &mut D) {
+    cover(j, dance);
+}
+
+pub fn uncover<D: Dance>(i: Uint, dance: &mut D) {
+    let l = *dance.llink(i);
+    let r = *dance.rlink(i);
+    *dance.rlink(l) = i;
+    *dance.llink(r) = i;
+    let mut p = *dance.ulink(i);
+    while p != i {
+        dance.unhide(p);
+        p = *dance.ulink(p);
+    }
+}
+
+pub fn uncommit<D: Dance>(_: Uint, j: Uint, dance: &mut D) {
+    uncover(j, dance);
+}
+
+pub fn hide<D: Dance>(p: Uint, dance: &mut D) {
+    let mut q = p + 1;
+    while q != p {
+        let (x, u, d) = dance.node(q);
+        if x <= 0 {
+            q = u;
+        } else {
+            *dance.dlink(u) = d;
+            *dance.ulink(d) = u;
+            *dance.len(x as Uint) -= 1;
+            q += 1;
+            *dance.updates() += 1;
+        }
+    }
+}
+
+pub fn unhide<D: Dance>(p: Uint, dance: &mut D) {
+    let mut q = p - 1;
+    while q != p {
+        let (x, u, d) = dance.node(q);
+        if x <= 0 {
+            q = d;
+        } else {
+            *dance.dlink(u) = q;
+            *dance.ulink(d) = q;
+            *dance.len(x as Uint) += 1;
+            q -= 1;
+        }
+    }
+}
+
+pub fn branch_degree<D: Dance>(i: Uint, dance: &mut D) -> Int {
+    *dance.len(i) as Int
+}
+
+pub fn prepare_to_branch<S: Solve>(solve: &mut S, i: Uint, _: Uint, _: Uint) {
+    solve.cover(i);
+}
+
+pub fn try_item<S: Solve>(solve: &mut S, i: Uint, xl: Uint) -> bool {
+    if xl == i {
+        return false;
+    }
+    let mut p = xl + 1;
+    while p != xl {
+        let j = *solve.top(p);
+        if j <= 0 {
+            p = *solve.ulink(p);
+        } else {
+            solve.commit(p, j as Uint);
+            p += 1;
+        }
+    }
+    true
+}
+
+pub fn try_again<S: Solve>(
+    solve: &mut S, i: Uint, l: Uint, xl: &mut Uint,
+) -> bool {
+    solve.undo_item(i, l, *xl);
+    *xl = *solve.dlink(*xl);
+    let again = solve.try_item(i, l, *xl);
+    if !again {
+        solve.restore_item(i, l, *xl);
+    }
+    again
+}
+
+pub fn undo_item<S: Solve>(solve: &mut S, xl: Uint) {
+    let mut p = xl - 1;
+    while p != xl {
+        let j = *solve.top(p);
+        if j <= 0 {
+            p = *solve.dlink(p);
+        } else {
+            solve.uncommit(p, j as Uint);
+            p -= 1;
+        }
+    }
+}
+
+pub fn restore_item<S: Solve>(solve: &mut S, i: Uint) {
+    solve.uncover(i);
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct INode {
+    left: Uint,
+    right: Uint,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct INodes {
+    nodes: Nodes<INode>,
+    primary: Uint,
+    len: Uint,
+    mems: Mems,
+}
+
+// Nodes compare equal whatever mems they have counted.
+impl PartialEq for INodes {
+    fn eq(&self, other: &INodes) -> bool {
+        let fields = (&self.nodes, self.primary, self.len);
+        fields == (&other.nodes, other.primary, other.len)
+    }
+}
+
+impl Eq for INodes {}
+
+impl INodes {
+    #[allow(clippy::unnecessary_cast)]
+    pub fn new(np: Uint, ns: Uint, order: ItemOrder) -> INodes {
+        assert!((np as u64) < Int::MAX as u64);
+        assert!((ns as u64) < Int::MAX as u64);
+        let mut nodes = INodes {
+            nodes: Nodes::from_vec(vec![
+                Default::default();
+                (np + ns + 2) as usize
+            ]),
+            primary: np,
+            len: np + ns,
+            mems: Mems::default(),
+        };
+        nodes.init_links(order);
+        nodes
+    }
+
+    pub fn from_spec(
+        spec: &Spec, order: ItemOrder,
+    ) -> Result<(INodes, Vec<String>)> {
+        use std::collections::HashSet;
+        let np = spec.primary.len() as Uint;
+        let ns = spec.secondary.len() as Uint;
+        let mut names = spec.primary.clone();
+        names.extend(spec.secondary.clone());
+        let mut used = HashSet::new();
+        let unique = names.iter().all(|e| used.insert(e));
+        if !unique {
+            bail!("Duplicate item names");
+        }
+        for name in &names {
+            if !name.chars().all(|c| c.is_alphanumeric() || c == '#') {
+                bail!("Invalid item name");
+            }
+        }
+        Ok((INodes::new(np, ns, order), names))
+    }
+
+    #[inline]
+    fn get_node(&mut self, i: Uint) -> &mut INode {
+        self.mems.add();
+        self.nodes.get_mut(i)
+    }
+}
+
+// Option nodes with their links stored as S, which can be narrower than
+// Uint for problems with few enough nodes.
+#[derive(Clone, Default, Debug)]
+pub struct ONodes<S: Link = Uint> {
+    links: Links<S>,
+    count: Int,
+    free: Vec<Uint>,
+    mems: Mems,
+}
+
+impl<S: Link> PartialEq for ONodes<S> {
+    fn eq(&self, other: &ONodes<S>) -> bool {
+        let fields = (&self.links, self.count, &self.free);
+        fields == (&other.links, other.count, &other.free)
+    }
+}
+
+impl<S: Link> Eq for ONodes<S> {}
+
+impl ONodes {
+    pub fn new(n: Uint, np: Uint, os: &[Vec<Uint>], order: OptOrder) -> ONodes {
+        let entries = os.iter().map(Vec::len).sum();
+        ONodes::from_options(n, np, os, entries, order)
+    }
+
+    // Builds the nodes from options produced one at a time, so that they
+    // needn't all be in memory as vectors of their own. They are flattened
+    // into compressed sparse row form, with room made up front for entries
+    // item uses in all.
+    pub fn from_options<T: AsRef<[Uint]>>(
+        n: Uint, np: Uint, os: impl IntoIterator<Item = T>, entries: usize,
+        order: OptOrder,
+    ) -> ONodes {
+        let (starts, items) = to_csr(os, entries);
+        ONodes::from_csr(n, np, &starts, &items, order)
+    }
+}
+
+impl<S: Link> ONodes<S> {
+    // Builds the nodes from options in compressed sparse row form, where
+    // option k has the items items[starts[k]..starts[k + 1]]. The nodes are
+    // counted first, so they are allocated once at their exact size.
+    pub fn from_csr(
+        n: Uint, np: Uint, starts: &[usize], items: &[Uint], order: OptOrder,
+    ) -> ONodes<S> {
+        let mut onodes = ONodes::blank(csr_size(n, np, starts, items));
+        onodes.link_csr(n, np, order, starts, items);
+        onodes
+    }
+
+    // Unlinked nodes, for a loaded problem to fill in.
+    pub(crate) fn blank(size: usize) -> ONodes<S> {
+        ONodes { links: Links::new(size), ..Default::default() }
+    }
+
+    pub fn from_spec(
+        spec: &Spec, names: &[String], order: OptOrder,
+    ) -> Result<ONodes<S>> {
+        let (starts, items) = spec_csr(spec, names)?;
+        let n = (spec.primary.len() + spec.secondary.len()) as Uint;
+        let np = spec.primary.len() as Uint;
+        if csr_size(n, np, &starts, &items) > S::MAX_NODES {
+            bail!("Too many nodes for the link type");
+        }
+        Ok(ONodes::from_csr(n, np, &starts, &items, order))
+    }
+
+    #[inline]
+    fn links(&mut self) -> &mut Links<S> {
+        self.mems.add();
+        &mut self.links
+    }
+}
+
+// The options of a spec in compressed sparse row form, as the indexes of
+// their items in names.
+fn spec_csr(spec: &Spec, names: &[String]) -> Result<(Vec<usize>, Vec<Uint>)> {
+    use std::collections::{HashMap, HashSet};
+    let mut idx = HashMap::new();
+    for (i, name) in names.iter().enumerate() {
+        idx.insert(name, i);
+    }
+    let mut starts = vec![0];
+    let mut items = Vec::with_capacity(spec.opts.iter().map(Vec::len).sum());
+    for opt in &spec.opts {
+        let mut used = HashSet::new();
+        for itm in opt {
+            let i = idx.get(itm).ok_or_else(|| anyhow!("Invalid item"))?;
+            if !used.insert(itm) {
+                bail!("Duplicate items in option");
+            }
+            items.push(*i as Uint);
+        }
+        starts.push(items.len());
+    }
+    Ok((starts, items))
+}
+
+pub fn make_problem(
+    np: Uint, ns: Uint, os: &[Vec<Uint>], order: OptOrder,
+    item_order: ItemOrder,
+) -> Problem {
+    let items = INodes::new(np, ns, item_order);
+    Problem::new(items, ONodes::new(np + ns, np, os, order))
+}
+
+#[derive(Debug)]
+pub struct Problem<S: Link = Uint> {
+    items: INodes,
+    opts: ONodes<S>,
+    updates: isize,
+    lookahead: Lookahead,
+}
+
+// Problems compare equal whatever lookahead they were set to use.
+impl<S: Link> PartialEq for Problem<S> {
+    fn eq(&self, other: &Problem<S>) -> bool {
+        let fields = (&self.items, &self.opts, self.updates);
+        fields == (&other.items, &other.opts, other.updates)
+    }
+}
+
+impl<S: Link> Eq for Problem<S> {}
+
+impl<S: Link> Problem<S> {
+    pub fn new(items: INodes, opts: ONodes<S>) -> Problem<S> {
+        let problem = Problem::from_nodes(items, opts);
+        problem.unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn from_nodes(items: INodes, opts: ONodes<S>) -> Result<Problem<S>> {
+        let mut problem = Problem {
+            items,
+            opts,
+            updates: 0,
+            lookahead: Default::default(),
+        };
+        check_links(&mut problem)?;
+        Ok(problem)
+    }
+}
+
+impl Problem {
+    pub fn from_spec(
+        spec: &Spec, order: OptOrder, item_order: ItemOrder,
+    ) -> Result<Problem> {
+        let (items, names) = INodes::from_spec(spec, item_order)?;
+        let opts = ONodes::from_spec(spec, &names, order)?;
+        Ok(Problem::new(items, opts))
+    }
+}
+
+// An exact cover problem with its links stored in the narrowest type that
+// holds all of its nodes: u16 for up to 32767 nodes, u32 for up to about two
+// billion and Uint beyond that. Narrower links take less memory, so more of
+// them stay in the cache while dancing. Each variant can be given to a
+// Solver as is.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Auto {
+    Links16(Problem<u16>),
+    #[cfg(not(feature = "32-bit"))]
+    Links32(Problem<u32>),
+    Links(Problem),
+}
+
+impl Auto {
+    pub fn new(
+        np: Uint, ns: Uint, os: &[Vec<Uint>], order: OptOrder,
+        item_order: ItemOrder,
+    ) -> Auto {
+        let (starts, data) = to_csr(os, os.iter().map(Vec::len).sum());
+        let items = INodes::new(np, ns, item_order);
+        Auto::from_csr(items, np, &starts, &data, order)
+    }
+
+    pub fn from_spec(
+        spec: &Spec, order: OptOrder, item_order: ItemOrder,
+    ) -> Result<Auto> {
+        let (items, names) = INodes::from_spec(spec, item_order)?;
+        let (starts, data) = spec_csr(spec, &names)?;
+        let np = spec.primary.len() as Uint;
+        Ok(Auto::from_csr(items, np, &starts, &data, order))
+    }
+
+    fn from_csr(
+        items: INodes, np: Uint, starts: &[usize], data: &[Uint],
+        order: OptOrder,
+    ) -> Auto {
+        let n = items.count();
+        let size = csr_size(n, np, starts, data);
+        if size <= u16::MAX_NODES {
+            let opts = ONodes::from_csr(n, np, starts, data, order);
+            return Auto::Links16(Problem::new(items, opts));
+        }
+        #[cfg(not(feature = "32-bit"))]
+        if size <= u32::MAX_NODES {
+            let opts = ONodes::from_csr(n, np, starts, data, order);
+            return Auto::Links32(Problem::new(items, opts));
+        }
+        Auto::Links(Problem::new(
+            items,
+            ONodes::from_csr(n, np, starts, data, order),
+        ))
+    }
+
+    // Calls f with each solution, as option numbers, until it returns
+    // false. Returns the number of solutions seen.
+    pub fn for_each_solution<F: FnMut(&[Int]) -> bool>(
+        &mut self, mut f: F,
+    ) -> usize {
+        match self {
+            Auto::Links16(problem) => each_solution(problem, &mut f),
+            #[cfg(not(feature = "32-bit"))]
+            Auto::Links32(problem) => each_solution(problem, &mut f),
+            Auto::Links(problem) => each_solution(problem, &mut f),
+        }
+    }
+
+    pub fn count_solutions(&mut self) -> usize {
+        self.for_each_solution(|_| true)
+    }
+}
+
+fn each_solution<S: Link, F: FnMut(&[Int]) -> bool>(
+    problem: &mut Problem<S>, f: &mut F,
+) -> usize {
+    let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+    let mut solver = Solver::new(problem);
+    let mut count = 0;
+    while solver.next_solution(&mut chooser) {
+        count += 1;
+        if !f(solver.fmt_solution()) {
+            break;
+        }
+    }
+    count
+}
+
+impl Items for INodes {
+    #[inline]
+    fn llink(&mut self, i: Uint) -> &mut Uint {
+        &mut self.get_node(i).left
+    }
+
+    #[inline]
+    fn rlink(&mut self, i: Uint) -> &mut Uint {
+        &mut self.get_node(i).right
+    }
+
+    #[inline]
+    fn primary(&self) -> Uint {
+        self.primary
+    }
+
+    #[inline]
+    fn count(&self) -> Uint {
+        self.len
+    }
+
+    #[inline]
+    fn mems(&self) -> u64 {
+        self.mems.get()
+    }
+
+    fn insert_nodes(&mut self, i: Uint, k: Uint) {
+        self.nodes
+            .insert_many(i as usize, k as usize, Default::default());
+        self.len += k;
+    }
+}
+
+impl OptData for Uint {
+    fn new_item(i: Uint) -> Self {
+        i
+    }
+    fn get_item(&self) -> Uint {
+        *self
+    }
+}
+
+impl<S: Link> Opts for ONodes<S> {
+    type Data = Uint;
+    type Ref<'a> = S::Ref<'a>;
+    type SignedRef<'a> = S::SignedRef<'a>;
+
+    #[inline]
+    fn len(&mut self, i: Uint) -> S::SignedRef<'_> {
+        self.links().top(i)
+    }
+
+    #[inline]
+    fn top(&mut self, i: Uint) -> S::SignedRef<'_> {
+        self.links().top(i)
+    }
+
+    #[inline]
+    fn ulink(&mut self, i: Uint) -> S::Ref<'_> {
+        self.links().up(i)
+    }
+
+    #[inline]
+    fn dlink(&mut self, i: Uint) -> S::Ref<'_> {
+        self.links().down(i)
+    }
+
+    #[inline]
+    fn node(&mut self, i: Uint) -> (Int, Uint, Uint) {
+        self.links().node(i)
+    }
+
+    fn set_data(&mut self, pk: Uint, s: Uint) -> Uint {
+        if pk as usize == self.links.len() {
+            self.links.insert(pk as usize, 1);
+        }
+        s
+    }
+
+    fn get_data_item(s: Self::Data) -> Uint {
+        s
+    }
+
+    #[inline]
+    fn size(&self) -> Uint {
+        self.links.len() as Uint
+    }
+
+    fn max_size(&self) -> Uint {
+        S::MAX_NODES as Uint
+    }
+
+    fn insert_nodes(&mut self, i: Uint, k: Uint) {
+        self.links.insert(i as usize, k as usize);
+    }
+
+    #[inline]
+    fn opt_count(&mut self) -> &mut Int {
+        &mut self.count
+    }
+
+    #[inline]
+    fn free(&mut self) -> &mut Vec<Uint> {
+        &mut self.free
+    }
+
+    #[inline]
+    fn mems(&self) -> u64 {
+        self.mems.get()
+    }
+}
+
+impl Store for Problem {
+    const KIND: u8 = 0;
+
+    fn blank_items(np: Uint, ns: Uint, _: &[(Int, Int)]) -> INodes {
+        INodes::new(np, ns, ItemOrder::Seq)
+    }
+
+    fn blank_opts(size: usize, _: Vec<Int>) -> ONodes {
+        ONodes::blank(size)
+    }
+
+    fn from_parts(
+        items: INodes, opts: ONodes, _: Vec<m::Uses>,
+    ) -> Result<Problem> {
+        Problem::from_nodes(items, opts)
+    }
+}
+
+impl<S: Link> Dance for Problem<S> {
+    type I = INodes;
+    type O = ONodes<S>;
+
+    #[inline]
+    fn items(&mut self) -> &mut Self::I {
+        &mut self.items
+    }
+
+    #[inline]
+    fn opts(&mut self) -> &mut Self::O {
+        &mut self.opts
+    }
+
+    #[inline]
+    fn updates(&mut self) -> &mut isize {
+        &mut self.updates
+    }
+
+    #[inline]
+    fn cover(&mut self, i: Uint) {
+        cover(i, self);
+    }
+
+    #[inline]
+    fn commit(&mut self, p: Uint, j: Uint) {
+        commit(p, j, self);
+    }
+
+    #[inline]
+    fn uncover(&mut self, i: Uint) {
+        uncover(i, self);
+    }
+
+    #[inline]
+    fn uncommit(&mut self, p: Uint, j: Uint) {
+        uncommit(p, j, self);
+    }
+
+    #[inline]
+    fn hide(&mut self, p: Uint) {
+        hide(p, self);
+    }
+
+    #[inline]
+    fn unhide(&mut self, p: Uint) {
+        unhide(p, self);
+    }
+
+    #[inline]
+    fn branch_degree(&mut self, i: Uint) -> Int {
+        branch_degree(i, self)
+    }
+}
+
+impl<S: Link> Solve for Problem<S> {
+    fn set_lookahead(&mut self, depth: usize, max_level: Uint) -> Result<()> {
+        self.lookahead = Lookahead::new(depth, max_level);
+        Ok(())
+    }
+
+    fn enter_level(&mut self, _: Uint, _: Uint, _: Uint) {}
+
+    #[inline]
+    fn prepare_to_branch(&mut self, i: Uint, l: Uint, xl: Uint) {
+        if self.lookahead.applies(l) {
+            let pruned = lookahead::prune(self, i, self.lookahead.depth());
+            self.lookahead.push(l, pruned);
+        }
+        prepare_to_branch(self, i, l, xl);
+    }
+
+    #[inline]
+    fn try_item(&mut self, i: Uint, _: Uint, xl: Uint) -> bool {
+        try_item(self, i, xl)
+    }
+
+    #[inline]
+    fn try_again(&mut self, i: Uint, l: Uint, xl: &mut Uint) -> bool {
+        try_again(self, i, l, xl)
+    }
+
+    #[inline]
+    fn undo_item(&mut self, _: Uint, _: Uint, xl: Uint) {
+        undo_item(self, xl);
+    }
+
+    #[inline]
+    fn restore_item(&mut self, i: Uint, l: Uint, _: Uint) {
+        restore_item(self, i);
+        if let Some(pruned) = self.lookahead.pop(l) {
+            lookahead::unprune(self, i, &pruned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_init() {
+        let items = INodes::new(3, 2, ItemOrder::Seq);
+        let inodes = inodes_data();
+        assert_eq!(items.nodes.as_slice(), inodes, "incorrect items");
+    }
+
+    #[test]
+    fn test_item_order() {
+        use crate::Rng;
+        let list = |items: &mut INodes, h: Uint| {
+            let mut is = Vec::new();
+            let mut i = *items.rlink(h);
+            while i != h {
+                let l = *items.llink(i);
+                assert_eq!(*items.rlink(l), i);
+                is.push(i);
+                i = *items.rlink(i);
+            }
+            is
+        };
+        let mut items = INodes::new(6, 4, ItemOrder::Rnd(Rng::new(12345)));
+        let mut primary = list(&mut items, 0);
+        let mut secondary = list(&mut items, 11);
+        assert_ne!(primary, vec![1, 2, 3, 4, 5, 6]);
+        assert_ne!(secondary, vec![7, 8, 9, 10]);
+        primary.sort();
+        secondary.sort();
+        assert_eq!(primary, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(secondary, vec![7, 8, 9, 10]);
+    }
+
+    #[test]
+    fn test_opt_init() {
+        let os: Vec<Vec<Uint>> = vec![
+            vec![0, 1, 3, 4],
+            vec![0, 2, 3, 4],
+            vec![0, 3],
+            vec![1, 3],
+            vec![2, 4],
+        ];
+        let opts = ONodes::new(5, 3, &os, OptOrder::Seq);
+        let onodes = onodes_data();
+        assert_eq!(records(&opts), onodes, "incorrect options");
+    }
+
+    #[test]
+    fn test_from_options() {
+        let os: Vec<Vec<Uint>> = vec![
+            vec![0, 1, 3, 4],
+            vec![0, 2, 3, 4],
+            vec![0, 3],
+            vec![1, 3],
+            vec![2, 4],
+        ];
+        let opts = ONodes::new(5, 3, &os, OptOrder::Seq);
+        let size = opts.links.len();
+        if cfg!(feature = "masked-index") {
+            assert_eq!(opts.links.capacity(), size.next_power_of_two());
+        } else {
+            assert_eq!(opts.links.capacity(), size, "nodes not sized exactly");
+        }
+        let streamed =
+            ONodes::from_options(5, 3, os.iter().cloned(), 0, OptOrder::Seq);
+        assert_eq!(streamed, opts);
+        let mut starts = vec![0];
+        for o in &os {
+            starts.push(starts.last().unwrap() + o.len());
+        }
+        let items = os.concat();
+        let csr = ONodes::from_csr(5, 3, &starts, &items, OptOrder::Seq);
+        assert_eq!(csr, opts);
+        assert_eq!(csr.links.capacity(), opts.links.capacity());
+
+        // An option without primary items takes no nodes
+        let mut os = os;
+        os.insert(2, vec![3, 4]);
+        let mut skipped = ONodes::new(5, 3, &os, OptOrder::Seq);
+        assert_eq!(skipped.links.len(), size);
+        assert_eq!(skipped.links.capacity(), opts.links.capacity());
+        assert_eq!(*skipped.opt_count(), 6);
+    }
+
+    #[test]
+    fn test_from_spec() {
+        let spec_str = "
+| This is a comment
+p q r | x y
+
+p q x y
+p r x y
+| Another comment
+p x
+q x
+r y";
+        let spec = Spec::new(spec_str, false).unwrap();
+        let problem =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        assert_eq!(problem.items.nodes.as_slice(), inodes_data());
+        assert_eq!(records(&problem.opts), onodes_data());
+    }
+
+    #[test]
+    fn test_xc() {
+        use crate::Solver;
+        use crate::choose::*;
+        let items = INodes::new(7, 0, ItemOrder::Seq);
+        let os: Vec<Vec<Uint>> = vec![
+            vec![2, 4],
+            vec![0, 3, 6],
+            vec![1, 2, 5],
+            vec![0, 3, 5],
+            vec![1, 6],
+            vec![3, 4, 6],
+        ];
+        let opts = ONodes::new(7, 7, &os, OptOrder::Seq);
+        let items_init = items.clone();
+        let opts_init = opts.clone();
+        let mut problem = Problem::new(items, opts);
+        let mut solver = Solver::new(&mut problem);
+        let mut solutions: Vec<Vec<Int>> = Vec::new();
+        let mut expected = vec![vec![0, 3, 4]];
+        let mut i = 0;
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        while solver.next_solution(&mut chooser) {
+            assert!(i <= expected.len(), "too many solutions");
+            solver.fmt_solution();
+            solver.o.sort();
+            solutions.push(solver.o.clone());
+            i += 1;
+        }
+        solutions.sort();
+        expected.sort();
+        assert_eq!(solutions, expected, "wrong solutions");
+        assert_eq!(solver.problem.items, items_init, "items not backtracked");
+        assert_eq!(solver.problem.opts, opts_init, "options not backtracked");
+        assert!(
+            solver.l == 0 && solver.restart == false,
+            "initial state not restored"
+        );
+    }
+
+    #[test]
+    fn test_mems() {
+        use crate::Solver;
+        use crate::choose::*;
+        let os: Vec<Vec<Uint>> = vec![
+            vec![2, 4],
+            vec![0, 3, 6],
+            vec![1, 2, 5],
+            vec![0, 3, 5],
+            vec![1, 6],
+            vec![3, 4, 6],
+        ];
+        let mut problem =
+            make_problem(7, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        let mut solver = Solver::new(&mut problem);
+        let mut chooser = mrv_chooser(prefer_any(), no_tiebreak());
+        assert!(solver.next_solution(&mut chooser));
+        let found = solver.get_mems();
+        assert_eq!(solver.get_solution_mems(), found);
+        assert!(!solver.next_solution(&mut chooser));
+        let total = solver.get_mems();
+        if cfg!(feature = "mems") {
+            assert!(found > 0 && total > found);
+        } else {
+            assert_eq!(total, 0);
+        }
+        let init = make_problem(7, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        problem.updates = 0;
+        assert_eq!(problem, init, "mems compared");
+    }
+
+    #[test]
+    fn test_auto() {
+        use crate::testing::{assert_restored, langford_spec, search};
+        let spec = langford_spec(7);
+        let mut auto =
+            Auto::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        let Auto::Links16(narrow) = &mut auto else {
+            panic!("links not narrowed");
+        };
+        let mut full =
+            Problem::from_spec(&spec, OptOrder::Seq, ItemOrder::Seq).unwrap();
+        assert_eq!(narrow.opts.links.fields(), full.opts.links.fields());
+        let init = Problem::new(narrow.items.clone(), narrow.opts.clone());
+        let (solutions, nodes) = search(narrow);
+        assert_restored(narrow, &init);
+        assert_eq!((solutions.len(), nodes), (52, search(&mut full).1));
+        assert_eq!(search(&mut full).0, solutions);
+        assert_eq!(auto.count_solutions(), 52);
+    }
+
+    #[test]
+    fn test_auto_size() {
+        use crate::edit::Edit;
+        let os = vec![vec![0]; 17000];
+        let mut auto = Auto::new(1, 0, &os, OptOrder::Seq, ItemOrder::Seq);
+        if cfg!(feature = "32-bit") {
+            assert!(matches!(auto, Auto::Links(_)));
+        } else {
+            assert!(!matches!(auto, Auto::Links16(_) | Auto::Links(_)));
+        }
+        assert_eq!(auto.count_solutions(), 17000);
+        let spec = Spec::new(&format!("p{}", "\np".repeat(17000)), false);
+        let names = vec!["p".to_string()];
+        let opts =
+            ONodes::<u16>::from_spec(&spec.unwrap(), &names, OptOrder::Seq);
+        assert!(opts.is_err());
+        // The most options of one item that fit in u16 links
+        let starts: Vec<usize> = (0..=16382).collect();
+        let items = vec![0; 16382];
+        let opts =
+            ONodes::<u16>::from_csr(1, 1, &starts, &items, OptOrder::Seq);
+        let mut problem = Problem::new(INodes::new(1, 0, ItemOrder::Seq), opts);
+        assert_eq!(problem.opts.size(), u16::MAX_NODES as Uint);
+        assert!(problem.add_option(&[0]).is_err());
+        assert_eq!(*problem.opts.opt_count(), 16382);
+        problem.delete_option(0).unwrap();
+        assert_eq!(problem.add_option(&[0]).unwrap(), 16382);
+    }
+
+    pub(crate) fn inodes_data() -> Vec<INode> {
+        vec![
+            INode { left: 3, right: 1 },
+            INode { left: 0, right: 2 },
+            INode { left: 1, right: 3 },
+            INode { left: 2, right: 0 },
+            INode { left: 6, right: 5 },
+            INode { left: 4, right: 6 },
+            INode { left: 5, right: 4 },
+        ]
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct ONode {
+        hdr_info: Int,
+        up: Uint,
+        down: Uint,
+    }
+
+    fn records(opts: &ONodes) -> Vec<ONode> {
+        let fields = opts.links.fields().into_iter();
+        fields
+            .map(|(hdr_info, up, down)| ONode { hdr_info, up, down })
+            .collect()
+    }
+
+    fn onodes_data() -> Vec<ONode> {
+        vec![
+            ONode { hdr_info: 0, up: 0, down: 0 },
+            ONode { hdr_info: 3, up: 17, down: 7 },
+            ONode { hdr_info: 2, up: 20, down: 8 },
+            ONode { hdr_info: 2, up: 23, down: 13 },
+            ONode { hdr_info: 4, up: 21, down: 9 },
+            ONode { hdr_info: 3, up: 24, down: 10 },
+            ONode { hdr_info: 0, up: 0, down: 10 },
+            ONode { hdr_info: 1, up: 1, down: 12 },
+            ONode { hdr_info: 2, up: 2, down: 20 },
+            ONode { hdr_info: 4, up: 4, down: 14 },
+            ONode { hdr_info: 5, up: 5, down: 15 },
+            ONode { hdr_info: -1, up: 7, down: 15 },
+            ONode { hdr_info: 1, up: 7, down: 17 },
+            ONode { hdr_info: 3, up: 3, down: 23 },
+            ONode { hdr_info: 4, up: 9, down: 18 },
+            ONode { hdr_info: 5, up: 10, down: 24 },
+            ONode { hdr_info: -2, up: 12, down: 18 },
+            ONode { hdr_info: 1, up: 12, down: 1 },
+            ONode { hdr_info: 4, up: 14, down: 21 },
+            ONode { hdr_info: -3, up: 17, down: 21 },
+            ONode { hdr_info: 2, up: 8, down: 2 },
+            ONode { hdr_info: 4, up: 18, down: 4 },
+            ONode { hdr_info: -4, up: 20, down: 24 },
+            ONode { hdr_info: 3, up: 13, down: 3 },
+            ONode { hdr_info: 5, up: 15, down: 5 },
+            ONode { hdr_info: -5, up: 23, down: 0 },
+        ]
+    }
+}